@@ -0,0 +1,64 @@
+//! Inserting a clone image the way radios hand it to us: in small blocks, mostly in address order,
+//! with some regions skipped.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use freqm::sparse_mem::SparseMem;
+
+/// Block size used by the Anytone HT `R`/`W` commands
+const BLOCK: u64 = 16;
+
+/// 256 KiB of memory
+const SIZE: u64 = 0x4_0000;
+
+/// Download every block in order
+fn sequential() -> SparseMem {
+    let data = [0xffu8; BLOCK as usize];
+    let mut s = SparseMem::default();
+    for addr in (0..SIZE).step_by(BLOCK as usize) {
+        s.insert(addr, &data).unwrap();
+    }
+    s
+}
+
+/// Download with unused regions skipped: 1 KiB populated, 1 KiB gap
+fn with_gaps() -> SparseMem {
+    let data = [0xffu8; BLOCK as usize];
+    let mut s = SparseMem::default();
+    for addr in (0..SIZE).step_by(BLOCK as usize) {
+        if addr & 0x400 == 0 {
+            s.insert(addr, &data).unwrap();
+        }
+    }
+    s
+}
+
+/// Worst case for merging: every other block first, then fill in the holes
+fn interleaved() -> SparseMem {
+    let data = [0xffu8; BLOCK as usize];
+    let mut s = SparseMem::default();
+    for addr in (0..SIZE).step_by(2 * BLOCK as usize) {
+        s.insert(addr, &data).unwrap();
+    }
+    for addr in (BLOCK..SIZE).step_by(2 * BLOCK as usize) {
+        s.insert(addr, &data).unwrap();
+    }
+    s
+}
+
+fn bench(c: &mut Criterion) {
+    c.bench_function("insert sequential", |b| b.iter(|| black_box(sequential())));
+    c.bench_function("insert with gaps", |b| b.iter(|| black_box(with_gaps())));
+    c.bench_function("insert interleaved", |b| b.iter(|| black_box(interleaved())));
+
+    let s = with_gaps();
+    c.bench_function("get every block", |b| {
+        b.iter(|| {
+            for addr in (0..SIZE).step_by(BLOCK as usize) {
+                black_box(s.get(addr..addr + BLOCK));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
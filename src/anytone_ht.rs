@@ -1,7 +1,20 @@
 use super::sparse_mem::SparseMem;
+use super::memory_map::MemoryMap;
+use super::error::*;
+use snafu::{ensure, ResultExt};
 use std::ffi::OsStr;
+use std::io::{Read, Write};
 use std::time::Duration;
 
+/// Definition of the radio's memory, as far as it is known
+const MEMORY_MAP: &str = include_str!("../maps/anytone_termn8r.toml");
+
+/// Decoder for images returned by `download()`
+pub fn memory_map() -> MemoryMap {
+    // checked by the tests
+    MEMORY_MAP.parse().unwrap()
+}
+
 // Serial settings: 9600 8N1
 //
 // Programming software using a 500 ms timeout (via the windows apis). Unclear if it actually does
@@ -13,29 +26,29 @@ use std::time::Duration;
 // "PROGRAM": request program mode, sent by the programming software
 //
 //
-pub fn download<P: AsRef<OsStr>>(port_name: P) -> serialport::Result<SparseMem> {
-    let mut p = serialport::SerialPort::builder()
-        .baud_rate(9600)?
-        .data_bits(serialport::DataBits::Eight)?
-        .parity(serialport::Parity::None)?
-        .stop_bits(serialport::StopBits::One)?
+pub fn download<P: AsRef<OsStr>>(port_name: P) -> Result<SparseMem, FreqmError> {
+    let mut p = serialport::new(port_name.as_ref().to_string_lossy(), 9600)
+        .data_bits(serialport::DataBits::Eight)
+        .parity(serialport::Parity::None)
+        .stop_bits(serialport::StopBits::One)
         .timeout(Duration::from_millis(500))
-        .flow_control(serialport::FlowControl::None)?
-        .path(port_name.as_ref())
-        .open()?;
+        .flow_control(serialport::FlowControl::None)
+        .open()
+        .context(PortSnafu)?;
 
     let sparse_mem = SparseMem::default();
 
-    p.write_all(b"PROGRAM")?;
+    p.write_all(b"PROGRAM").context(SerialIoSnafu)?;
 
     {
         let mut b = [0u8; 3];
-        let c = p.read(&mut b)?;
-        assert_eq!(c, b.len());
+        let c = p.read(&mut b).context(SerialIoSnafu)?;
 
-        if [b'Q', b'X', 0x06] != b {
-            // bad, bail out
-        }
+        let expected = [b'Q', b'X', 0x06];
+        ensure!(
+            c == b.len() && expected == b,
+            ProtocolSnafu { expected: expected.to_vec(), got: b[..c].to_vec() }
+        );
     }
 
     // -> 02
@@ -0,0 +1,386 @@
+//! freqm's own channel file: every detail of the common channel model, as JSON or YAML
+//!
+//! Unlike the radio formats in `formats`, a channel file holds everything freqm knows about each
+//! channel, so it reads back without losing anything. It's meant for other tools to read, and for
+//! reviewing and editing channel lists by hand:
+//!
+//! ```yaml
+//! version: 1
+//! channels:
+//!   - number: 1
+//!     name: W1BOS Boston
+//!     group: 2m
+//!     callsign: W1BOS
+//!     output_freq: "146.610"
+//!     input_freq: "146.010"
+//!     modes: [Fm]
+//!     tone_in: "88.5"
+//!     site: { name: Prudential, location: "Boston, MA", lat: 42.347, lon: -71.082 }
+//!     place: { town: Boston, state: MA, county: Suffolk }
+//!     provenance: { sources: [ne, icom], fields: { input_freq: ne, tone_in: icom } }
+//!   - callsign: W1DMR
+//!     output_freq: "446.500"
+//!     input_freq: "441.500"
+//!     modes: [Dmr]
+//!     dmr: { color_code_in: 1, color_code_out: 1 }
+//! ```
+//!
+//! Frequencies are in MHz, and written as strings so they keep their exact value. Tones (CTCSS,
+//! DCS or NAC) are written as `Code` writes them; DMR color codes go in `dmr` instead, and D-STAR
+//! repeaters have their module in `dstar`. `number`, `name` and `group` are the channel's place in
+//! the list it was written as (see `layout`), and are ignored when reading. Files made from a
+//! merged database (see `repeater_db`) say which listing each value came from.
+//!
+//! Unknown fields are errors, so misspelled ones aren't silently dropped. Files with a `version`
+//! newer than `VERSION` are rejected rather than read without the details they add.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::formats::Channels;
+use crate::icom_id51a::module_of;
+use crate::import::{ImportReport, OnError, RowIssue};
+use crate::layout::Plan;
+use crate::repeater_db::{Entry, RepeaterDb, Source, Sourced};
+use crate::{Code, Mode, Place, Repeater, Site, Status};
+
+/// The schema version written, and the newest one read
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelFile {
+    pub version: u32,
+    pub channels: Vec<ChannelRecord>,
+}
+
+/// A channel: a `Repeater`, with its place in a channel list and where its details came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    pub callsign: String,
+    /// MHz
+    pub output_freq: decimal::d128,
+    /// MHz, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_freq: Option<decimal::d128>,
+    #[serde(default)]
+    pub modes: Vec<Mode>,
+    #[serde(default, skip_serializing_if = "is_on")]
+    pub status: Status,
+
+    /// CTCSS tone, DCS code or NAC needed on the input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone_in: Option<Code>,
+    /// CTCSS tone, DCS code or NAC sent on the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tone_out: Option<Code>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dmr: Option<Dmr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dstar: Option<DStar>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub irlp_node: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub echolink_node: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<chrono::NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<Site>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub place: Option<Place>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+}
+
+fn is_on(status: &Status) -> bool {
+    *status == Status::On
+}
+
+/// A DMR repeater's color codes, in place of `tone_in` and `tone_out`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dmr {
+    /// Needed on the input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_code_in: Option<u8>,
+    /// Sent on the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_code_out: Option<u8>,
+}
+
+/// A D-STAR repeater's module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DStar {
+    /// 'A' to 'D'. Written from the listing when it gives one, and otherwise as the band's usual
+    /// module: 'A' on 23cm, 'B' on 70cm, 'C' on 2m.
+    pub module: char,
+}
+
+/// Where a merged repeater's details came from
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Provenance {
+    /// Every listing with a record of the repeater
+    pub sources: Vec<Source>,
+    /// The listing each field's value came from, by field name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, Source>,
+    /// Values from other listings that disagree, as `repeater_db::Conflict` writes them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+impl From<&Repeater> for ChannelRecord {
+    fn from(r: &Repeater) -> Self {
+        let color_code = |code| match code {
+            Some(Code::ColorCode(c)) => Some(c),
+            _ => None,
+        };
+        let tone = |code: Option<Code>| code.filter(|c| !matches!(c, Code::ColorCode(_)));
+        let dmr = match (color_code(r.code_in()), color_code(r.code_out())) {
+            (None, None) => None,
+            (color_code_in, color_code_out) => Some(Dmr { color_code_in, color_code_out }),
+        };
+        let dstar = r
+            .modes()
+            .contains(&Mode::DStar)
+            .then(|| DStar { module: module_of(r) });
+
+        ChannelRecord {
+            number: None,
+            name: None,
+            group: None,
+            callsign: r.callsign().to_owned(),
+            output_freq: r.output_freq(),
+            input_freq: r.input_freq(),
+            modes: r.modes().to_vec(),
+            status: r.status(),
+            tone_in: tone(r.code_in()),
+            tone_out: tone(r.code_out()),
+            dmr,
+            dstar,
+            irlp_node: r.irlp_node(),
+            echolink_node: r.echolink_node(),
+            updated: r.updated(),
+            site: r.site().cloned(),
+            place: r.place().cloned(),
+            comment: r.comment().map(str::to_owned),
+            provenance: None,
+        }
+    }
+}
+
+impl ChannelRecord {
+    /// The record's repeater, checking the parts of it serde can't
+    pub fn repeater(&self) -> Result<Repeater, FreqmError> {
+        let invalid = |column, reason: &str| ChannelRecordSnafu { reason }.build().column(column);
+        let freq = |mhz: decimal::d128, column| {
+            if mhz.is_nan() {
+                return Err(FrequencySnafu { value: mhz.to_string() }.build().column(column));
+            }
+            Ok(mhz)
+        };
+        let output_freq = freq(self.output_freq, "output_freq")?;
+        let input_freq = self.input_freq.map(|f| freq(f, "input_freq")).transpose()?;
+
+        for (tone, column) in [(self.tone_in, "tone_in"), (self.tone_out, "tone_out")] {
+            if let Some(Code::ColorCode(_)) = tone {
+                return Err(invalid(column, "color codes go in dmr"));
+            }
+        }
+        let dmr = self.dmr.unwrap_or(Dmr { color_code_in: None, color_code_out: None });
+        let code = |tone: Option<Code>, color_code: Option<u8>, side| match (tone, color_code) {
+            (Some(_), Some(_)) => Err(invalid("dmr", &format!("a channel has an {} tone or color code, not both", side))),
+            (_, Some(c)) if c > 15 => Err(CodeSnafu { value: format!("CC{}", c) }.build().column("dmr")),
+            (tone, color_code) => Ok(tone.or(color_code.map(Code::ColorCode))),
+        };
+        let code_in = code(self.tone_in, dmr.color_code_in, "input")?;
+        let code_out = code(self.tone_out, dmr.color_code_out, "output")?;
+        let dstar_module = match self.dstar {
+            Some(DStar { module }) => {
+                if !self.modes.contains(&Mode::DStar) {
+                    return Err(invalid("dstar", "not a D-STAR channel"));
+                }
+                let module = module.to_ascii_uppercase();
+                if !('A'..='D').contains(&module) {
+                    return Err(DStarModuleSnafu { value: module.to_string() }.build().column("dstar"));
+                }
+                Some(module)
+            },
+            None => None,
+        };
+
+        Ok(Repeater {
+            callsign: self.callsign.trim().to_owned(),
+            output_freq,
+            input_freq,
+            modes: self.modes.clone(),
+            status: self.status,
+            irlp_node: self.irlp_node,
+            echolink_node: self.echolink_node,
+            updated: self.updated,
+            code_in,
+            code_out,
+            dstar_module,
+            site: self.site.clone(),
+            place: self.place.clone(),
+            comment: self.comment.clone(),
+        })
+    }
+}
+
+impl From<&Entry> for Provenance {
+    fn from(e: &Entry) -> Self {
+        fn source<T>(s: &Option<Sourced<T>>) -> Option<Source> {
+            s.as_ref().map(|s| s.source)
+        }
+        // color codes are written in `dmr`, other codes as tones
+        let code = |s: &Option<Sourced<Code>>, dmr: bool| {
+            s.as_ref().filter(|s| matches!(s.value, Code::ColorCode(_)) == dmr).map(|s| s.source)
+        };
+
+        let fields = [
+            ("input_freq", source(&e.input_freq)),
+            ("modes", source(&e.modes)),
+            ("status", source(&e.status)),
+            ("tone_in", code(&e.code_in, false)),
+            ("tone_out", code(&e.code_out, false)),
+            ("dmr", code(&e.code_in, true).or(code(&e.code_out, true))),
+            ("dstar", source(&e.dstar_module)),
+            ("irlp_node", source(&e.irlp_node)),
+            ("echolink_node", source(&e.echolink_node)),
+            ("updated", source(&e.updated)),
+            ("site", source(&e.site)),
+            ("place", source(&e.place)),
+            ("comment", source(&e.comment)),
+        ];
+        Provenance {
+            sources: e.sources.clone(),
+            fields: fields.into_iter().filter_map(|(field, s)| Some((field.to_owned(), s?))).collect(),
+            conflicts: e.conflicts().iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+impl ChannelFile {
+    pub fn new(channels: Vec<ChannelRecord>) -> Self {
+        ChannelFile { version: VERSION, channels }
+    }
+
+    /// The channels of a plan, with their numbers, names and groups
+    pub fn from_plan(plan: &Plan<'_>) -> Self {
+        let channels = plan
+            .channels
+            .iter()
+            .map(|c| ChannelRecord {
+                number: Some(c.number),
+                name: c.name.clone(),
+                group: plan.group_of(c).map(|g| g.name.clone()),
+                ..c.repeater.into()
+            })
+            .collect();
+        ChannelFile::new(channels)
+    }
+
+    /// The merged repeaters, with where each value came from
+    pub fn from_db(db: &RepeaterDb) -> Self {
+        let channels = db
+            .entries()
+            .iter()
+            .map(|e| ChannelRecord { provenance: Some(e.into()), ..(&e.repeater()).into() })
+            .collect();
+        ChannelFile::new(channels)
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<Self, FreqmError> {
+        let file: ChannelFile = serde_json::from_slice(bytes).context(JsonSnafu)?;
+        file.checked()
+    }
+
+    pub fn from_yaml(bytes: &[u8]) -> Result<Self, FreqmError> {
+        let file: ChannelFile = serde_norway::from_slice(bytes).context(YamlSnafu)?;
+        file.checked()
+    }
+
+    fn checked(self) -> Result<Self, FreqmError> {
+        if self.version > VERSION {
+            return Err(ChannelFileVersionSnafu { found: self.version, supported: VERSION }.build().into());
+        }
+        Ok(self)
+    }
+
+    pub fn write_json<W: Write>(&self, mut w: W) -> Result<(), FreqmError> {
+        serde_json::to_writer_pretty(&mut w, self).context(JsonSnafu)?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    pub fn write_yaml<W: Write>(&self, w: W) -> Result<(), FreqmError> {
+        serde_norway::to_writer(w, self).context(YamlSnafu)?;
+        Ok(())
+    }
+
+    /// Read a channel file, as YAML if it's named ".yaml" or ".yml" and as JSON otherwise
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FreqmError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        let file = if is_yaml(path) { Self::from_yaml(&bytes) } else { Self::from_json(&bytes) };
+        file.map_err(|e| e.with_file(path))
+    }
+
+    /// Write a channel file, as YAML if it's named ".yaml" or ".yml" and as JSON otherwise
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), FreqmError> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        let mut w = std::io::BufWriter::new(file);
+        let written = if is_yaml(path) { self.write_yaml(&mut w) } else { self.write_json(&mut w) };
+        written.map_err(|e| e.with_file(path))?;
+        w.flush().context(IoSnafu { path: Some(path.to_owned()) })
+    }
+
+    /// Every record's repeater. Records are numbered from 1 in errors.
+    pub fn repeaters(&self, on_error: OnError) -> Result<Channels, FreqmError> {
+        let mut repeaters = Vec::new();
+        let mut report = ImportReport::default();
+        for (i, record) in self.channels.iter().enumerate() {
+            match record.repeater() {
+                Ok(r) => {
+                    repeaters.push(r);
+                    report.records.push(i as u64 + 1);
+                },
+                Err(e) => {
+                    let error = e.with_record(i as u64 + 1);
+                    if on_error == OnError::Abort {
+                        return Err(error);
+                    }
+                    // documents aren't read line by line
+                    report.skipped.push(RowIssue { line: 0, error, raw: String::new() });
+                },
+            }
+        }
+        report.imported = repeaters.len();
+        Ok((repeaters, report))
+    }
+}
+
+fn is_yaml(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("yaml") || e.eq_ignore_ascii_case("yml"))
+}
@@ -1,144 +1,981 @@
+use serde::{Serialize, Deserialize};
+
+use crate::error::*;
+use crate::geo::{Grid, LatLon, Located};
+use crate::import::{import_deserialize, ImportReport, OnError};
+use crate::ne_repeater::parse_mhz;
+use crate::{mhz_to_f64, Code, Mode, Place, Repeater, Site, Status};
+
 // Supported by Kenwood supplied MCP (memory control program) software as an
 // import format
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-struct ArrlTravelPlusRow {
-    sequence_number: String,
-    country: String,
-    region: String,
-    state: String,
-    location: String,
-    output_frequency: String,
-    input_frequency: String,
-    call_sign: String,
-    repeater_notes: String,
-    ctcss_tones: String,
-    sponsor: String,
+pub struct ArrlTravelPlusRow {
+    pub sequence_number: String,
+    pub country: String,
+    pub region: String,
+    pub state: String,
+    pub location: String,
+    pub output_frequency: String,
+    pub input_frequency: String,
+    pub call_sign: String,
+    pub repeater_notes: String,
+    pub ctcss_tones: String,
+    pub sponsor: String,
 }
 
 // Chrip format, offset based.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-struct ChirpRow {
-    location: String,
-    name: String,
-    frequency: String,
-    duplex: String,
-    offset: String,
-    tone: String,
-    r_tone_freq: String,
-    c_tone_freq: String,
-    dtcs_code: String,
-    dtcs_polarity: String,
-    rx_dtcs_code: String,
-    cross_mode: String,
-    mode: String,
-    t_step: String,
-    skip: String,
-    power: String,
-    comment: String,
-    ur_call: String,
-    rpt1_call: String,
-    rpt2_call: String,
-    dv_code: String,
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChirpRow {
+    pub location: String,
+    pub name: String,
+    pub frequency: String,
+    pub duplex: String,
+    pub offset: String,
+    pub tone: String,
+    pub r_tone_freq: String,
+    pub c_tone_freq: String,
+    pub dtcs_code: String,
+    pub dtcs_polarity: String,
+    pub rx_dtcs_code: String,
+    pub cross_mode: String,
+    pub mode: String,
+    pub t_step: String,
+    pub skip: String,
+    pub power: String,
+    pub comment: String,
+    pub ur_call: String,
+    pub rpt1_call: String,
+    pub rpt2_call: String,
+    pub dv_code: String,
 }
 
 // tsv (tab seperated)
 // Wn	World Region	Cn	Country	Gn	Group	Callsign	Gateway	Lockout	Name	Sub Name	Frequency	Shift	Offset	Mode	Uplink Tone	Downlink Tone	Position	Lat DD	Lat MM.mm	N/S	Lon DDD	Lon MM.mm	E/W	Time Zone	TH-D74A	TH-D74E	TH-D74	Aux 1	Aux 2	Aux 3
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-struct KenwoodTh74aRow {
-    wn: String,
-    world_region: String,
-    cn: String,
-    country: String,
-    gn: String,
-    group: String,
-    callsign: String,
-    gateway: String,
-    lockout: String,
-    name: String,
-    sub_name: String,
-    frequency: String,
-    shift: String,
-    offset: String,
-    mode: String,
-    uplink_tone: String,
-    downlink_tone: String,
-    position: String,
-    lat_dd: String,
-    lat_mm_mm: String,
-    n_s: String,
-    lon_ddd: String,
-    lon_mm_mm: String,
-    e_w: String,
-    time_zone: String,
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KenwoodTh74aRow {
+    pub wn: String,
+    pub world_region: String,
+    pub cn: String,
+    pub country: String,
+    pub gn: String,
+    pub group: String,
+    pub callsign: String,
+    pub gateway: String,
+    pub lockout: String,
+    pub name: String,
+    pub sub_name: String,
+    pub frequency: String,
+    pub shift: String,
+    pub offset: String,
+    pub mode: String,
+    pub uplink_tone: String,
+    pub downlink_tone: String,
+    pub position: String,
+    pub lat_dd: String,
+    pub lat_mm_mm: String,
+    pub n_s: String,
+    pub lon_ddd: String,
+    pub lon_mm_mm: String,
+    pub e_w: String,
+    pub time_zone: String,
+    // older lists stop at "Time Zone"
+    #[serde(default)]
+    pub th_d74a: String,
+    #[serde(default)]
+    pub th_d74e: String,
+    #[serde(default)]
+    pub th_d74: String,
+    #[serde(default)]
+    pub aux_1: String,
+    #[serde(default)]
+    pub aux_2: String,
+    #[serde(default)]
+    pub aux_3: String,
 }
 
 /// Boston Marathon ICS (Incident Command System) format, exported from the PDF using Tabula
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-struct BostonMarathonIcsRow {
-    ch_number: String,
-    function: String,
-    channel_name: String,
-    assignment: String,
-    rx_freq_wn: String,
-    rx_tone_nac: String,
-    tx_freq_wn: String,
-    tx_tone_nac: String,
-    mode_a_or_d: String,
-    remarks: String,
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BostonMarathonIcsRow {
+    pub ch_number: String,
+    pub function: String,
+    pub channel_name: String,
+    pub assignment: String,
+    pub rx_freq_wn: String,
+    pub rx_tone_nac: String,
+    pub tx_freq_wn: String,
+    pub tx_tone_nac: String,
+    pub mode_a_or_d: String,
+    pub remarks: String,
 }
 
 /// CPS MD-UV390 format, exported from the CPS software
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-struct Uv390Row {
-    channel_mode: String,
-    channel_name: String,
-    rx_freq_mhz: String,
-    tx_freq_mhz: String,
-    band_width: String,
-    scan_list: String,
-    squelch: String,
-    rx_ref_freq: String,
-    tx_ref_freq: String,
-    tot_s: String,
-    tot_rekey_delay_s: String,
-    power: String,
-    admit_criteria: String,
-    auto_scan: String,
-    rx_only: String,
-    lone_worker: String,
-    vox: String,
-    allow_talkaround: String,
-    send_gps_info: String,
-    recv_gps_info: String,
-    private_call_confirmed: String,
-    emergency_alarm_ack: String,
-    data_call_confirmed: String,
-    allow_interrupt: String,
-    dcdm_switch: String,
-    leader_ms: String,
-    emergency_system: String,
-    contact_name: String,
-    group_list: String,
-    color_code: String,
-    repeater_slot: String,
-    in_call_criteria: String,
-    privacy: String,
-    privacy_number: String,
-    gps_system: String,
-    ctcss_dcs_dec: String,
-    ctcss_dcs_enc: String,
-    rx_signaling_system: String,
-    tx_signaling_system: String,
-    qt_reverse: String,
-    non_qt_dqt_turn_off_freq: String,
-    display_ptt_id: String,
-    reverse_burst_turn_off_code: String,
-    decode_1: String,
-    decode_2: String,
-    decode_3: String,
-    decode_4: String,
-    decode_5: String,
-    decode_6: String,
-    decode_7: String,
-    decode_8: String,
+pub struct Uv390Row {
+    pub channel_mode: String,
+    pub channel_name: String,
+    pub rx_freq_mhz: String,
+    pub tx_freq_mhz: String,
+    pub band_width: String,
+    pub scan_list: String,
+    pub squelch: String,
+    pub rx_ref_freq: String,
+    pub tx_ref_freq: String,
+    pub tot_s: String,
+    pub tot_rekey_delay_s: String,
+    pub power: String,
+    pub admit_criteria: String,
+    pub auto_scan: String,
+    pub rx_only: String,
+    pub lone_worker: String,
+    pub vox: String,
+    pub allow_talkaround: String,
+    pub send_gps_info: String,
+    pub recv_gps_info: String,
+    pub private_call_confirmed: String,
+    pub emergency_alarm_ack: String,
+    pub data_call_confirmed: String,
+    pub allow_interrupt: String,
+    pub dcdm_switch: String,
+    pub leader_ms: String,
+    pub emergency_system: String,
+    pub contact_name: String,
+    pub group_list: String,
+    pub color_code: String,
+    pub repeater_slot: String,
+    pub in_call_criteria: String,
+    pub privacy: String,
+    pub privacy_number: String,
+    pub gps_system: String,
+    pub ctcss_dcs_dec: String,
+    pub ctcss_dcs_enc: String,
+    pub rx_signaling_system: String,
+    pub tx_signaling_system: String,
+    pub qt_reverse: String,
+    pub non_qt_dqt_turn_off_freq: String,
+    pub display_ptt_id: String,
+    pub reverse_burst_turn_off_code: String,
+    pub decode_1: String,
+    pub decode_2: String,
+    pub decode_3: String,
+    pub decode_4: String,
+    pub decode_5: String,
+    pub decode_6: String,
+    pub decode_7: String,
+    pub decode_8: String,
+}
+
+/// AnyTone AT-878UV II format, exported from the CPS software (version 3.06)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct At878Row {
+    pub no: String,
+    pub channel_name: String,
+    pub receive_frequency: String,
+    pub transmit_frequency: String,
+    /// "A-Analog", "D-Digital", "A+D TX A" or "D+A TX D"
+    pub channel_type: String,
+    pub transmit_power: String,
+    /// "25K" or "12.5K"
+    pub band_width: String,
+    /// "Off", a CTCSS tone ("88.5") or a DCS code with its polarity ("D023N")
+    pub ctcss_dcs_decode: String,
+    pub ctcss_dcs_encode: String,
+    pub contact: String,
+    pub contact_call_type: String,
+    pub contact_tg_dmr_id: String,
+    pub radio_id: String,
+    pub busy_lock_tx_permit: String,
+    pub squelch_mode: String,
+    pub optional_signal: String,
+    pub dtmf_id: String,
+    pub two_tone_id: String,
+    pub five_tone_id: String,
+    pub ptt_id: String,
+    pub rx_color_code: String,
+    pub slot: String,
+    pub scan_list: String,
+    pub receive_group_list: String,
+    pub ptt_prohibit: String,
+    pub reverse: String,
+    pub simplex_tdma: String,
+    pub slot_suit: String,
+    pub aes_digital_encryption: String,
+    pub digital_encryption: String,
+    pub call_confirmation: String,
+    pub talk_around: String,
+    pub work_alone: String,
+    pub custom_ctcss: String,
+    pub two_tone_decode: String,
+    pub ranging: String,
+    pub through_mode: String,
+    pub aprs_rx: String,
+    pub analog_aprs_ptt_mode: String,
+    pub digital_aprs_ptt_mode: String,
+    pub aprs_report_type: String,
+    pub digital_aprs_report_channel: String,
+    pub correct_frequency_hz: String,
+    pub sms_confirmation: String,
+    pub exclude_from_roaming: String,
+    pub dmr_mode: String,
+    pub data_ack_disable: String,
+    pub r5tone_bot: String,
+    pub r5tone_eot: String,
+    pub auto_scan: String,
+    pub ana_aprs_mute: String,
+    pub send_talker_alias: String,
+    pub ana_aprs_tx_path: String,
+    pub arc4: String,
+    pub ex_emg_kind: String,
+    pub tx_cc: String,
+}
+
+/// Chirp column names, in order
+pub const CHIRP_COLUMNS: [&str; 21] = [
+    "Location", "Name", "Frequency", "Duplex", "Offset", "Tone", "rToneFreq", "cToneFreq", "DtcsCode", "DtcsPolarity",
+    "RxDtcsCode", "CrossMode", "Mode", "TStep", "Skip", "Power", "Comment", "URCALL", "RPT1CALL", "RPT2CALL", "DVCODE",
+];
+
+/// Boston Marathon ICS column names, in order. Tabula pads the frequency columns' names with
+/// spaces ("RX Freq      N or W").
+pub const ICS_COLUMNS: [&str; 10] = [
+    "Ch #", "Function", "Channel Name/Trunked Radio System Talkgroup", "Assignment", "RX Freq N or W", "RX Tone/NAC",
+    "TX Freq N or W", "TX Tone/NAC", "Mode A or D", "Remarks",
+];
+
+/// MD-UV390 CPS column names, in order
+pub const UV390_COLUMNS: [&str; 51] = [
+    "Channel Mode", "Channel Name", "RX Frequency(MHz)", "TX Frequency(MHz)", "Band Width", "Scan List", "Squelch",
+    "RX Ref Frequency", "TX Ref Frequency", "TOT[s]", "TOT Rekey Delay[s]", "Power", "Admit Criteria", "Auto Scan",
+    "Rx Only", "Lone Worker", "VOX", "Allow TalkAround", "Send GPS Info", "Receive GPS Info",
+    "Private Call Confirmed", "Emergency Alarm Ack", "Data Call Confirmed", "Allow Interrupt", "DCDM Switch",
+    "Leader/MS", "Emergency System", "Contact Name", "Group List", "Color Code", "Repeater Slot", "In Call Criteria",
+    "Privacy", "Privacy No.", "GPS System", "CTCSS/DCS Dec", "CTCSS/DCS Enc", "Rx Signaling System",
+    "Tx Signaling System", "QT Reverse", "Non-QT/DQT Turn-off Freq", "Display PTT ID", "Reverse Burst/Turn-off Code",
+    "Decode 1", "Decode 2", "Decode 3", "Decode 4", "Decode 5", "Decode 6", "Decode 7", "Decode 8",
+];
+
+/// Values for the MD-UV390 columns we don't fill in, from a channel made with the CPS
+const UV390_DEFAULTS: [&str; 51] = [
+    "Analog", "", "", "", "25", "None", "Normal", "Low", "Low", "60", "0", "High", "Always", "Off", "Off", "Off",
+    "Off", "Off", "Off", "Off", "Off", "Off", "Off", "Off", "Off", "Off", "None", "None", "None", "1", "1", "Always",
+    "None", "1", "None", "None", "None", "Off", "Off", "180", "259.2", "Off", "Off", "Off", "Off", "Off", "Off",
+    "Off", "Off", "Off", "Off",
+];
+
+/// AT-878UV II CPS column names, in order
+pub const AT878_COLUMNS: [&str; 56] = [
+    "No.", "Channel Name", "Receive Frequency", "Transmit Frequency", "Channel Type", "Transmit Power", "Band Width",
+    "CTCSS/DCS Decode", "CTCSS/DCS Encode", "Contact", "Contact Call Type", "Contact TG/DMR ID", "Radio ID",
+    "Busy Lock/TX Permit", "Squelch Mode", "Optional Signal", "DTMF ID", "2Tone ID", "5Tone ID", "PTT ID",
+    "RX Color Code", "Slot", "Scan List", "Receive Group List", "PTT Prohibit", "Reverse", "Simplex TDMA",
+    "Slot Suit", "AES Digital Encryption", "Digital Encryption", "Call Confirmation", "Talk Around(Simplex)",
+    "Work Alone", "Custom CTCSS", "2TONE Decode", "Ranging", "Through Mode", "APRS RX", "Analog APRS PTT Mode",
+    "Digital APRS PTT Mode", "APRS Report Type", "Digital APRS Report Channel", "Correct Frequency[Hz]",
+    "SMS Confirmation", "Exclude channel from roaming", "DMR MODE", "DataACK Disable", "R5toneBot", "R5ToneEot",
+    "Auto Scan", "Ana Aprs Mute", "Send Talker Alias", "AnaAprsTxPath", "ARC4", "ex_emg_kind", "TxCC",
+];
+
+/// AT-878UV II CPS zone list column names, in order
+pub const AT878_ZONE_COLUMNS: [&str; 11] = [
+    "No.", "Zone Name", "Zone Channel Member", "Zone Channel Member RX Frequency",
+    "Zone Channel Member TX Frequency", "A Channel", "A Channel RX Frequency", "A Channel TX Frequency", "B Channel",
+    "B Channel RX Frequency", "B Channel TX Frequency",
+];
+
+/// AT-878UV II CPS scan list column names, in order
+pub const AT878_SCAN_LIST_COLUMNS: [&str; 18] = [
+    "No.", "Scan List Name", "Scan Channel Member", "Scan Channel Member RX Frequency",
+    "Scan Channel Member TX Frequency", "Scan Mode", "Priority Channel Select", "Priority Channel 1",
+    "Priority Channel 1 RX Frequency", "Priority Channel 1 TX Frequency", "Priority Channel 2",
+    "Priority Channel 2 RX Frequency", "Priority Channel 2 TX Frequency", "Revert Channel", "Look Back Time A[s]",
+    "Look Back Time B[s]", "Dropout Delay Time[s]", "Dwell Time[s]",
+];
+
+/// Values for the AT-878UV II scan list columns after the members: no priority channels, the CPS's
+/// timings
+pub const AT878_SCAN_LIST_DEFAULTS: [&str; 13] =
+    ["Off", "Off", "Off", "", "", "Off", "", "", "Selected", "2.0", "3.0", "3.1", "3.1"];
+
+/// Most channels in an AT-878UV II scan list
+pub const AT878_SCAN_LIST_LEN: usize = 50;
+
+/// Longest channel, zone or scan list name the AT-878UV II takes
+pub const AT878_NAME_LEN: usize = 16;
+
+/// Values for the AT-878UV II columns we don't fill in, from a channel exported by the CPS
+const AT878_DEFAULTS: [&str; 56] = [
+    "", "", "", "", "A-Analog", "High", "25K", "Off", "Off", "Contact1", "Group Call", "12345678", "My Radio", "Off",
+    "Carrier", "Off", "1", "1", "1", "Off", "1", "1", "None", "None", "Off", "Off", "Off", "Off",
+    "Normal Encryption", "Off", "Off", "Off", "Off", "131.8", "1", "Off", "Off", "Off", "Off", "Off", "Off", "1",
+    "0", "Off", "0", "1", "1", "0", "0", "0", "0", "0", "0", "0", "0", "1",
+];
+
+/// Kenwood TH-D74 column names, in order
+pub const KENWOOD_COLUMNS: [&str; 31] = [
+    "Wn", "World Region", "Cn", "Country", "Gn", "Group", "Callsign", "Gateway", "Lockout", "Name", "Sub Name",
+    "Frequency", "Shift", "Offset", "Mode", "Uplink Tone", "Downlink Tone", "Position", "Lat DD", "Lat MM.mm", "N/S",
+    "Lon DDD", "Lon MM.mm", "E/W", "Time Zone", "TH-D74A", "TH-D74E", "TH-D74", "Aux 1", "Aux 2", "Aux 3",
+];
+
+impl KenwoodTh74aRow {
+    /// Fill in the position columns, as `icom_id51a::ChannelLine::set_position()` does
+    pub fn set_position(&mut self, position: Option<LatLon>, exact: bool) {
+        let split = |value: f64| {
+            let deg = value.abs().trunc();
+            (format!("{}", deg), format!("{:05.2}", (value.abs() - deg) * 60.0))
+        };
+
+        self.position = crate::icom_id51a::position_column(position, exact);
+        match position {
+            Some(pos) => {
+                (self.lat_dd, self.lat_mm_mm) = split(pos.lat);
+                self.n_s = if pos.lat < 0.0 { "S" } else { "N" }.to_owned();
+                (self.lon_ddd, self.lon_mm_mm) = split(pos.lon);
+                self.lon_ddd = format!("{:0>3}", self.lon_ddd);
+                self.e_w = if pos.lon < 0.0 { "W" } else { "E" }.to_owned();
+            }
+            None => {
+                for column in [
+                    &mut self.lat_dd,
+                    &mut self.lat_mm_mm,
+                    &mut self.n_s,
+                    &mut self.lon_ddd,
+                    &mut self.lon_mm_mm,
+                    &mut self.e_w,
+                ] {
+                    column.clear();
+                }
+            }
+        }
+    }
+
+    /// Degrees and decimal minutes, with the hemisphere, to signed decimal degrees
+    fn coordinate(deg: &str, min: &str, hemisphere: &str, negative: &str) -> Option<f64> {
+        let deg: f64 = deg.trim().parse().ok()?;
+        let min: f64 = min.trim().parse().ok()?;
+        let value = deg + min / 60.0;
+        Some(if hemisphere.trim().eq_ignore_ascii_case(negative) { -value } else { value })
+    }
+}
+
+impl Located for KenwoodTh74aRow {
+    /// `None` when the "Position" column is "None" or the coordinates are missing
+    fn position(&self) -> Option<LatLon> {
+        if self.position.eq_ignore_ascii_case("none") {
+            return None;
+        }
+
+        let lat = Self::coordinate(&self.lat_dd, &self.lat_mm_mm, &self.n_s, "S")?;
+        let lon = Self::coordinate(&self.lon_ddd, &self.lon_mm_mm, &self.e_w, "W")?;
+        LatLon::new(lat, lon)
+    }
+}
+
+/// Read a Kenwood TH-D74 repeater list (tab separated, with a header row)
+pub fn read_kenwood_tsv<P: AsRef<std::path::Path>>(
+    path: P,
+    on_error: OnError,
+) -> Result<(Vec<KenwoodTh74aRow>, ImportReport), FreqmError> {
+    let path = path.as_ref();
+    let mut tsv = ::csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| FreqmError::from(e).with_file(path))?;
+
+    let (rows, mut report) = import_deserialize(&mut tsv, on_error).map_err(|e| e.with_file(path))?;
+    report.set_file(path);
+    Ok((rows, report))
+}
+
+impl std::convert::TryFrom<&KenwoodTh74aRow> for Repeater {
+    type Error = FreqmError;
+
+    fn try_from(row: &KenwoodTh74aRow) -> Result<Self, Self::Error> {
+        let freq = |text: &str, column| {
+            parse_mhz(text.trim()).ok_or_else(|| FrequencySnafu { value: text }.build().column(column))
+        };
+        let output_freq = freq(&row.frequency, "Frequency")?;
+        let input_freq = match row.shift.trim() {
+            "-" => output_freq - freq(&row.offset, "Offset")?,
+            "+" => output_freq + freq(&row.offset, "Offset")?,
+            "" => output_freq,
+            _ => return Err(OffsetKindSnafu { value: &row.shift }.build().column("Shift")),
+        };
+        let mode = match row.mode.trim() {
+            "DV" => Mode::DStar,
+            "FM" => Mode::Fm,
+            "NFM" | "FM-N" => Mode::Nfm,
+            _ => return Err(ModeSnafu { value: &row.mode }.build().column("Mode")),
+        };
+
+        // "Uplink Tone" is "On" when the "Downlink Tone" frequency is sent
+        let code_in = match row.uplink_tone.trim() {
+            "" | "Off" => None,
+            "On" => Some(
+                row.downlink_tone
+                    .parse::<Code>()
+                    .map_err(|_| CodeSnafu { value: &row.downlink_tone }.build().column("Downlink Tone"))?,
+            ),
+            _ => return Err(CodeSnafu { value: &row.uplink_tone }.build().column("Uplink Tone")),
+        };
+
+        Ok(Repeater {
+            callsign: row.callsign.split_whitespace().next().unwrap_or_default().to_owned(),
+            output_freq,
+            input_freq: Some(input_freq),
+            modes: vec![mode],
+            status: if row.lockout.trim().eq_ignore_ascii_case("on") { Status::Off } else { Status::On },
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in,
+            code_out: None,
+            dstar_module: Some(mode)
+                .filter(|&m| m == Mode::DStar)
+                .and_then(|_| crate::icom_id51a::module_in_call_sign(&row.callsign)),
+            site: row.position().map(|p| {
+                let site = Site::new(&row.name, format!("{}, {}", row.name, row.sub_name), p);
+                if row.position.eq_ignore_ascii_case("approximate") { site.approximate() } else { site }
+            }),
+            place: Place::new(&row.name, &row.sub_name, ""),
+            comment: None,
+        })
+    }
+}
+
+impl From<&Repeater> for KenwoodTh74aRow {
+    fn from(r: &Repeater) -> Self {
+        let output = r.output_freq();
+        let input = r.input_freq().unwrap_or(output);
+        let (shift, offset) = if input < output {
+            ("-", (output - input).to_string())
+        } else if input > output {
+            ("+", (input - output).to_string())
+        } else {
+            ("", String::new())
+        };
+        let mode = if r.modes().contains(&Mode::DStar) {
+            "DV"
+        } else if r.modes().contains(&Mode::Nfm) {
+            "NFM"
+        } else {
+            "FM"
+        };
+
+        let tone = r.code_in().filter(|c| matches!(c, Code::Ctcss(_)));
+
+        let mut row = KenwoodTh74aRow {
+            callsign: r.callsign().to_owned(),
+            lockout: if r.status() == Status::Off { "On" } else { "Off" }.to_owned(),
+            name: r.site().map(|s| s.name()).or(r.place().map(|p| p.town.as_str())).unwrap_or_default().to_owned(),
+            // the site's location already starts with the name
+            sub_name: r.place().map(|p| p.state.as_str()).or(r.site().map(|s| s.location())).unwrap_or_default().to_owned(),
+            frequency: output.to_string(),
+            shift: shift.to_owned(),
+            offset,
+            mode: mode.to_owned(),
+            uplink_tone: if tone.is_some() { "On" } else { "Off" }.to_owned(),
+            downlink_tone: tone.map(|t| t.to_string()).unwrap_or_default(),
+            ..Default::default()
+        };
+        row.set_position(r.position(), !r.site().is_some_and(Site::is_approximate));
+        row
+    }
+}
+
+/// Write a Kenwood TH-D74 repeater list (tab separated, with a header row)
+///
+/// Rows with a position and an empty "Aux 1" column get their 6 character grid locator there.
+pub fn write_kenwood_tsv<W: std::io::Write>(w: W, rows: &[KenwoodTh74aRow]) -> Result<(), FreqmError> {
+    let mut tsv = ::csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(false)
+        .from_writer(w);
+
+    tsv.write_record(KENWOOD_COLUMNS)?;
+    for row in rows {
+        match row.position() {
+            Some(pos) if row.aux_1.is_empty() => {
+                let mut row = row.clone();
+                row.aux_1 = Grid::of(pos, 6).to_string();
+                tsv.serialize(row)?;
+            }
+            _ => tsv.serialize(row)?,
+        }
+    }
+
+    tsv.flush()?;
+    Ok(())
+}
+
+/// A frequency in MHz, ignoring the zeros exports pad it with ("146.610000" is 146.61)
+fn mhz(text: &str, column: &'static str) -> Result<decimal::d128, FreqmError> {
+    let trimmed = text.trim();
+    let trimmed = if trimmed.contains('.') { trimmed.trim_end_matches('0').trim_end_matches('.') } else { trimmed };
+    parse_mhz(trimmed).ok_or_else(|| FrequencySnafu { value: text }.build().column(column))
+}
+
+/// "", "Off" and "None" are no tone. DCS codes may have a polarity ("D023N"), but only normal
+/// polarity is understood.
+fn parse_tone(text: &str, column: &'static str) -> Result<Option<Code>, FreqmError> {
+    let t = text.trim();
+    if t.is_empty() || t.eq_ignore_ascii_case("off") || t.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+
+    let t = match t.strip_suffix(['N', 'n']) {
+        Some(dcs) if dcs.starts_with(['D', 'd']) => dcs,
+        _ => t,
+    };
+    t.parse().map(Some).map_err(|_| CodeSnafu { value: text }.build().column(column))
+}
+
+/// The text `parse_tone()` reads, `off` for no tone. Codes other than CTCSS and DCS are left off.
+fn tone_text(code: Option<Code>, off: &str) -> String {
+    match code {
+        Some(c @ Code::Ctcss(_)) => c.to_string(),
+        Some(c @ Code::Dcs(_)) => format!("{}N", c),
+        _ => off.to_owned(),
+    }
+}
+
+/// The analog mode a channel for `r` should use, if it has one
+fn analog_mode(r: &Repeater) -> Option<Mode> {
+    [Mode::Nfm, Mode::Fm].into_iter().find(|m| r.modes().contains(m))
+}
+
+/// A color code column
+fn color_code(text: &str, column: &'static str) -> Result<Code, FreqmError> {
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|&c| c <= 15)
+        .map(Code::ColorCode)
+        .ok_or_else(|| CodeSnafu { value: text }.build().column(column))
+}
+
+impl std::convert::TryFrom<&ChirpRow> for Repeater {
+    type Error = FreqmError;
+
+    /// The channel's name is used as the callsign
+    fn try_from(row: &ChirpRow) -> Result<Self, Self::Error> {
+        let output_freq = mhz(&row.frequency, "Frequency")?;
+        let input_freq = match row.duplex.trim() {
+            "" | "off" => output_freq,
+            "+" => output_freq + mhz(&row.offset, "Offset")?,
+            "-" => output_freq - mhz(&row.offset, "Offset")?,
+            // the offset column holds the transmit frequency
+            "split" => mhz(&row.offset, "Offset")?,
+            _ => return Err(OffsetKindSnafu { value: &row.duplex }.build().column("Duplex")),
+        };
+        let mode = match row.mode.trim() {
+            "FM" => Mode::Fm,
+            "NFM" => Mode::Nfm,
+            "DV" => Mode::DStar,
+            _ => return Err(ModeSnafu { value: &row.mode }.build().column("Mode")),
+        };
+
+        let ctcss = |text: &str, column| {
+            text.parse::<Code>()
+                .ok()
+                .filter(|c| matches!(c, Code::Ctcss(_)))
+                .ok_or_else(|| CodeSnafu { value: text }.build().column(column))
+        };
+        let dcs = |text: &str, column| {
+            if row.dtcs_polarity.trim() != "NN" {
+                return Err(CodeSnafu { value: &row.dtcs_polarity }.build().column("DtcsPolarity"));
+            }
+            format!("D{}", text.trim()).parse::<Code>().map_err(|_| CodeSnafu { value: text }.build().column(column))
+        };
+
+        // "Tone" sends rToneFreq, "TSQL" sends and requires cToneFreq, "DTCS" sends and requires
+        // DtcsCode, and "Cross" sets the sent and required codes separately: "<tx>-><rx>"
+        let (code_in, code_out) = match row.tone.trim() {
+            "" => (None, None),
+            "Tone" => (Some(ctcss(&row.r_tone_freq, "rToneFreq")?), None),
+            "TSQL" => {
+                let tone = ctcss(&row.c_tone_freq, "cToneFreq")?;
+                (Some(tone), Some(tone))
+            }
+            "DTCS" => {
+                let code = dcs(&row.dtcs_code, "DtcsCode")?;
+                (Some(code), Some(code))
+            }
+            "Cross" => {
+                let bad = || CodeSnafu { value: &row.cross_mode }.build().column("CrossMode");
+                let (tx, rx) = row.cross_mode.trim().split_once("->").ok_or_else(bad)?;
+                let code_in = match tx {
+                    "" => None,
+                    "Tone" => Some(ctcss(&row.r_tone_freq, "rToneFreq")?),
+                    "DTCS" => Some(dcs(&row.dtcs_code, "DtcsCode")?),
+                    _ => return Err(bad()),
+                };
+                let code_out = match rx {
+                    "" => None,
+                    "Tone" => Some(ctcss(&row.c_tone_freq, "cToneFreq")?),
+                    "DTCS" => Some(dcs(&row.rx_dtcs_code, "RxDtcsCode")?),
+                    _ => return Err(bad()),
+                };
+                (code_in, code_out)
+            }
+            _ => return Err(CodeSnafu { value: &row.tone }.build().column("Tone")),
+        };
+
+        Ok(Repeater {
+            callsign: row.name.trim().to_owned(),
+            output_freq,
+            input_freq: Some(input_freq),
+            modes: vec![mode],
+            status: Status::On,
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in,
+            code_out,
+            dstar_module: Some(mode)
+                .filter(|&m| m == Mode::DStar)
+                .and_then(|_| crate::icom_id51a::module_in_call_sign(&row.rpt1_call)),
+            site: None,
+            place: None,
+            comment: Some(row.comment.trim()).filter(|c| !c.is_empty()).map(str::to_owned),
+        })
+    }
+}
+
+impl From<&Repeater> for ChirpRow {
+    /// Everything but the "Location" (channel number)
+    fn from(r: &Repeater) -> Self {
+        let mhz = |f: decimal::d128| format!("{:.6}", mhz_to_f64(f));
+        let output = r.output_freq();
+        let input = r.input_freq().unwrap_or(output);
+        let (duplex, offset) = if input < output {
+            ("-", mhz(output - input))
+        } else if input > output {
+            ("+", mhz(input - output))
+        } else {
+            ("", mhz(decimal::d128::zero()))
+        };
+
+        let code = |c: Option<Code>| c.filter(|c| matches!(c, Code::Ctcss(_) | Code::Dcs(_)));
+        let (code_in, code_out) = (code(r.code_in()), code(r.code_out()));
+        let kind = |c: Option<Code>| match c {
+            Some(Code::Ctcss(_)) => "Tone",
+            Some(_) => "DTCS",
+            None => "",
+        };
+        let (tone, cross_mode) = match (code_in, code_out) {
+            (None, None) => ("", "Tone->Tone".to_owned()),
+            (Some(Code::Ctcss(_)), None) => ("Tone", "Tone->Tone".to_owned()),
+            (Some(Code::Ctcss(a)), Some(Code::Ctcss(b))) if a == b => ("TSQL", "Tone->Tone".to_owned()),
+            (Some(Code::Dcs(a)), Some(Code::Dcs(b))) if a == b => ("DTCS", "Tone->Tone".to_owned()),
+            _ => ("Cross", format!("{}->{}", kind(code_in), kind(code_out))),
+        };
+        let ctcss = |c: Option<Code>| match c {
+            Some(t @ Code::Ctcss(_)) => t.to_string(),
+            _ => "88.5".to_owned(),
+        };
+        let dcs = |c: Option<Code>| match c {
+            Some(Code::Dcs(d)) => format!("{:03o}", d),
+            _ => "023".to_owned(),
+        };
+
+        let dstar = r.modes().contains(&Mode::DStar);
+        let dv_call = |suffix: char| if dstar { format!("{:<7}{}", r.callsign(), suffix) } else { String::new() };
+        let module = crate::icom_id51a::module_of(r);
+
+        ChirpRow {
+            location: String::new(),
+            name: r.callsign().to_owned(),
+            frequency: mhz(output),
+            duplex: duplex.to_owned(),
+            offset,
+            tone: tone.to_owned(),
+            r_tone_freq: ctcss(code_in),
+            // "TSQL" uses cToneFreq both ways
+            c_tone_freq: ctcss(code_out),
+            // "DTCS" uses DtcsCode both ways, "Cross" uses RxDtcsCode for receiving
+            dtcs_code: dcs(code_in),
+            dtcs_polarity: "NN".to_owned(),
+            rx_dtcs_code: dcs(code_out),
+            cross_mode,
+            mode: if dstar { "DV" } else if analog_mode(r) == Some(Mode::Nfm) { "NFM" } else { "FM" }.to_owned(),
+            t_step: "5.00".to_owned(),
+            skip: String::new(),
+            power: String::new(),
+            comment: r.comment().map(str::to_owned).or(r.site().map(|s| s.location().to_owned())).unwrap_or_default(),
+            ur_call: if dstar { "CQCQCQ".to_owned() } else { String::new() },
+            rpt1_call: dv_call(module),
+            rpt2_call: dv_call('G'),
+            dv_code: String::new(),
+        }
+    }
+}
+
+impl BostonMarathonIcsRow {
+    /// Rows without a channel number (blank lines, headings) and restricted channels aren't
+    /// channels that can be programmed
+    pub fn is_channel(&self) -> bool {
+        !self.ch_number.trim().is_empty() && !self.assignment.trim().starts_with("RESTRICTED")
+    }
+
+    /// "<function> | <assignment> | <remarks>", as a comment for the channel
+    fn comment(&self) -> Option<String> {
+        let parts = [&self.function, &self.assignment, &self.remarks].map(|p| p.trim());
+        parts.iter().any(|p| !p.is_empty()).then(|| parts.join(" | "))
+    }
+
+    /// "<MHz> <W or N>"
+    fn freq(text: &str, column: &'static str) -> Result<(decimal::d128, bool), FreqmError> {
+        let (freq, width) = text.trim().split_once(' ').unwrap_or((text.trim(), "W"));
+        let narrow = match width.trim() {
+            "W" => false,
+            "N" => true,
+            _ => return Err(FrequencySnafu { value: text }.build().column(column)),
+        };
+        Ok((mhz(freq, column)?, narrow))
+    }
+}
+
+impl std::convert::TryFrom<&BostonMarathonIcsRow> for Repeater {
+    type Error = FreqmError;
+
+    /// The channel's name is used as the callsign. Digital channels are P25.
+    fn try_from(row: &BostonMarathonIcsRow) -> Result<Self, Self::Error> {
+        let (output_freq, narrow) = BostonMarathonIcsRow::freq(&row.rx_freq_wn, "RX Freq N or W")?;
+        let (input_freq, _) = BostonMarathonIcsRow::freq(&row.tx_freq_wn, "TX Freq N or W")?;
+        let digital = match row.mode_a_or_d.trim() {
+            "A" => false,
+            "D" => true,
+            _ => return Err(ModeSnafu { value: &row.mode_a_or_d }.build().column("Mode A or D")),
+        };
+
+        // "CSQ" is carrier squelch. Digital channels have a NAC, in hex.
+        let code = |text: &str, column| match text.trim() {
+            "" | "CSQ" => Ok(None),
+            t if digital => format!("NAC{}", t).parse().map(Some).map_err(|_| CodeSnafu { value: text }.build().column(column)),
+            _ => parse_tone(text, column),
+        };
+
+        Ok(Repeater {
+            callsign: row.channel_name.trim().to_owned(),
+            output_freq,
+            input_freq: Some(input_freq),
+            modes: vec![if digital { Mode::P25 } else if narrow { Mode::Nfm } else { Mode::Fm }],
+            status: Status::On,
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in: code(&row.tx_tone_nac, "TX Tone/NAC")?,
+            code_out: code(&row.rx_tone_nac, "RX Tone/NAC")?,
+            dstar_module: None,
+            site: None,
+            place: None,
+            comment: row.comment(),
+        })
+    }
+}
+
+impl From<&Repeater> for BostonMarathonIcsRow {
+    /// Everything but the "Ch #"
+    fn from(r: &Repeater) -> Self {
+        let digital = r.modes().contains(&Mode::P25);
+        let width = if !digital && analog_mode(r) == Some(Mode::Nfm) { "N" } else { "W" };
+        let freq = |f: decimal::d128| format!("{} {}", f, width);
+        let code = |c: Option<Code>| match c {
+            Some(Code::Nac(n)) if digital => format!("{:03X}", n),
+            c if !digital => tone_text(c, "CSQ"),
+            _ => String::new(),
+        };
+
+        // comments read from an ICS plan split back into their columns, others are remarks
+        let comment = r.comment().unwrap_or_default();
+        let (function, assignment, remarks) = match comment.splitn(3, " | ").collect::<Vec<_>>()[..] {
+            [function, assignment, remarks] => (function.to_owned(), assignment.to_owned(), remarks.to_owned()),
+            _ => (String::new(), r.site().map(|s| s.location().to_owned()).unwrap_or_default(), comment.to_owned()),
+        };
+
+        BostonMarathonIcsRow {
+            ch_number: String::new(),
+            function,
+            channel_name: r.callsign().to_owned(),
+            assignment,
+            rx_freq_wn: freq(r.output_freq()),
+            rx_tone_nac: code(r.code_out()),
+            tx_freq_wn: freq(r.input_freq().unwrap_or(r.output_freq())),
+            tx_tone_nac: code(r.code_in()),
+            mode_a_or_d: if digital { "D" } else { "A" }.to_owned(),
+            remarks,
+        }
+    }
+}
+
+impl Default for Uv390Row {
+    fn default() -> Self {
+        // the defaults have the right number of fields, so this can't fail
+        ::csv::StringRecord::from(UV390_DEFAULTS.to_vec()).deserialize(None).unwrap()
+    }
+}
+
+impl std::convert::TryFrom<&Uv390Row> for Repeater {
+    type Error = FreqmError;
+
+    /// The channel's name is used as the callsign
+    fn try_from(row: &Uv390Row) -> Result<Self, Self::Error> {
+        let (mode, code_in, code_out) = match row.channel_mode.trim() {
+            "Digital" => {
+                let cc = color_code(&row.color_code, "Color Code")?;
+                (Mode::Dmr, Some(cc), Some(cc))
+            }
+            "Analog" => {
+                let mode = match row.band_width.trim() {
+                    "12.5" => Mode::Nfm,
+                    "20" | "25" => Mode::Fm,
+                    _ => return Err(ModeSnafu { value: &row.band_width }.build().column("Band Width")),
+                };
+                (
+                    mode,
+                    parse_tone(&row.ctcss_dcs_enc, "CTCSS/DCS Enc")?,
+                    parse_tone(&row.ctcss_dcs_dec, "CTCSS/DCS Dec")?,
+                )
+            }
+            _ => return Err(ModeSnafu { value: &row.channel_mode }.build().column("Channel Mode")),
+        };
+
+        Ok(Repeater {
+            callsign: row.channel_name.trim().to_owned(),
+            output_freq: mhz(&row.rx_freq_mhz, "RX Frequency(MHz)")?,
+            input_freq: Some(mhz(&row.tx_freq_mhz, "TX Frequency(MHz)")?),
+            modes: vec![mode],
+            status: Status::On,
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in,
+            code_out,
+            dstar_module: None,
+            site: None,
+            place: None,
+            comment: None,
+        })
+    }
+}
+
+impl From<&Repeater> for Uv390Row {
+    /// DMR repeaters get digital channels, with the color code they require
+    fn from(r: &Repeater) -> Self {
+        let freq = |f: decimal::d128| format!("{:.5}", mhz_to_f64(f));
+        let dmr = r.modes().contains(&Mode::Dmr);
+        let cc = match (r.code_in(), r.code_out()) {
+            (Some(Code::ColorCode(c)), _) | (None, Some(Code::ColorCode(c))) => c,
+            _ => 1,
+        };
+
+        let mut row = Uv390Row {
+            channel_mode: if dmr { "Digital" } else { "Analog" }.to_owned(),
+            channel_name: r.callsign().to_owned(),
+            rx_freq_mhz: freq(r.output_freq()),
+            tx_freq_mhz: freq(r.input_freq().unwrap_or(r.output_freq())),
+            band_width: if !dmr && analog_mode(r) == Some(Mode::Nfm) { "12.5" } else { "25" }.to_owned(),
+            color_code: cc.to_string(),
+            ..Default::default()
+        };
+        if !dmr {
+            row.ctcss_dcs_enc = tone_text(r.code_in(), "None");
+            row.ctcss_dcs_dec = tone_text(r.code_out(), "None");
+        }
+        row
+    }
+}
+
+impl Default for At878Row {
+    fn default() -> Self {
+        // the defaults have the right number of fields, so this can't fail
+        ::csv::StringRecord::from(AT878_DEFAULTS.to_vec()).deserialize(None).unwrap()
+    }
+}
+
+impl std::convert::TryFrom<&At878Row> for Repeater {
+    type Error = FreqmError;
+
+    /// The channel's name is used as the callsign. Mixed analog and DMR channels have both modes,
+    /// with the color codes as their codes if they transmit DMR.
+    fn try_from(row: &At878Row) -> Result<Self, Self::Error> {
+        let analog = match row.band_width.trim() {
+            "12.5K" => Mode::Nfm,
+            "25K" => Mode::Fm,
+            _ => return Err(ModeSnafu { value: &row.band_width }.build().column("Band Width")),
+        };
+        let modes = match row.channel_type.trim() {
+            "A-Analog" => vec![analog],
+            "D-Digital" => vec![Mode::Dmr],
+            "A+D TX A" => vec![analog, Mode::Dmr],
+            "D+A TX D" => vec![Mode::Dmr, analog],
+            _ => return Err(ModeSnafu { value: &row.channel_type }.build().column("Channel Type")),
+        };
+
+        let (code_in, code_out) = if modes[0] == Mode::Dmr {
+            let rx = color_code(&row.rx_color_code, "RX Color Code")?;
+            let tx = if row.tx_cc.trim().is_empty() { rx } else { color_code(&row.tx_cc, "TxCC")? };
+            (Some(tx), Some(rx))
+        } else {
+            (
+                parse_tone(&row.ctcss_dcs_encode, "CTCSS/DCS Encode")?,
+                parse_tone(&row.ctcss_dcs_decode, "CTCSS/DCS Decode")?,
+            )
+        };
+
+        Ok(Repeater {
+            callsign: row.channel_name.trim().to_owned(),
+            output_freq: mhz(&row.receive_frequency, "Receive Frequency")?,
+            input_freq: Some(mhz(&row.transmit_frequency, "Transmit Frequency")?),
+            modes,
+            status: Status::On,
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in,
+            code_out,
+            dstar_module: None,
+            site: None,
+            place: None,
+            comment: None,
+        })
+    }
+}
+
+impl From<&Repeater> for At878Row {
+    /// Everything but the "No." (channel number). Repeaters with DMR and an analog mode get mixed
+    /// channels that transmit DMR.
+    fn from(r: &Repeater) -> Self {
+        let freq = |f: decimal::d128| format!("{:.5}", mhz_to_f64(f));
+        let dmr = r.modes().contains(&Mode::Dmr);
+        let analog = analog_mode(r);
+        let cc = |c: Option<Code>| match c {
+            Some(Code::ColorCode(c)) => Some(c),
+            _ => None,
+        };
+
+        let mut row = At878Row {
+            channel_name: r.callsign().to_owned(),
+            receive_frequency: freq(r.output_freq()),
+            transmit_frequency: freq(r.input_freq().unwrap_or(r.output_freq())),
+            channel_type: match (dmr, analog) {
+                (true, Some(_)) => "D+A TX D",
+                (true, None) => "D-Digital",
+                (false, _) => "A-Analog",
+            }
+            .to_owned(),
+            band_width: if analog == Some(Mode::Nfm) { "12.5K" } else { "25K" }.to_owned(),
+            ..Default::default()
+        };
+        if dmr {
+            let rx = cc(r.code_out()).or(cc(r.code_in())).unwrap_or(1);
+            row.rx_color_code = rx.to_string();
+            row.tx_cc = cc(r.code_in()).unwrap_or(rx).to_string();
+        } else {
+            row.ctcss_dcs_encode = tone_text(r.code_in(), "Off");
+            row.ctcss_dcs_decode = tone_text(r.code_out(), "Off");
+        }
+        row
+    }
 }
@@ -0,0 +1,183 @@
+//! Comparing two channel lists: the channels added, removed and changed
+//!
+//! Channels are matched by callsign and output frequency, as when merging listings (see
+//! `repeater_db`). If a list has several channels with the same callsign and frequency, they're
+//! matched in order.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::geo::Located;
+use crate::repeater_db::key;
+use crate::{Mode, Repeater};
+
+/// Positions closer than this are the same (some formats round them)
+const SAME_POSITION_KM: f64 = 0.1;
+
+/// Which channel, for reports
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelId {
+    pub callsign: String,
+    /// MHz
+    pub output_freq: decimal::d128,
+    /// "Town, ST", if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub place: Option<String>,
+}
+
+impl From<&Repeater> for ChannelId {
+    fn from(r: &Repeater) -> Self {
+        ChannelId { callsign: r.callsign().to_owned(), output_freq: r.output_freq(), place: r.place().map(|p| p.to_string()) }
+    }
+}
+
+impl fmt::Display for ChannelId {
+    /// "W1BOS 146.610 (Boston, MA)"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.callsign, self.output_freq)?;
+        if let Some(place) = &self.place {
+            write!(f, " ({})", place)?;
+        }
+        Ok(())
+    }
+}
+
+/// A field with a different value in the new list, with the values as text (`None` if missing)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    /// Name of the `Repeater` field
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl fmt::Display for Change {
+    /// "code_in: 88.5 -> 100.0", "irlp_node: none -> 1234"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: &Option<String>| v.clone().unwrap_or_else(|| "none".to_owned());
+        write!(f, "{}: {} -> {}", self.field, show(&self.old), show(&self.new))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Modified {
+    #[serde(flatten)]
+    pub channel: ChannelId,
+    pub changes: Vec<Change>,
+}
+
+/// How a channel list changed. Removed channels are in the old list's order, the others in the
+/// new list's.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ListDiff {
+    pub added: Vec<ChannelId>,
+    pub removed: Vec<ChannelId>,
+    pub modified: Vec<Modified>,
+    pub unchanged: usize,
+}
+
+impl ListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// A line for each added and removed channel, and each changed field, followed by the summary
+    /// line
+    pub fn write_text<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        for c in &self.removed {
+            writeln!(w, "removed: {}", c)?;
+        }
+        for c in &self.added {
+            writeln!(w, "added: {}", c)?;
+        }
+        for m in &self.modified {
+            for change in &m.changes {
+                writeln!(w, "modified: {}: {}", m.channel, change)?;
+            }
+        }
+        writeln!(w, "{}", self)
+    }
+}
+
+impl fmt::Display for ListDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "added {}, removed {}, modified {}, unchanged {}",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len(),
+            self.unchanged
+        )
+    }
+}
+
+/// Compare the channels in `old` with the ones in `new`
+pub fn diff(old: &[Repeater], new: &[Repeater]) -> ListDiff {
+    let mut unmatched: HashMap<(String, i64), VecDeque<usize>> = HashMap::new();
+    for (i, r) in old.iter().enumerate() {
+        unmatched.entry(key(r.callsign(), r.output_freq())).or_default().push_back(i);
+    }
+
+    let mut diff = ListDiff::default();
+    let mut matched = vec![false; old.len()];
+    for r in new {
+        match unmatched.get_mut(&key(r.callsign(), r.output_freq())).and_then(VecDeque::pop_front) {
+            Some(i) => {
+                matched[i] = true;
+                let changes = changes(&old[i], r);
+                if changes.is_empty() {
+                    diff.unchanged += 1;
+                } else {
+                    diff.modified.push(Modified { channel: r.into(), changes });
+                }
+            },
+            None => diff.added.push(r.into()),
+        }
+    }
+    diff.removed = old.iter().zip(matched).filter(|(_, m)| !m).map(|(r, _)| r.into()).collect();
+    diff
+}
+
+/// The fields that differ between `old` and `new`, other than the callsign and output frequency
+pub fn changes(old: &Repeater, new: &Repeater) -> Vec<Change> {
+    fn show<T: fmt::Display>(value: Option<T>) -> Option<String> {
+        value.map(|v| v.to_string())
+    }
+    fn modes(modes: &[Mode]) -> Option<String> {
+        let names: Vec<String> = modes.iter().map(|m| format!("{:?}", m)).collect();
+        (!names.is_empty()).then(|| names.join("/"))
+    }
+
+    let mut changes = Vec::new();
+    let mut field = |field: &'static str, old: Option<String>, new: Option<String>| {
+        if old != new {
+            changes.push(Change { field, old, new });
+        }
+    };
+
+    // lists write frequencies with different numbers of places
+    field("input_freq", show(old.input_freq().map(|f| f.reduce())), show(new.input_freq().map(|f| f.reduce())));
+    field("modes", modes(old.modes()), modes(new.modes()));
+    field("status", Some(format!("{:?}", old.status())), Some(format!("{:?}", new.status())));
+    field("code_in", show(old.code_in()), show(new.code_in()));
+    field("code_out", show(old.code_out()), show(new.code_out()));
+    field("dstar_module", show(old.dstar_module()), show(new.dstar_module()));
+    field("irlp_node", show(old.irlp_node()), show(new.irlp_node()));
+    field("echolink_node", show(old.echolink_node()), show(new.echolink_node()));
+    field("updated", show(old.updated()), show(new.updated()));
+    field("place", show(old.place()), show(new.place()));
+    field("comment", show(old.comment()), show(new.comment()));
+    let moved = match (old.position(), new.position()) {
+        (Some(a), Some(b)) => a.distance(&b).as_km() > SAME_POSITION_KM,
+        (a, b) => a.is_some() != b.is_some(),
+    };
+    if moved {
+        field("position", show(old.position()), show(new.position()));
+    }
+
+    changes
+}
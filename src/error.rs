@@ -0,0 +1,463 @@
+//! Errors, and where in the input they came from
+//!
+//! `FreqmError` sorts failures into a few broad kinds, each wrapping a more specific error:
+//!
+//!  - `Parse`: a field's text couldn't be interpreted
+//!  - `Validation`: a field parsed, but its value doesn't make sense
+//!  - `Format`: the structure of the input is wrong (field counts, csv syntax)
+//!  - `Io`: reading or writing a file failed
+//!  - `Sqlite`: reading or writing a SQLite database failed
+//!  - `Serial`: talking to a radio failed
+//!  - `Memory`: a memory image operation was invalid
+//!  - `Map`: a memory map definition, or a value being decoded or encoded with one, was invalid
+//!
+//! Parse, validation and format errors carry a `Location`. Errors are created where only the
+//! column is known, and the file, line and record are filled in as the error passes back up
+//! through the importer (see `FreqmError::with_file()` and `FreqmError::with_row()`).
+
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use snafu::Snafu;
+
+/// Where in the input an error occurred. Every part is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    pub file: Option<PathBuf>,
+
+    /// Line in the file (1-based)
+    pub line: Option<u64>,
+
+    /// Record number (1-based, not counting any header)
+    pub record: Option<u64>,
+
+    /// Column name, as used in the source format's documentation
+    pub column: Option<&'static str>,
+}
+
+impl Location {
+    pub fn column(column: &'static str) -> Self {
+        Location {
+            column: Some(column),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Location::default()
+    }
+}
+
+impl fmt::Display for Location {
+    /// "<file>:<line>: record <record>: column <column>", omitting any parts that are missing
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => parts.push(format!("{}:{}", file.display(), line)),
+            (Some(file), None) => parts.push(file.display().to_string()),
+            (None, Some(line)) => parts.push(format!("line {}", line)),
+            (None, None) => {}
+        }
+
+        if let Some(record) = self.record {
+            parts.push(format!("record {}", record));
+        }
+
+        if let Some(column) = self.column {
+            parts.push(format!("column {}", column));
+        }
+
+        write!(f, "{}", parts.join(": "))
+    }
+}
+
+/// Prefix for errors that have a location
+fn at(location: &Location) -> String {
+    if location.is_empty() {
+        String::new()
+    } else {
+        format!("{}: ", location)
+    }
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum FreqmError {
+    #[snafu(display("{}{}", at(location), source))]
+    Parse { location: Location, source: ParseError },
+
+    #[snafu(display("{}{}", at(location), source))]
+    Validation { location: Location, source: ValidationError },
+
+    #[snafu(display("{}{}", at(location), source))]
+    Format { location: Location, source: FormatError },
+
+    #[snafu(display("{}: {}", path.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "I/O".to_owned()), source))]
+    Io { path: Option<PathBuf>, source: std::io::Error },
+
+    #[snafu(display("{}: SQLite: {}", path.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "database".to_owned()), source))]
+    Sqlite {
+        path: Option<PathBuf>,
+        #[snafu(source(from(rusqlite::Error, Box::new)))]
+        source: Box<rusqlite::Error>,
+    },
+
+    #[snafu(display("serial: {}", source))]
+    Serial { source: SerialError },
+
+    #[snafu(display("memory: {}", source))]
+    Memory { source: MemoryError },
+
+    #[snafu(display("memory map: {}", source))]
+    Map { source: MapError },
+}
+
+/// A field's text couldn't be interpreted
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum ParseError {
+    #[snafu(display("{:?} is not a frequency", value))]
+    Frequency { value: String },
+
+    #[snafu(display("offset kind {:?} unrecognized", value))]
+    OffsetKind { value: String },
+
+    #[snafu(display("comment parse failed: {:?}", value))]
+    Comment { value: String },
+
+    #[snafu(display("mode {:?} unrecognized", value))]
+    Mode { value: String },
+
+    #[snafu(display("status {:?} unrecognized", value))]
+    Status { value: String },
+
+    #[snafu(display("node number {:?} is not a number", value))]
+    Node { value: String },
+
+    #[snafu(display("timestamp {:?} is not a YYYY/MM/DD date", value))]
+    Timestamp { value: String },
+
+    #[snafu(display("{:?} is not a number", value))]
+    Number { value: String },
+
+    #[snafu(display("band {:?} unknown", value))]
+    Band { value: String },
+
+    #[snafu(display("{:?}: {}", text, reason))]
+    Filter { text: String, reason: String },
+
+    #[snafu(display("{:?} is not a position", value))]
+    Position { value: String },
+
+    #[snafu(display("{:?} is not a distance with a unit (mi or km)", value))]
+    Distance { value: String },
+
+    #[snafu(display("{:?} is not a Maidenhead grid locator", value))]
+    Grid { value: String },
+
+    #[snafu(display("{:?} is not a CTCSS tone, DCS code, color code or NAC", value))]
+    Code { value: String },
+
+    #[snafu(display("{:?} is not a D-STAR module (A to D)", value))]
+    DStarModule { value: String },
+
+    #[snafu(display("source {:?} unknown", value))]
+    Source { value: String },
+
+    #[snafu(display("radio model {:?} unknown", value))]
+    Model { value: String },
+
+    #[snafu(display("channel list format {:?} unknown", value))]
+    ListFormat { value: String },
+
+    #[snafu(display("sort key {:?} unknown", value))]
+    SortKey { value: String },
+
+    #[snafu(display("grouping {:?} unknown", value))]
+    GroupBy { value: String },
+
+    #[snafu(display("{:?} is not a channel number range (\"<first>-<last>\")", value))]
+    ChannelRange { value: String },
+
+    #[snafu(display("name template: {}", reason))]
+    NameTemplate { reason: String },
+
+    #[snafu(display("lint check {:?} unknown", value))]
+    LintCheck { value: String },
+
+    #[snafu(display("{:?} is not an address", value))]
+    Address { value: String },
+
+    #[snafu(display("{:?} is not an image format", value))]
+    ImageFormat { value: String },
+
+    #[snafu(display("annotation {:?} is not \"<start>..<end> <name>\" or \"<start>+<len> <name>\"", value))]
+    Annotation { value: String },
+}
+
+/// A field parsed, but the value doesn't make sense
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum ValidationError {
+    #[snafu(display("frequency {} is not in a known band", freq))]
+    FreqNotInAnyBand { freq: decimal::d128 },
+
+    #[snafu(display("custom split without an input frequency: {:?}", comment))]
+    CustomSplitUnknown { comment: String },
+
+    #[snafu(display("{}", reason))]
+    ChannelRecord { reason: String },
+
+    #[snafu(display("group {:?} has {} channels, more than the {} a list holds", group, found, max))]
+    GroupSize { group: String, found: usize, max: usize },
+}
+
+/// The structure of the input is wrong
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum FormatError {
+    #[snafu(display("wrong number of fields: have {}, expected {}", found, expected))]
+    FieldCount { found: usize, expected: &'static str },
+
+    #[snafu(display("CSV read failed: {}", source))]
+    Csv { source: ::csv::Error },
+
+    #[snafu(display("no {:?} column in the header", column))]
+    MissingColumn { column: &'static str },
+
+    #[snafu(display("JSON read failed: {}", source))]
+    Json { source: serde_json::Error },
+
+    #[snafu(display("YAML read failed: {}", source))]
+    Yaml { source: serde_norway::Error },
+
+    #[snafu(display("channel file version {} is newer than this freqm reads ({})", found, supported))]
+    ChannelFileVersion { found: u32, supported: u32 },
+
+    #[snafu(display("database schema version {} is newer than this freqm reads ({})", found, supported))]
+    DatabaseVersion { found: u32, supported: u32 },
+
+    #[snafu(display("TOML read failed: {}", source))]
+    TomlRead {
+        #[snafu(source(from(toml::de::Error, Box::new)))]
+        source: Box<toml::de::Error>,
+    },
+
+    #[snafu(display("project: {}", reason))]
+    Project { reason: String },
+
+    #[snafu(display("malformed record: {}", reason))]
+    ImageRecord { reason: String },
+
+    #[snafu(display("GPX file has no usable points: {}", reason))]
+    Gpx { reason: String },
+
+    #[snafu(display("record checksum is {:#04x}, computed {:#04x}", found, expected))]
+    ImageChecksum { found: u8, expected: u8 },
+
+    #[snafu(display("address {:#x} does not fit in {} bits", addr, bits))]
+    AddressWidth { addr: u64, bits: u32 },
+
+    #[snafu(display("data at {:#x} is below the image base address {:#x}", addr, base))]
+    BelowBase { addr: u64, base: u64 },
+}
+
+/// Communicating with a radio failed
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum SerialError {
+    #[snafu(display("port: {}", source))]
+    Port { source: serialport::Error },
+
+    #[snafu(display("I/O: {}", source))]
+    SerialIo { source: std::io::Error },
+
+    #[snafu(display("protocol: expected {:02x?}, got {:02x?}", expected, got))]
+    Protocol { expected: Vec<u8>, got: Vec<u8> },
+}
+
+/// An operation on a `SparseMem` was invalid
+#[derive(Debug, Snafu, Clone, PartialEq, Eq)]
+#[snafu(visibility(pub(crate)))]
+pub enum MemoryError {
+    #[snafu(display("{:#x}..{:#x} overlaps existing data at {:#x}..{:#x}", range.start, range.end, existing.start, existing.end))]
+    Overlap { range: Range<u64>, existing: Range<u64> },
+
+    #[snafu(display("{} bytes at {:#x} extends past the end of the address space", len, addr))]
+    AddressOverflow { addr: u64, len: usize },
+
+    #[snafu(display("{:#x}..{:#x} is not populated", gap.start, gap.end))]
+    Unpopulated { gap: Range<u64> },
+}
+
+/// A memory map definition was invalid, or a value couldn't be encoded with it
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum MapError {
+    #[snafu(display("{}", source))]
+    Toml {
+        #[snafu(source(from(toml::de::Error, Box::new)))]
+        source: Box<toml::de::Error>,
+    },
+
+    #[snafu(display("{}: {}", name, reason))]
+    Definition { name: String, reason: String },
+
+    #[snafu(display("no field {:?}", path))]
+    UnknownField { path: String },
+
+    #[snafu(display("{}: {:?} {}", path, value, reason))]
+    Value { path: String, value: String, reason: &'static str },
+}
+
+impl ParseError {
+    /// Attach the column the text came from
+    pub(crate) fn column(self, column: &'static str) -> FreqmError {
+        FreqmError::Parse {
+            location: Location::column(column),
+            source: self,
+        }
+    }
+}
+
+impl ValidationError {
+    /// Attach the column the value came from
+    pub(crate) fn column(self, column: &'static str) -> FreqmError {
+        FreqmError::Validation {
+            location: Location::column(column),
+            source: self,
+        }
+    }
+}
+
+impl From<FormatError> for FreqmError {
+    fn from(source: FormatError) -> Self {
+        FreqmError::Format {
+            location: Location::default(),
+            source,
+        }
+    }
+}
+
+impl From<SerialError> for FreqmError {
+    fn from(source: SerialError) -> Self {
+        FreqmError::Serial { source }
+    }
+}
+
+impl From<std::io::Error> for FreqmError {
+    /// An I/O error on an unnamed stream. The path can be added later with `set_file()`.
+    fn from(source: std::io::Error) -> Self {
+        FreqmError::Io { path: None, source }
+    }
+}
+
+impl From<rusqlite::Error> for FreqmError {
+    /// An error on an unnamed database. The path can be added later with `set_file()`.
+    fn from(source: rusqlite::Error) -> Self {
+        FreqmError::Sqlite { path: None, source: Box::new(source) }
+    }
+}
+
+impl From<MemoryError> for FreqmError {
+    fn from(source: MemoryError) -> Self {
+        FreqmError::Memory { source }
+    }
+}
+
+impl From<MapError> for FreqmError {
+    fn from(source: MapError) -> Self {
+        FreqmError::Map { source }
+    }
+}
+
+impl From<::csv::Error> for FreqmError {
+    /// I/O errors become `Io`, everything else `Format`
+    fn from(e: ::csv::Error) -> Self {
+        let position = e.position().cloned();
+        if e.is_io_error() {
+            match e.into_kind() {
+                ::csv::ErrorKind::Io(source) => return FreqmError::Io { path: None, source },
+                _ => unreachable!(),
+            }
+        }
+
+        // the record number depends on whether the reader has a header, which only the caller knows
+        let mut err: FreqmError = FormatError::Csv { source: e }.into();
+        if let Some(p) = position
+            && let Some(location) = err.location_mut()
+        {
+            location.line.get_or_insert(p.line());
+        }
+        err
+    }
+}
+
+impl FreqmError {
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            FreqmError::Parse { location, .. }
+            | FreqmError::Validation { location, .. }
+            | FreqmError::Format { location, .. } => Some(location),
+            FreqmError::Io { .. }
+            | FreqmError::Sqlite { .. }
+            | FreqmError::Serial { .. }
+            | FreqmError::Memory { .. }
+            | FreqmError::Map { .. } => None,
+        }
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Location> {
+        match self {
+            FreqmError::Parse { location, .. }
+            | FreqmError::Validation { location, .. }
+            | FreqmError::Format { location, .. } => Some(location),
+            FreqmError::Io { .. }
+            | FreqmError::Sqlite { .. }
+            | FreqmError::Serial { .. }
+            | FreqmError::Memory { .. }
+            | FreqmError::Map { .. } => None,
+        }
+    }
+
+    /// Record which file the error came from, if it isn't already known
+    pub fn set_file<P: Into<PathBuf>>(&mut self, file: P) {
+        match self {
+            FreqmError::Io { path, .. } | FreqmError::Sqlite { path, .. } => {
+                path.get_or_insert(file.into());
+            }
+            e => {
+                if let Some(location) = e.location_mut() {
+                    location.file.get_or_insert(file.into());
+                }
+            }
+        }
+    }
+
+    /// Record which line & record the error came from, if they aren't already known
+    pub fn set_row(&mut self, line: u64, record: u64) {
+        if let Some(location) = self.location_mut() {
+            location.line.get_or_insert(line);
+            location.record.get_or_insert(record);
+        }
+    }
+
+    /// Record which record the error came from, for inputs that aren't read line by line
+    pub fn with_record(mut self, record: u64) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.record.get_or_insert(record);
+        }
+        self
+    }
+
+    pub fn with_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.set_file(file);
+        self
+    }
+
+    pub fn with_row(mut self, line: u64, record: u64) -> Self {
+        self.set_row(line, record);
+        self
+    }
+}
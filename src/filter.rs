@@ -0,0 +1,447 @@
+//! Selecting repeaters with filter expressions
+//!
+//! An expression is tests joined with `and`, `or` and `not`, grouped with parentheses. `not` binds
+//! tightest and `or` loosest. The tests are:
+//!
+//!  - `band = 2m`: output on a band (10m, 6m, 2m, 1.25m, 70cm, 33cm or 23cm)
+//!  - `freq >= 144`, `freq in 146..148`: output frequency, MHz. Ranges include both ends.
+//!  - `mode = dmr`: operates in a mode (fm, nfm, dmr, dstar, ysf, nxdn or p25), or in any
+//!    `analog` or `digital` one
+//!  - `tone`: needs a CTCSS tone or DCS code on its input. `tone = 88.5` for a particular one.
+//!  - `town`, `county`, `state = MA`: the place the listing puts it in, ignoring case
+//!  - `status = off`: on, off, local, limitedrx or limitedtx
+//!  - `callsign ~ W1*`: `~` matches a pattern, where `*` is any characters and `?` is one. It
+//!    works on callsigns and places, ignoring case.
+//!  - `updated > 2020-01-01`: when the listing last updated it
+//!  - `within 30mi of FN42`: distance from "<lat>,<lon>" or a grid locator's center
+//!
+//! `!=` is the opposite of `=`. Values with spaces are quoted: `county = "New Haven"`. Repeaters
+//! without what a test looks at (a place, an update date, a position) fail it.
+
+use std::borrow::Borrow;
+use std::fmt;
+
+use crate::error::*;
+use crate::geo::{Distance, LatLon, Located};
+use crate::ne_repeater::parse_mhz;
+use crate::{Band, Code, Mode, Repeater, Status};
+
+/// A parsed filter expression. The default matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    text: String,
+    expr: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    Band(Band),
+    Freq(Cmp, decimal::d128),
+    FreqIn(decimal::d128, decimal::d128),
+    /// Any of these
+    Mode(Vec<Mode>),
+    /// Any tone or code if `None`
+    Tone(Option<Code>),
+    Text(Field, Pattern),
+    Status(Status),
+    Updated(Cmp, chrono::NaiveDate),
+    Within(Distance, LatLon),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn holds<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Callsign,
+    Town,
+    County,
+    State,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Exact(String),
+    Glob(String),
+}
+
+impl Pattern {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Pattern::Exact(p) => p.eq_ignore_ascii_case(text.trim()),
+            Pattern::Glob(p) => glob(p, text.trim()),
+        }
+    }
+}
+
+/// Whether `text` matches `pattern`, ignoring case. `*` matches any characters and `?` one.
+fn glob(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let t: Vec<char> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let (mut i, mut j) = (0, 0);
+    // where the last `*` was, and the text position it's matched up to
+    let mut star = None;
+
+    while j < t.len() {
+        if i < p.len() && (p[i] == '?' || p[i] == t[j]) {
+            i += 1;
+            j += 1;
+        } else if i < p.len() && p[i] == '*' {
+            star = Some((i, j));
+            i += 1;
+        } else if let Some((si, sj)) = star {
+            // let the `*` take one more character
+            i = si + 1;
+            j = sj + 1;
+            star = Some((si, sj + 1));
+        } else {
+            return false;
+        }
+    }
+    p[i..].iter().all(|&c| c == '*')
+}
+
+impl Test {
+    fn matches(&self, r: &Repeater) -> bool {
+        match self {
+            Test::Band(band) => Band::of(r.output_freq()) == Some(*band),
+            Test::Freq(cmp, mhz) => cmp.holds(r.output_freq(), *mhz),
+            Test::FreqIn(low, high) => r.output_freq() >= *low && r.output_freq() <= *high,
+            Test::Mode(modes) => r.modes().iter().any(|m| modes.contains(m)),
+            Test::Tone(None) => matches!(r.code_in(), Some(Code::Ctcss(_) | Code::Dcs(_))),
+            Test::Tone(Some(code)) => r.code_in() == Some(*code),
+            Test::Text(Field::Callsign, p) => p.matches(r.callsign()),
+            Test::Text(field, p) => r.place().is_some_and(|place| {
+                p.matches(match field {
+                    Field::Town => &place.town,
+                    Field::County => &place.county,
+                    _ => &place.state,
+                })
+            }),
+            Test::Status(status) => r.status() == *status,
+            Test::Updated(cmp, date) => r.updated().is_some_and(|d| cmp.holds(d, *date)),
+            Test::Within(distance, center) => r.position().is_some_and(|p| center.distance(&p) <= *distance),
+        }
+    }
+}
+
+impl Expr {
+    fn matches(&self, r: &Repeater) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(r) && b.matches(r),
+            Expr::Or(a, b) => a.matches(r) || b.matches(r),
+            Expr::Not(e) => !e.matches(r),
+            Expr::Test(t) => t.matches(r),
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, r: &Repeater) -> bool {
+        self.expr.as_ref().is_none_or(|e| e.matches(r))
+    }
+
+    /// The items that match, in their order
+    pub fn select<'a, T, I>(&'a self, items: I) -> impl Iterator<Item = T> + 'a
+    where
+        T: Borrow<Repeater>,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: 'a,
+    {
+        items.into_iter().filter(move |r| self.matches(r.borrow()))
+    }
+
+    /// Whether this is the default filter, which matches everything
+    pub fn is_empty(&self) -> bool {
+        self.expr.is_none()
+    }
+}
+
+impl fmt::Display for Filter {
+    /// The expression, as it was written
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = FreqmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = |reason: String| FilterSnafu { text: s, reason }.build().column("where");
+        let tokens = tokenize(s).map_err(bad)?;
+        if tokens.is_empty() {
+            return Ok(Filter { text: String::new(), expr: None });
+        }
+
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or().map_err(bad)?;
+        if let Some(t) = parser.peek() {
+            return Err(bad(format!("unexpected {}", t)));
+        }
+        Ok(Filter { text: s.trim().to_owned(), expr: Some(expr) })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(&'static str),
+    Word(String),
+    /// Quoted text, never a keyword
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "\"(\""),
+            Token::Close => write!(f, "\")\""),
+            Token::Op(op) => write!(f, "{:?}", op),
+            Token::Word(w) | Token::Quoted(w) => write!(f, "{:?}", w),
+        }
+    }
+}
+
+const OPS: [&str; 8] = ["!=", "<=", ">=", "=", "~", "<", ">", "!"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '(' | ')' => {
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+                1
+            },
+            '"' => {
+                let end = rest[1..].find('"').ok_or("unterminated quote")?;
+                tokens.push(Token::Quoted(rest[1..end + 1].to_owned()));
+                end + 2
+            },
+            _ => match OPS.iter().find(|op| rest.starts_with(*op)) {
+                Some(&"!") => return Err("\"!\" is only used in \"!=\", use \"not\"".to_owned()),
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    op.len()
+                },
+                None => {
+                    let end = rest
+                        .find(|c: char| c.is_whitespace() || "()\"=!~<>".contains(c))
+                        .unwrap_or(rest.len());
+                    tokens.push(Token::Word(rest[..end].to_owned()));
+                    end
+                },
+            },
+        };
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    /// Take the next token if it's the keyword `word`
+    fn keyword(&mut self, word: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(word));
+        self.next += found as usize;
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+
+        match self.tokens.get(self.next).cloned() {
+            Some(Token::Open) => {
+                self.next += 1;
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err("missing \")\"".to_owned());
+                }
+                self.next += 1;
+                Ok(expr)
+            },
+            Some(Token::Word(field)) => {
+                self.next += 1;
+                self.test(&field.to_ascii_lowercase())
+            },
+            Some(t) => Err(format!("expected a test, found {}", t)),
+            None => Err("expected a test at the end".to_owned()),
+        }
+    }
+
+    fn op(&mut self) -> Option<&'static str> {
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("in") => "in",
+            _ => return None,
+        };
+        self.next += 1;
+        Some(op)
+    }
+
+    fn value(&mut self, after: &str) -> Result<String, String> {
+        match self.tokens.get(self.next).cloned() {
+            Some(Token::Word(v) | Token::Quoted(v)) => {
+                self.next += 1;
+                Ok(v)
+            },
+            _ => Err(format!("expected a value after {:?}", after)),
+        }
+    }
+
+    /// The test on `field`, which has been taken
+    fn test(&mut self, field: &str) -> Result<Expr, String> {
+        if field == "within" {
+            let distance = self.value("within")?;
+            let distance = distance.parse::<Distance>().map_err(reason)?;
+            if !self.keyword("of") {
+                return Err("expected \"of\" after the distance".to_owned());
+            }
+            let center = self.value("of")?.parse::<LatLon>().map_err(reason)?;
+            return Ok(Expr::Test(Test::Within(distance, center)));
+        }
+
+        let op = match self.op() {
+            Some(op) => op,
+            None if field == "tone" => return Ok(Expr::Test(Test::Tone(None))),
+            None => return Err(format!("expected a comparison after {:?}", field)),
+        };
+        let value = self.value(&format!("{} {}", field, op))?;
+        let bad_op = || format!("{:?} can't be used with {}", op, field);
+        let cmp = match op {
+            "=" | "!=" => Cmp::Eq,
+            "<" => Cmp::Lt,
+            "<=" => Cmp::Le,
+            ">" => Cmp::Gt,
+            ">=" => Cmp::Ge,
+            _ => Cmp::Eq,
+        };
+        let ordered = || if matches!(op, "~" | "in") { Err(bad_op()) } else { Ok(cmp) };
+        let equality = || if matches!(op, "=" | "!=") { Ok(()) } else { Err(bad_op()) };
+        let mhz = |text: &str| parse_mhz(text.trim()).ok_or_else(|| format!("{:?} is not a frequency", text));
+
+        let test = match field {
+            "band" => {
+                equality()?;
+                Test::Band(value.parse().map_err(reason)?)
+            },
+            "freq" | "frequency" if op == "in" => {
+                let (low, high) = value.split_once("..").ok_or_else(|| format!("{:?} is not \"<low>..<high>\"", value))?;
+                Test::FreqIn(mhz(low)?, mhz(high)?)
+            },
+            "freq" | "frequency" => Test::Freq(ordered()?, mhz(&value)?),
+            "mode" => {
+                equality()?;
+                Test::Mode(modes(&value).ok_or_else(|| format!("mode {:?} unknown", value))?)
+            },
+            "tone" => {
+                equality()?;
+                Test::Tone(Some(value.parse().map_err(reason)?))
+            },
+            "status" => {
+                equality()?;
+                Test::Status(value.parse().map_err(reason)?)
+            },
+            "updated" => {
+                let date = chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                    .map_err(|_| format!("{:?} is not a YYYY-MM-DD date", value))?;
+                Test::Updated(ordered()?, date)
+            },
+            "callsign" | "town" | "county" | "state" => {
+                let field = match field {
+                    "callsign" => Field::Callsign,
+                    "town" => Field::Town,
+                    "county" => Field::County,
+                    _ => Field::State,
+                };
+                let pattern = match op {
+                    "~" => Pattern::Glob(value),
+                    "=" | "!=" => Pattern::Exact(value),
+                    _ => return Err(bad_op()),
+                };
+                Test::Text(field, pattern)
+            },
+            _ => return Err(format!("unknown field {:?}", field)),
+        };
+
+        let expr = Expr::Test(test);
+        Ok(if op == "!=" { Expr::Not(Box::new(expr)) } else { expr })
+    }
+}
+
+/// A value's parse error, without the column the value type names
+fn reason(e: FreqmError) -> String {
+    match e {
+        FreqmError::Parse { source, .. } => source.to_string(),
+        e => e.to_string(),
+    }
+}
+
+/// A mode by its name, or all the analog or digital modes
+fn modes(name: &str) -> Option<Vec<Mode>> {
+    let name = name.trim();
+    if name.eq_ignore_ascii_case("analog") {
+        Some(Mode::ALL.into_iter().filter(|m| !m.is_digital()).collect())
+    } else if name.eq_ignore_ascii_case("digital") {
+        Some(Mode::ALL.into_iter().filter(|m| m.is_digital()).collect())
+    } else {
+        name.parse().ok().map(|m| vec![m])
+    }
+}
+
@@ -0,0 +1,704 @@
+//! Channel list file formats, and converting between them
+//!
+//! Every format in `FORMATS` can be read into `Repeater`s and written from them. Formats without
+//! a place for some of a repeater's details (e.g. Chirp has no position columns, Kenwood lists only
+//! hold the tone sent to the repeater) lose them when written. `Format::write()` finds what was
+//! lost by converting each repeater to the format's row and back, and reports the differences.
+//!
+//! Channels are written in the order, and with the numbers and groups, of a `layout::Plan`.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::channel_file::{ChannelFile, ChannelRecord};
+use crate::csv::*;
+use crate::error::*;
+use crate::geo::Located;
+use crate::icom_id51a::ChannelLine;
+use crate::import::{import, ImportReport, OnError};
+use crate::layout::{Channel, Layout, Plan};
+use crate::ne_repeater::NeRepeaterRecord;
+use crate::{Mode, Repeater};
+
+/// The channels read from a list, and how the import went
+pub type Channels = (Vec<Repeater>, ImportReport);
+
+/// Writes a file from a plan: its channels, or its groups
+pub type WritePlan = fn(&mut dyn Write, &Plan<'_>) -> Result<(), FreqmError>;
+
+#[derive(Debug)]
+pub struct Format {
+    /// Short name used on the command line
+    pub id: &'static str,
+    pub name: &'static str,
+    /// 0 for formats that aren't delimited text (JSON and YAML)
+    pub delimiter: u8,
+    /// Column names in the header row, `None` for formats without a header
+    pub columns: Option<&'static [&'static str]>,
+    /// Where the format keeps channel groups
+    pub groups: Groups,
+    read: fn(&[u8], OnError) -> Result<Channels, FreqmError>,
+    write: WritePlan,
+    /// `r` as it reads back after being written, `None` if it isn't written as a channel
+    round_trip: fn(&Repeater) -> Result<Option<Repeater>, FreqmError>,
+}
+
+/// Where a format keeps the groups of a `Plan`
+#[derive(Debug, Clone, Copy)]
+pub enum Groups {
+    /// The format has no groups, channels are written without them
+    None,
+    /// Each row has its group's name (and number)
+    Columns,
+    /// A separate file lists the channels in each group, see `Format::write_groups()`. Radios that
+    /// scan by list also get a scan list per group, see `Format::write_scan_lists()`.
+    File {
+        groups: WritePlan,
+        scan_lists: Option<WritePlan>,
+    },
+}
+
+pub const FORMATS: &[Format] = &[
+    Format {
+        id: "chirp",
+        name: "Chirp csv",
+        delimiter: b',',
+        columns: Some(&CHIRP_COLUMNS),
+        groups: Groups::None,
+        read: read_rows::<ChirpRow>,
+        write: write_rows::<ChirpRow>,
+        round_trip: round_trip_row::<ChirpRow>,
+    },
+    Format {
+        id: "at878",
+        name: "AnyTone AT-878UV II CPS channels",
+        delimiter: b',',
+        columns: Some(&AT878_COLUMNS),
+        groups: Groups::File { groups: write_at878_zones, scan_lists: Some(write_at878_scan_lists) },
+        read: read_rows::<At878Row>,
+        write: write_rows::<At878Row>,
+        round_trip: round_trip_row::<At878Row>,
+    },
+    Format {
+        id: "uv390",
+        name: "TYT MD-UV390 CPS channels",
+        delimiter: b',',
+        columns: Some(&UV390_COLUMNS),
+        groups: Groups::None,
+        read: read_rows::<Uv390Row>,
+        write: write_rows::<Uv390Row>,
+        round_trip: round_trip_row::<Uv390Row>,
+    },
+    Format {
+        id: "ne",
+        name: "NE repeater listing csv",
+        delimiter: b',',
+        columns: None,
+        groups: Groups::None,
+        read: read_ne,
+        write: write_ne,
+        round_trip: |r| Repeater::try_from(NeRepeaterRecord::from(r)).map(Some),
+    },
+    Format {
+        id: "icom",
+        name: "Icom repeater list (IRNAID51.csv)",
+        delimiter: b',',
+        columns: Some(&crate::icom_id51a::COLUMNS),
+        groups: Groups::Columns,
+        read: read_icom,
+        write: write_icom,
+        round_trip: |r| Repeater::try_from(&ChannelLine::from(r)).map(Some),
+    },
+    Format {
+        id: "kenwood",
+        name: "Kenwood TH-D74 repeater list tsv",
+        delimiter: b'\t',
+        columns: Some(&KENWOOD_COLUMNS),
+        groups: Groups::Columns,
+        read: read_rows::<KenwoodTh74aRow>,
+        write: write_kenwood,
+        round_trip: round_trip_row::<KenwoodTh74aRow>,
+    },
+    Format {
+        id: "ics",
+        name: "ICS channel plan csv (Boston Marathon, exported with Tabula)",
+        delimiter: b',',
+        columns: Some(&ICS_COLUMNS),
+        groups: Groups::Columns,
+        read: read_rows::<BostonMarathonIcsRow>,
+        write: write_rows::<BostonMarathonIcsRow>,
+        round_trip: round_trip_row::<BostonMarathonIcsRow>,
+    },
+    Format {
+        id: "json",
+        name: "freqm channel file (JSON)",
+        delimiter: 0,
+        columns: None,
+        groups: Groups::Columns,
+        read: |bytes, on_error| ChannelFile::from_json(bytes)?.repeaters(on_error),
+        write: |w, plan| ChannelFile::from_plan(plan).write_json(w),
+        round_trip: |r| ChannelRecord::from(r).repeater().map(Some),
+    },
+    Format {
+        id: "yaml",
+        name: "freqm channel file (YAML)",
+        delimiter: 0,
+        columns: None,
+        groups: Groups::Columns,
+        read: |bytes, on_error| ChannelFile::from_yaml(bytes)?.repeaters(on_error),
+        write: |w, plan| ChannelFile::from_plan(plan).write_yaml(w),
+        round_trip: |r| ChannelRecord::from(r).repeater().map(Some),
+    },
+];
+
+/// Look up a format by `id` (case insensitive)
+pub fn find(id: &str) -> Result<&'static Format, FreqmError> {
+    FORMATS
+        .iter()
+        .find(|f| f.id.eq_ignore_ascii_case(id.trim()))
+        .ok_or_else(|| ListFormatSnafu { value: id }.build().column("format"))
+}
+
+/// How many records after the header `sniff()` tries converting
+const SNIFF_RECORDS: usize = 20;
+
+/// A format a channel list might be in
+#[derive(Debug, Clone, Copy)]
+pub struct Sniffed {
+    pub format: &'static Format,
+    /// From 0 (no resemblance) to 1 (the header matches and every sampled record converts)
+    pub confidence: f64,
+}
+
+/// The most likely format of a channel list, see `sniff_all()`
+pub fn sniff(bytes: &[u8]) -> Option<Sniffed> {
+    sniff_all(bytes).into_iter().next()
+}
+
+/// Every format a channel list might be in, most likely first
+///
+/// Channel files (JSON and YAML) are recognized by reading them: a file that reads as one with at
+/// least one channel is that format for certain. YAML is a superset of JSON, so JSON files are
+/// both, JSON first.
+///
+/// Otherwise the delimiter is whichever of tab and comma is more common in the first line, and only
+/// formats using it are considered. Each is scored on its shape and on its records:
+///
+///  - shape: the fraction of the format's column names found in the header row. Extra columns
+///    (e.g. "Grid" in our Icom lists) don't count against it, missing ones do (older Kenwood lists
+///    stop at "Time Zone"). NE listings have no header, so their shape is the fraction of the first
+///    records with 13 to 15 fields starting with a frequency and an offset direction.
+///  - records: the fraction of the first `SNIFF_RECORDS` records that the format's reader converts.
+///
+/// The shape counts for 60% of the confidence.
+pub fn sniff_all(bytes: &[u8]) -> Vec<Sniffed> {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let documents: Vec<Sniffed> = FORMATS
+        .iter()
+        .filter(|f| f.delimiter == 0)
+        .filter(|f| matches!(f.read(bytes, OnError::Continue), Ok((_, report)) if report.imported > 0))
+        .map(|format| Sniffed { format, confidence: 1.0 })
+        .collect();
+    if !documents.is_empty() {
+        return documents;
+    }
+
+    let first_line = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    let count = |c: u8| first_line.iter().filter(|&&b| b == c).count();
+    let delimiter = if count(b'\t') > count(b',') { b'\t' } else { b',' };
+
+    // the header and the records after it
+    let end = bytes
+        .iter()
+        .enumerate()
+        .filter(|&(_, &b)| b == b'\n')
+        .nth(SNIFF_RECORDS)
+        .map(|(i, _)| i + 1)
+        .unwrap_or(bytes.len());
+    let sample = &bytes[..end];
+    let records: Vec<::csv::StringRecord> = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(sample)
+        .records()
+        .filter_map(Result::ok)
+        .collect();
+    let Some(header) = records.first() else { return Vec::new() };
+
+    let mut found: Vec<Sniffed> = FORMATS
+        .iter()
+        .filter(|f| f.delimiter == delimiter)
+        .filter_map(|format| {
+            let shape = match format.columns {
+                Some(columns) => {
+                    let matched = columns.iter().filter(|c| header.iter().any(|h| same_column(h, c))).count();
+                    matched as f64 / columns.len() as f64
+                }
+                None => records.iter().filter(|r| looks_like_ne(r)).count() as f64 / records.len() as f64,
+            };
+            let converted = match format.read(sample, OnError::Continue) {
+                Ok((_, report)) if report.imported > 0 => {
+                    report.imported as f64 / (report.imported + report.skipped.len()) as f64
+                }
+                _ => 0.0,
+            };
+
+            let confidence = 0.6 * shape + 0.4 * converted;
+            (confidence > 0.0).then_some(Sniffed { format, confidence })
+        })
+        .collect();
+
+    found.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    found
+}
+
+/// 13 to 15 fields, starting with a frequency and an offset direction
+fn looks_like_ne(record: &::csv::StringRecord) -> bool {
+    (13..=15).contains(&record.len())
+        && crate::ne_repeater::parse_mhz(record[0].trim()).is_some()
+        && ["+", "-", "*", "S"].contains(&record[1].trim())
+}
+
+/// Column names compared ignoring case and runs of whitespace
+fn same_column(field: &str, column: &str) -> bool {
+    let mut field = field.split_whitespace();
+    let mut column = column.split_whitespace();
+    loop {
+        match (field.next(), column.next()) {
+            (Some(a), Some(b)) if a.eq_ignore_ascii_case(b) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+impl Format {
+    /// Read every channel in `bytes`
+    pub fn read(&self, bytes: &[u8], on_error: OnError) -> Result<Channels, FreqmError> {
+        (self.read)(bytes, on_error)
+    }
+
+    /// Read every channel in a file
+    pub fn load<P: AsRef<Path>>(&self, path: P, on_error: OnError) -> Result<Channels, FreqmError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| FreqmError::from(e).with_file(path))?;
+        let (repeaters, mut report) = self.read(&bytes, on_error).map_err(|e| e.with_file(path))?;
+        report.set_file(path);
+        Ok((repeaters, report))
+    }
+
+    /// Write the repeaters the format has a channel type for, in order, reporting the ones left out
+    /// and the details lost from the rest
+    pub fn write<W: Write>(&self, w: W, repeaters: &[Repeater]) -> Result<ConvertReport, FreqmError> {
+        let (written, report) = self.check(repeaters);
+        self.write_plan(w, &Layout::default().plan(&written))?;
+        Ok(report)
+    }
+
+    /// Write the channels of a plan made from the repeaters `check()` keeps
+    pub fn write_plan<W: Write>(&self, mut w: W, plan: &Plan<'_>) -> Result<(), FreqmError> {
+        (self.write)(&mut w, plan)
+    }
+
+    /// Write the plan's groups, for formats that keep them in a separate file. Does nothing for
+    /// other formats.
+    pub fn write_groups<W: Write>(&self, mut w: W, plan: &Plan<'_>) -> Result<(), FreqmError> {
+        match self.groups {
+            Groups::File { groups, .. } => groups(&mut w, plan),
+            Groups::None | Groups::Columns => Ok(()),
+        }
+    }
+
+    /// Write a scan list for each of the plan's groups, for formats whose radios scan by list. Does
+    /// nothing for other formats.
+    pub fn write_scan_lists<W: Write>(&self, mut w: W, plan: &Plan<'_>) -> Result<(), FreqmError> {
+        match self.groups {
+            Groups::File { scan_lists: Some(write), .. } => write(&mut w, plan),
+            _ => Ok(()),
+        }
+    }
+
+    /// The repeaters the format has a channel type for, and a report of the ones left out and the
+    /// details that would be lost from the rest
+    pub fn check<'a>(&self, repeaters: impl IntoIterator<Item = &'a Repeater>) -> (Vec<&'a Repeater>, ConvertReport) {
+        let mut report = ConvertReport::default();
+        let mut written = Vec::new();
+
+        for r in repeaters {
+            let left_out = |reason: String| LeftOut {
+                callsign: r.callsign().to_owned(),
+                output_freq: r.output_freq(),
+                reason,
+            };
+
+            match (self.round_trip)(r) {
+                // the row would claim a mode the repeater doesn't have
+                Ok(Some(back)) if back.modes().iter().any(|m| !r.modes().contains(m)) => {
+                    report.left_out.push(left_out(format!("no {} channels", modes(r.modes()))));
+                }
+                Ok(Some(back)) => {
+                    report.unrepresented.extend(lost(r, &back).into_iter().map(|(field, value)| Unrepresented {
+                        callsign: r.callsign().to_owned(),
+                        output_freq: r.output_freq(),
+                        field,
+                        value,
+                    }));
+                    written.push(r);
+                }
+                Ok(None) => report.left_out.push(left_out("not a channel".to_owned())),
+                Err(e) => report.left_out.push(left_out(e.to_string())),
+            }
+        }
+
+        report.written = written.len();
+        (written, report)
+    }
+}
+
+/// "DMR/Fm"
+fn modes(modes: &[Mode]) -> String {
+    modes.iter().map(|m| format!("{:?}", m)).collect::<Vec<_>>().join("/")
+}
+
+/// The fields of `r` that read back differently (as `back`), with their values in `r`
+fn lost(r: &Repeater, back: &Repeater) -> Vec<(&'static str, String)> {
+    fn show<T: fmt::Display>(value: Option<T>) -> String {
+        value.map(|v| v.to_string()).unwrap_or_else(|| "none".to_owned())
+    }
+
+    let mut lost = Vec::new();
+    let missing: Vec<Mode> = r.modes().iter().copied().filter(|m| !back.modes().contains(m)).collect();
+    if !missing.is_empty() {
+        lost.push(("modes", modes(&missing)));
+    }
+    if back.callsign() != r.callsign() {
+        lost.push(("callsign", r.callsign().to_owned()));
+    }
+    if back.output_freq() != r.output_freq() {
+        lost.push(("output_freq", r.output_freq().to_string()));
+    }
+    // a missing input frequency is written as simplex
+    if r.input_freq().is_some() && back.input_freq() != r.input_freq() {
+        lost.push(("input_freq", show(r.input_freq())));
+    }
+    if back.code_in() != r.code_in() {
+        lost.push(("code_in", show(r.code_in())));
+    }
+    if back.code_out() != r.code_out() {
+        lost.push(("code_out", show(r.code_out())));
+    }
+    if r.dstar_module().is_some() && back.dstar_module() != r.dstar_module() {
+        lost.push(("dstar_module", show(r.dstar_module())));
+    }
+    if back.status() != r.status() {
+        lost.push(("status", format!("{:?}", r.status())));
+    }
+    if back.irlp_node() != r.irlp_node() {
+        lost.push(("irlp_node", show(r.irlp_node())));
+    }
+    if back.echolink_node() != r.echolink_node() {
+        lost.push(("echolink_node", show(r.echolink_node())));
+    }
+    if r.comment().is_some() && back.comment() != r.comment() {
+        lost.push(("comment", show(r.comment())));
+    }
+    if back.updated() != r.updated() {
+        lost.push(("updated", show(r.updated())));
+    }
+    // positions are allowed to move a little, as some formats round them
+    match (r.position(), back.position()) {
+        (Some(p), None) => lost.push(("position", p.to_string())),
+        (Some(p), Some(q)) if p.distance(&q).as_km() > 0.1 => lost.push(("position", p.to_string())),
+        _ => {}
+    }
+
+    lost
+}
+
+/// A repeater that wasn't written, because the format has no channel for it
+#[derive(Debug)]
+pub struct LeftOut {
+    pub callsign: String,
+    pub output_freq: decimal::d128,
+    pub reason: String,
+}
+
+impl fmt::Display for LeftOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.callsign, self.output_freq, self.reason)
+    }
+}
+
+/// A value the format has no place for, which was dropped (or changed) when its repeater was written
+#[derive(Debug)]
+pub struct Unrepresented {
+    pub callsign: String,
+    pub output_freq: decimal::d128,
+    /// Name of the `Repeater` field
+    pub field: &'static str,
+    /// The value that was lost, as text
+    pub value: String,
+}
+
+impl fmt::Display for Unrepresented {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {} {}", self.callsign, self.output_freq, self.field, self.value)
+    }
+}
+
+/// Summary of writing repeaters in a format
+#[derive(Debug, Default)]
+pub struct ConvertReport {
+    pub written: usize,
+    pub left_out: Vec<LeftOut>,
+    pub unrepresented: Vec<Unrepresented>,
+}
+
+impl ConvertReport {
+    /// Write every left out repeater and lost value, followed by the summary line
+    pub fn write_details<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        for l in &self.left_out {
+            writeln!(w, "left out: {}", l)?;
+        }
+
+        for u in &self.unrepresented {
+            writeln!(w, "not representable: {}", u)?;
+        }
+
+        writeln!(w, "{}", self)
+    }
+}
+
+impl fmt::Display for ConvertReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wrote {}, left out {}, {} values not representable",
+            self.written,
+            self.left_out.len(),
+            self.unrepresented.len()
+        )
+    }
+}
+
+/// A row of one of the formats in `crate::csv`
+trait Row: Serialize + DeserializeOwned {
+    const COLUMNS: &'static [&'static str];
+    const DELIMITER: u8 = b',';
+
+    /// `None` for rows that aren't channels
+    fn repeater(&self) -> Result<Option<Repeater>, FreqmError>;
+
+    /// The row for a channel of `plan`
+    fn channel(c: &Channel<'_>, plan: &Plan<'_>) -> Self;
+}
+
+impl Row for ChirpRow {
+    const COLUMNS: &'static [&'static str] = &CHIRP_COLUMNS;
+
+    fn repeater(&self) -> Result<Option<Repeater>, FreqmError> {
+        Repeater::try_from(self).map(Some)
+    }
+
+    fn channel(c: &Channel<'_>, _: &Plan<'_>) -> Self {
+        let row = ChirpRow { location: c.number.to_string(), ..c.repeater.into() };
+        ChirpRow { name: c.name.clone().unwrap_or(row.name), ..row }
+    }
+}
+
+impl Row for At878Row {
+    const COLUMNS: &'static [&'static str] = &AT878_COLUMNS;
+
+    fn repeater(&self) -> Result<Option<Repeater>, FreqmError> {
+        Repeater::try_from(self).map(Some)
+    }
+
+    /// Channels in a group are in its scan list, see `write_at878_scan_lists()`
+    fn channel(c: &Channel<'_>, plan: &Plan<'_>) -> Self {
+        let row = At878Row { no: c.number.to_string(), ..c.repeater.into() };
+        let scan_list = plan.group_of(c).map(|g| g.name.chars().take(AT878_NAME_LEN).collect()).unwrap_or(row.scan_list);
+        At878Row { channel_name: c.name.clone().unwrap_or(row.channel_name), scan_list, ..row }
+    }
+}
+
+impl Row for Uv390Row {
+    const COLUMNS: &'static [&'static str] = &UV390_COLUMNS;
+
+    fn repeater(&self) -> Result<Option<Repeater>, FreqmError> {
+        Repeater::try_from(self).map(Some)
+    }
+
+    fn channel(c: &Channel<'_>, _: &Plan<'_>) -> Self {
+        let row: Uv390Row = c.repeater.into();
+        Uv390Row { channel_name: c.name.clone().unwrap_or(row.channel_name), ..row }
+    }
+}
+
+impl Row for KenwoodTh74aRow {
+    const COLUMNS: &'static [&'static str] = &KENWOOD_COLUMNS;
+    const DELIMITER: u8 = b'\t';
+
+    fn repeater(&self) -> Result<Option<Repeater>, FreqmError> {
+        Repeater::try_from(self).map(Some)
+    }
+
+    fn channel(c: &Channel<'_>, plan: &Plan<'_>) -> Self {
+        let mut row: KenwoodTh74aRow = c.repeater.into();
+        if let Some(name) = &c.name {
+            row.name = name.clone();
+        }
+        if let (Some(g), Some(group)) = (c.group, plan.group_of(c)) {
+            row.gn = (g + 1).to_string();
+            row.group = group.name.clone();
+        }
+        row
+    }
+}
+
+impl Row for BostonMarathonIcsRow {
+    const COLUMNS: &'static [&'static str] = &ICS_COLUMNS;
+
+    fn repeater(&self) -> Result<Option<Repeater>, FreqmError> {
+        if !self.is_channel() {
+            return Ok(None);
+        }
+        Repeater::try_from(self).map(Some)
+    }
+
+    /// Groups go in the "Function" column, in place of the function the channel was read with
+    fn channel(c: &Channel<'_>, plan: &Plan<'_>) -> Self {
+        let row = BostonMarathonIcsRow::from(c.repeater);
+        let function = plan.group_of(c).map(|g| g.name.clone()).unwrap_or(row.function);
+        let channel_name = c.name.clone().unwrap_or(row.channel_name);
+        BostonMarathonIcsRow { ch_number: c.number.to_string(), function, channel_name, ..row }
+    }
+}
+
+fn read_rows<T: Row>(bytes: &[u8], on_error: OnError) -> Result<Channels, FreqmError> {
+    let mut csv = ::csv::ReaderBuilder::new().delimiter(T::DELIMITER).flexible(true).from_reader(bytes);
+    let (rows, mut report) = import(&mut csv, on_error, |record, _| record.deserialize::<T>(None)?.repeater())?;
+    // rows that aren't channels were imported, but don't count
+    let (records, repeaters): (Vec<u64>, Vec<Repeater>) =
+        report.records.iter().zip(rows).filter_map(|(&n, r)| Some((n, r?))).unzip();
+    report.imported = repeaters.len();
+    report.records = records;
+    Ok((repeaters, report))
+}
+
+fn write_rows<T: Row>(w: &mut dyn Write, plan: &Plan<'_>) -> Result<(), FreqmError> {
+    let mut csv = ::csv::WriterBuilder::new().delimiter(T::DELIMITER).has_headers(false).from_writer(w);
+    csv.write_record(T::COLUMNS)?;
+    for c in &plan.channels {
+        csv.serialize(T::channel(c, plan))?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn round_trip_row<T: Row>(r: &Repeater) -> Result<Option<Repeater>, FreqmError> {
+    T::channel(&Channel { repeater: r, number: 1, group: None, name: None }, &Plan::default()).repeater()
+}
+
+fn read_ne(bytes: &[u8], on_error: OnError) -> Result<Channels, FreqmError> {
+    let (records, report) = crate::ne_repeater::import(bytes, on_error)?;
+    // every record was already converted once by `import()`, so these can't fail
+    let repeaters = records.into_iter().map(Repeater::try_from).collect::<Result<_, _>>()?;
+    Ok((repeaters, report))
+}
+
+fn write_ne(w: &mut dyn Write, plan: &Plan<'_>) -> Result<(), FreqmError> {
+    let mut csv = ::csv::WriterBuilder::new().has_headers(false).from_writer(w);
+    for c in &plan.channels {
+        csv.serialize(NeRepeaterRecord::from(c.repeater))?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+fn read_icom(bytes: &[u8], on_error: OnError) -> Result<Channels, FreqmError> {
+    let mut csv = ::csv::ReaderBuilder::new().flexible(true).from_reader(bytes);
+    import(&mut csv, on_error, |record, _| Repeater::try_from(&ChannelLine::try_from(record)?))
+}
+
+fn write_icom(w: &mut dyn Write, plan: &Plan<'_>) -> Result<(), FreqmError> {
+    let lines: Vec<ChannelLine> = plan
+        .channels
+        .iter()
+        .map(|c| {
+            let mut line = ChannelLine::from(c.repeater);
+            if let Some(name) = &c.name {
+                line.name = name.clone();
+            }
+            if let (Some(g), Some(group)) = (c.group, plan.group_of(c)) {
+                line.group_number = g as u64 + 1;
+                line.group_name = group.name.clone();
+            }
+            line
+        })
+        .collect();
+    crate::icom_id51a::write_csv(w, &lines)
+}
+
+fn write_kenwood(w: &mut dyn Write, plan: &Plan<'_>) -> Result<(), FreqmError> {
+    let rows: Vec<KenwoodTh74aRow> = plan.channels.iter().map(|c| KenwoodTh74aRow::channel(c, plan)).collect();
+    crate::csv::write_kenwood_tsv(w, &rows)
+}
+
+/// The AT-878 CPS zone list: each zone's channels by name and frequencies, separated by "|". Both
+/// VFOs start on the zone's first channel.
+fn write_at878_zones(w: &mut dyn Write, plan: &Plan<'_>) -> Result<(), FreqmError> {
+    let mut csv = ::csv::WriterBuilder::new().has_headers(false).from_writer(w);
+    csv.write_record(AT878_ZONE_COLUMNS)?;
+    for (n, group) in plan.groups.iter().enumerate() {
+        let rows: Vec<At878Row> = group.channels.iter().map(|&c| At878Row::channel(&plan.channels[c], plan)).collect();
+        let join = |field: fn(&At878Row) -> &str| rows.iter().map(field).collect::<Vec<_>>().join("|");
+        let first = |field: fn(&At878Row) -> &str| rows.first().map(field).unwrap_or_default().to_owned();
+        let a = [first(|r| &r.channel_name), first(|r| &r.receive_frequency), first(|r| &r.transmit_frequency)];
+
+        let mut record = vec![
+            (n + 1).to_string(),
+            group.name.chars().take(AT878_NAME_LEN).collect(),
+            join(|r| &r.channel_name),
+            join(|r| &r.receive_frequency),
+            join(|r| &r.transmit_frequency),
+        ];
+        record.extend(a.iter().cloned());
+        record.extend(a);
+        csv.write_record(&record)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// The AT-878 CPS scan list list: a scan list for each zone, named after it, with the same
+/// channels. The radio takes at most `AT878_SCAN_LIST_LEN` channels in a list.
+fn write_at878_scan_lists(w: &mut dyn Write, plan: &Plan<'_>) -> Result<(), FreqmError> {
+    let mut csv = ::csv::WriterBuilder::new().has_headers(false).from_writer(w);
+    csv.write_record(AT878_SCAN_LIST_COLUMNS)?;
+    for (n, group) in plan.groups.iter().enumerate() {
+        if group.channels.len() > AT878_SCAN_LIST_LEN {
+            let found = group.channels.len();
+            return Err(GroupSizeSnafu { group: &group.name, found, max: AT878_SCAN_LIST_LEN }.build().column("Scan Channel Member"));
+        }
+
+        let rows: Vec<At878Row> = group.channels.iter().map(|&c| At878Row::channel(&plan.channels[c], plan)).collect();
+        let join = |field: fn(&At878Row) -> &str| rows.iter().map(field).collect::<Vec<_>>().join("|");
+
+        let mut record = vec![
+            (n + 1).to_string(),
+            group.name.chars().take(AT878_NAME_LEN).collect(),
+            join(|r| &r.channel_name),
+            join(|r| &r.receive_frequency),
+            join(|r| &r.transmit_frequency),
+        ];
+        record.extend(AT878_SCAN_LIST_DEFAULTS.iter().map(|&d| d.to_owned()));
+        csv.write_record(&record)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
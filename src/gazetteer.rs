@@ -0,0 +1,142 @@
+//! Approximate positions for towns, from an offline list of places
+//!
+//! Listings like the NE repeater listing only give a repeater's town and state. A `Gazetteer`
+//! looks those up in a places file to get a position for distance queries. The position is the
+//! town's center (or a point inside it), so sites located this way are marked approximate.
+//!
+//! Two kinds of places file are read:
+//!
+//!  - US Census Bureau gazetteer files (tab separated, with `USPS`, `NAME`, `INTPTLAT` and
+//!    `INTPTLONG` columns). In New England, towns are "county subdivisions", so the county
+//!    subdivision file (`*_Gaz_cousubs_national.txt`) finds more of them than the places file.
+//!  - csv files with a `state,town,lat,lon` header, for places the census files miss
+//!
+//! Town names are compared ignoring case, punctuation, the census' type suffixes ("Weston town",
+//! "Boston city") and common abbreviations ("Mt." for "Mount", "N." for "North").
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::error::*;
+use crate::geo::LatLon;
+
+/// Type suffixes on census place names
+const SUFFIXES: &[&str] = &["city", "town", "township", "village", "borough", "cdp", "plantation", "gore", "grant"];
+
+const ABBREVIATIONS: &[(&str, &str)] = &[
+    ("mount", "mt"),
+    ("saint", "st"),
+    ("fort", "ft"),
+    ("north", "n"),
+    ("south", "s"),
+    ("east", "e"),
+    ("west", "w"),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Gazetteer {
+    /// (state, normalized town) to position
+    places: HashMap<(String, String), LatLon>,
+}
+
+impl Gazetteer {
+    pub fn new() -> Self {
+        Gazetteer::default()
+    }
+
+    /// Add the places in a census gazetteer file or a `state,town,lat,lon` csv file. Places that
+    /// are already known keep their first position.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), FreqmError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| FreqmError::from(e).with_file(path))?;
+        self.read(&bytes).map_err(|e| e.with_file(path))
+    }
+
+    /// Add the places in the contents of a places file (see `load()`)
+    pub fn read(&mut self, bytes: &[u8]) -> Result<(), FreqmError> {
+        let census = bytes.split(|&b| b == b'\n').next().unwrap_or_default().contains(&b'\t');
+        let (delimiter, columns) = if census {
+            (b'\t', ["USPS", "NAME", "INTPTLAT", "INTPTLONG"])
+        } else {
+            (b',', ["state", "town", "lat", "lon"])
+        };
+        let mut csv = ::csv::ReaderBuilder::new().delimiter(delimiter).flexible(true).from_reader(bytes);
+
+        let headers = csv.byte_headers()?.clone();
+        let mut index = [0; 4];
+        for (i, &column) in index.iter_mut().zip(columns.iter()) {
+            // the census files pad the last header with spaces
+            *i = headers
+                .iter()
+                .position(|h| String::from_utf8_lossy(h).trim().eq_ignore_ascii_case(column))
+                .ok_or_else(|| FreqmError::from(FormatError::MissingColumn { column }))?;
+        }
+
+        for record in csv.byte_records() {
+            let record = record?;
+            let field = |i: usize| String::from_utf8_lossy(record.get(index[i]).unwrap_or_default()).trim().to_owned();
+            let coordinate = |i: usize| {
+                let text = field(i);
+                text.parse::<f64>().map_err(|_| {
+                    let (line, n) = record.position().map(|p| (p.line(), p.record())).unwrap_or_default();
+                    NumberSnafu { value: text }.build().column(columns[i]).with_row(line, n)
+                })
+            };
+
+            let (state, town) = (field(0), field(1));
+            let (lat, lon) = (coordinate(2)?, coordinate(3)?);
+            if let Some(pos) = LatLon::new(lat, lon) {
+                self.add(&state, &town, pos);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a place, unless it is already known
+    pub fn add(&mut self, state: &str, town: &str, pos: LatLon) {
+        self.places.entry(key(state, town)).or_insert(pos);
+    }
+
+    pub fn lookup(&self, state: &str, town: &str) -> Option<LatLon> {
+        self.places.get(&key(state, town)).copied()
+    }
+
+    /// Look up "<town>, <state>"
+    pub fn lookup_place(&self, place: &str) -> Option<LatLon> {
+        let (town, state) = place.rsplit_once(',')?;
+        self.lookup(state, town)
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+}
+
+fn key(state: &str, town: &str) -> (String, String) {
+    (state.trim().to_ascii_uppercase(), normalize(town))
+}
+
+/// Lower case words without punctuation, type suffixes or long forms of abbreviations
+fn normalize(town: &str) -> String {
+    let cleaned: String = town
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { ' ' })
+        .collect();
+    let mut words: Vec<&str> = cleaned.split_whitespace().collect();
+
+    // "Boston city", "Weston town", but not a town that is just "Town"
+    if words.len() > 1 && words.last().is_some_and(|w| SUFFIXES.contains(w)) {
+        words.pop();
+    }
+
+    let words: Vec<&str> = words
+        .into_iter()
+        .map(|w| ABBREVIATIONS.iter().find(|(long, _)| *long == w).map(|(_, short)| *short).unwrap_or(w))
+        .collect();
+    words.join(" ")
+}
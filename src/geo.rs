@@ -0,0 +1,356 @@
+//! Positions, distances and proximity queries
+//!
+//! Distances are great circle distances from the haversine formula on a spherical earth, which is
+//! well within the precision of any repeater listing's coordinates.
+//!
+//! Positions can also be given as Maidenhead grid locators (`Grid`), e.g. "FN42" or "FN42ki".
+
+use std::fmt;
+
+use crate::error::*;
+
+/// Mean radius of the earth
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+const KM_PER_MILE: f64 = 1.609344;
+
+/// A point on the earth, in decimal degrees. North and east are positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl LatLon {
+    /// `None` if either coordinate is out of range
+    pub fn new(lat: f64, lon: f64) -> Option<Self> {
+        if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) {
+            Some(LatLon { lat, lon })
+        } else {
+            None
+        }
+    }
+
+    /// Great circle distance
+    pub fn distance(&self, to: &LatLon) -> Distance {
+        let (lat1, lat2) = (self.lat.to_radians(), to.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (to.lon - self.lon).to_radians();
+
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+        Distance::km(EARTH_RADIUS_KM * c)
+    }
+
+    /// Initial bearing of the great circle path to `to`, in degrees clockwise from true north
+    /// (0 to 360)
+    pub fn bearing(&self, to: &LatLon) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), to.lat.to_radians());
+        let dlon = (to.lon - self.lon).to_radians();
+
+        let y = dlon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+        (y.atan2(x).to_degrees() + 360.0) % 360.0
+    }
+}
+
+impl fmt::Display for LatLon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.5},{:.5}", self.lat, self.lon)
+    }
+}
+
+impl std::str::FromStr for LatLon {
+    type Err = FreqmError;
+
+    /// "<lat>,<lon>" in decimal degrees, or a grid locator (meaning the center of the square)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || PositionSnafu { value: s }.build().column("position");
+        let (lat, lon) = match s.split_once(',') {
+            Some(latlon) => latlon,
+            None => return s.parse::<Grid>().map(|g| g.center()).map_err(|_| bad()),
+        };
+        let lat = lat.trim().parse().map_err(|_| bad())?;
+        let lon = lon.trim().parse().map_err(|_| bad())?;
+        LatLon::new(lat, lon).ok_or_else(bad)
+    }
+}
+
+/// A Maidenhead grid locator: a field (2 letters), optionally followed by a square (2 digits), a
+/// subsquare (2 letters) and an extended square (2 digits)
+///
+/// Fields are written in upper case and subsquares in lower case, e.g. "FN42ki".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid {
+    code: String,
+}
+
+/// Width and height of each pair of a locator, in degrees of longitude and latitude
+const GRID_PAIRS: [(f64, f64, u8); 4] = [
+    (20.0, 10.0, 18),
+    (2.0, 1.0, 10),
+    (2.0 / 24.0, 1.0 / 24.0, 24),
+    (2.0 / 240.0, 1.0 / 240.0, 10),
+];
+
+impl Grid {
+    /// The grid square containing `pos`, with `chars` characters (2, 4, 6 or 8)
+    ///
+    /// # Panics
+    ///
+    /// If `chars` isn't one of the above.
+    pub fn of(pos: LatLon, chars: usize) -> Grid {
+        assert!(matches!(chars, 2 | 4 | 6 | 8), "grid locators have 2, 4, 6 or 8 characters");
+
+        // the north pole and the antimeridian belong to the last square
+        let mut lon = (pos.lon + 180.0).clamp(0.0, 360.0 - 1e-9);
+        let mut lat = (pos.lat + 90.0).clamp(0.0, 180.0 - 1e-9);
+
+        let mut code = String::with_capacity(chars);
+        for (i, &(width, height, count)) in GRID_PAIRS[..chars / 2].iter().enumerate() {
+            let x = ((lon / width) as u8).min(count - 1);
+            let y = ((lat / height) as u8).min(count - 1);
+            lon -= x as f64 * width;
+            lat -= y as f64 * height;
+
+            let base = match i {
+                0 => b'A',
+                2 => b'a',
+                _ => b'0',
+            };
+            code.push((base + x) as char);
+            code.push((base + y) as char);
+        }
+
+        Grid { code }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.code
+    }
+
+    /// South west and north east corners
+    pub fn bounds(&self) -> (LatLon, LatLon) {
+        let (mut lon, mut lat) = (-180.0, -90.0);
+        let mut size = (0.0, 0.0);
+        for (pair, &(width, height, _)) in self.code.as_bytes().chunks(2).zip(GRID_PAIRS.iter()) {
+            let base = if pair[0].is_ascii_digit() { b'0' } else if pair[0].is_ascii_uppercase() { b'A' } else { b'a' };
+            lon += (pair[0] - base) as f64 * width;
+            lat += (pair[1] - base) as f64 * height;
+            size = (width, height);
+        }
+
+        (LatLon { lat, lon }, LatLon { lat: lat + size.1, lon: lon + size.0 })
+    }
+
+    pub fn center(&self) -> LatLon {
+        let (sw, ne) = self.bounds();
+        LatLon { lat: (sw.lat + ne.lat) / 2.0, lon: (sw.lon + ne.lon) / 2.0 }
+    }
+
+    /// Whether `pos` is in this square. Squares include their south and west edges.
+    pub fn contains(&self, pos: &LatLon) -> bool {
+        Grid::of(*pos, self.code.len()) == *self
+    }
+}
+
+impl fmt::Display for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.code)
+    }
+}
+
+impl std::str::FromStr for Grid {
+    type Err = FreqmError;
+
+    /// Case insensitive
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || GridSnafu { value: s }.build().column("grid");
+        let text = s.trim();
+        if !matches!(text.len(), 2 | 4 | 6 | 8) || !text.is_ascii() {
+            return Err(bad());
+        }
+
+        let mut code = String::with_capacity(text.len());
+        for (i, pair) in text.as_bytes().chunks(2).enumerate() {
+            let (base, count) = match i {
+                0 => (b'A', 18),
+                2 => (b'a', 24),
+                _ => (b'0', 10),
+            };
+            for &c in pair {
+                let c = if i == 0 { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() };
+                if c < base || c >= base + count {
+                    return Err(bad());
+                }
+                code.push(c as char);
+            }
+        }
+
+        Ok(Grid { code })
+    }
+}
+
+/// The 16 point compass direction nearest to `bearing` (degrees)
+pub fn compass(bearing: f64) -> &'static str {
+    const POINTS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW",
+    ];
+    POINTS[((bearing.rem_euclid(360.0) / 22.5).round() as usize) % 16]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceUnit {
+    #[default]
+    Km,
+    Mi,
+}
+
+impl DistanceUnit {
+    fn km_per_unit(self) -> f64 {
+        match self {
+            DistanceUnit::Km => 1.0,
+            DistanceUnit::Mi => KM_PER_MILE,
+        }
+    }
+}
+
+impl fmt::Display for DistanceUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DistanceUnit::Km => "km",
+            DistanceUnit::Mi => "mi",
+        })
+    }
+}
+
+/// A distance, and the unit to show it in
+///
+/// Distances compare by length, whatever unit they're shown in.
+#[derive(Debug, Clone, Copy)]
+pub struct Distance {
+    km: f64,
+    unit: DistanceUnit,
+}
+
+impl Distance {
+    pub fn km(km: f64) -> Self {
+        Distance { km, unit: DistanceUnit::Km }
+    }
+
+    pub fn miles(mi: f64) -> Self {
+        Distance { km: mi * KM_PER_MILE, unit: DistanceUnit::Mi }
+    }
+
+    pub fn as_km(&self) -> f64 {
+        self.km
+    }
+
+    pub fn as_miles(&self) -> f64 {
+        self.km / KM_PER_MILE
+    }
+
+    pub fn unit(&self) -> DistanceUnit {
+        self.unit
+    }
+
+    /// The same distance, shown in `unit`
+    pub fn in_unit(self, unit: DistanceUnit) -> Self {
+        Distance { unit, ..self }
+    }
+
+    /// The distance in its unit
+    pub fn value(&self) -> f64 {
+        self.km / self.unit.km_per_unit()
+    }
+}
+
+impl PartialEq for Distance {
+    fn eq(&self, other: &Self) -> bool {
+        self.km == other.km
+    }
+}
+
+impl PartialOrd for Distance {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.km.partial_cmp(&other.km)
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.precision() {
+            Some(p) => write!(f, "{:.*}{}", p, self.value(), self.unit),
+            None => write!(f, "{:.1}{}", self.value(), self.unit),
+        }
+    }
+}
+
+impl std::str::FromStr for Distance {
+    type Err = FreqmError;
+
+    /// A number followed by "mi" or "km", e.g. "30mi"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || DistanceSnafu { value: s }.build().column("distance");
+        let s = s.trim();
+        let split = s.find(|c: char| c.is_ascii_alphabetic()).ok_or_else(bad)?;
+        let (value, unit) = s.split_at(split);
+        let value: f64 = value.trim().parse().map_err(|_| bad())?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(bad());
+        }
+
+        match unit.to_ascii_lowercase().as_str() {
+            "km" => Ok(Distance::km(value)),
+            "mi" | "mile" | "miles" => Ok(Distance::miles(value)),
+            _ => Err(bad()),
+        }
+    }
+}
+
+/// Anything with a position
+pub trait Located {
+    /// `None` if the position isn't known
+    fn position(&self) -> Option<LatLon>;
+}
+
+impl<T: Located> Located for &T {
+    fn position(&self) -> Option<LatLon> {
+        (*self).position()
+    }
+}
+
+impl Located for LatLon {
+    fn position(&self) -> Option<LatLon> {
+        Some(*self)
+    }
+}
+
+/// An item found by `near()`
+#[derive(Debug, Clone)]
+pub struct Nearby<T> {
+    pub item: T,
+    /// From the search center, in the unit of the search radius
+    pub distance: Distance,
+    /// From the search center, degrees clockwise from true north
+    pub bearing: f64,
+}
+
+/// Items within `radius` of `center`, nearest first. Items without a position are skipped.
+pub fn near<T, I>(items: I, center: LatLon, radius: Distance) -> Vec<Nearby<T>>
+where
+    T: Located,
+    I: IntoIterator<Item = T>,
+{
+    let mut found: Vec<Nearby<T>> = items
+        .into_iter()
+        .filter_map(|item| {
+            let pos = item.position()?;
+            let distance = center.distance(&pos).in_unit(radius.unit());
+            (distance <= radius).then(|| Nearby { bearing: center.bearing(&pos), distance, item })
+        })
+        .collect();
+
+    found.sort_by(|a, b| a.distance.as_km().total_cmp(&b.distance.as_km()));
+    found
+}
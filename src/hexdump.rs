@@ -0,0 +1,315 @@
+//! Hexdumps of `SparseMem` images, and diffs between two images
+//!
+//! Both print 16 bytes per row with an ASCII column. Unpopulated bytes are left blank, and runs of
+//! unpopulated rows are collapsed into a single "gap" line. Rows can be annotated with the names of
+//! the regions of a memory map that start in them.
+//!
+//! The diff only prints rows that differ, as a `-` row from the old image followed by a `+` row
+//! from the new one. Changed bytes are colored, or marked with `^` on the following line when
+//! color is off.
+
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::image::parse_addr;
+use crate::sparse_mem::SparseMem;
+
+const WIDTH: u64 = 16;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// A named range of addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub range: Range<u64>,
+    pub name: String,
+}
+
+/// Names for regions of an image, used to label dumps
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    // sorted by start address
+    regions: Vec<Region>,
+}
+
+impl Annotations {
+    pub fn new(mut regions: Vec<Region>) -> Self {
+        regions.sort_by_key(|r| (r.range.start, r.range.end));
+        Annotations { regions }
+    }
+
+    /// Load a list of regions, one per line: "<start>..<end> <name>" or "<start>+<len> <name>"
+    ///
+    /// Addresses are decimal or "0x" prefixed hex. Blank lines and lines starting with `#` are
+    /// ignored.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FreqmError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        text.parse().map_err(|e: FreqmError| e.with_file(path))
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Regions starting within `range`
+    pub fn starting_in(&self, range: Range<u64>) -> impl Iterator<Item = &Region> {
+        let first = self.regions.partition_point(|r| r.range.start < range.start);
+        self.regions[first..]
+            .iter()
+            .take_while(move |r| r.range.start < range.end)
+    }
+
+    /// Regions containing `addr`
+    pub fn containing(&self, addr: u64) -> impl Iterator<Item = &Region> {
+        let end = self.regions.partition_point(|r| r.range.start <= addr);
+        self.regions[..end]
+            .iter()
+            .filter(move |r| r.range.contains(&addr))
+    }
+}
+
+impl std::str::FromStr for Annotations {
+    type Err = FreqmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut regions = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bad = || {
+                let mut e = AnnotationSnafu { value: line }.build().column("region");
+                e.set_row(i as u64 + 1, regions.len() as u64 + 1);
+                e
+            };
+
+            let (span, name) = line.split_once(char::is_whitespace).ok_or_else(bad)?;
+            let range = if let Some((start, end)) = span.split_once("..") {
+                parse_addr(start).map_err(|_| bad())?..parse_addr(end).map_err(|_| bad())?
+            } else if let Some((start, len)) = span.split_once('+') {
+                let start = parse_addr(start).map_err(|_| bad())?;
+                let len = parse_addr(len).map_err(|_| bad())?;
+                start..start.checked_add(len).ok_or_else(bad)?
+            } else {
+                return Err(bad());
+            };
+
+            regions.push(Region { range, name: name.trim().to_owned() });
+        }
+
+        Ok(Annotations::new(regions))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DumpOptions<'a> {
+    /// Only show this part of the image. Defaults to everything that is populated. Nothing past
+    /// the last populated byte is shown.
+    pub range: Option<Range<u64>>,
+    pub annotations: Option<&'a Annotations>,
+    /// Use ANSI colors
+    pub color: bool,
+}
+
+type Cells = [Option<u8>; WIDTH as usize];
+
+/// The bytes of the row starting at `row`, limited to `range`
+fn cells(mem: &SparseMem, row: u64, range: &Range<u64>) -> Cells {
+    let mut cells = [None; WIDTH as usize];
+    for (i, c) in cells.iter_mut().enumerate() {
+        let addr = row + i as u64;
+        if range.contains(&addr) {
+            *c = mem.get(addr..addr + 1).map(|b| b[0]);
+        }
+    }
+    cells
+}
+
+/// The first populated address in `from..end`, or `end` if there isn't one
+fn next_populated(mem: &SparseMem, from: u64, end: u64) -> u64 {
+    match mem.gaps(from..end).next() {
+        Some(gap) if gap.start == from => gap.end,
+        _ => from,
+    }
+}
+
+fn row_of(addr: u64) -> u64 {
+    addr - addr % WIDTH
+}
+
+/// Write one row, with the bytes where `highlight` is set colored with `color`
+fn write_row<W: Write>(
+    w: &mut W,
+    prefix: &str,
+    row: u64,
+    cells: &Cells,
+    highlight: &[bool; WIDTH as usize],
+    color: Option<&str>,
+    labels: &[&str],
+) -> io::Result<()> {
+    let mut hex = String::new();
+    let mut ascii = String::new();
+    for (i, c) in cells.iter().enumerate() {
+        if i == WIDTH as usize / 2 {
+            hex.push(' ');
+        }
+
+        let text = c.map(|b| format!("{:02x}", b)).unwrap_or_else(|| "  ".to_owned());
+        let ch = c.map(|b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).unwrap_or(' ');
+        match color {
+            Some(color) if highlight[i] => {
+                hex.push_str(&format!(" {}{}{}", color, text, RESET));
+                ascii.push_str(&format!("{}{}{}", color, ch, RESET));
+            }
+            _ => {
+                hex.push(' ');
+                hex.push_str(&text);
+                ascii.push(ch);
+            }
+        }
+    }
+
+    write!(w, "{}{:08x} {} |{}|", prefix, row, hex, ascii)?;
+    if !labels.is_empty() {
+        write!(w, "  {}", labels.join(", "))?;
+    }
+    writeln!(w)
+}
+
+/// Mark the highlighted bytes of the row above with `^`, for when color is off
+fn write_marks<W: Write>(w: &mut W, prefix: &str, highlight: &[bool; WIDTH as usize]) -> io::Result<()> {
+    let mut marks = String::new();
+    for (i, &h) in highlight.iter().enumerate() {
+        if i == WIDTH as usize / 2 {
+            marks.push(' ');
+        }
+        marks.push_str(if h { " ^^" } else { "   " });
+    }
+
+    writeln!(w, "{}{:8} {}", " ".repeat(prefix.len()), "", marks.trim_end())
+}
+
+fn write_gap<W: Write>(w: &mut W, gap: Range<u64>, color: bool) -> io::Result<()> {
+    let text = format!("-- gap {:#x}..{:#x} ({} bytes) --", gap.start, gap.end, gap.end - gap.start);
+    if color {
+        writeln!(w, "{}{}{}", DIM, text, RESET)
+    } else {
+        writeln!(w, "{}", text)
+    }
+}
+
+/// Hexdump `mem`
+pub fn dump<W: Write>(mut w: W, mem: &SparseMem, opts: &DumpOptions<'_>) -> io::Result<()> {
+    let extent = match mem.extent() {
+        Some(e) => e,
+        None => return Ok(()),
+    };
+    let range = match opts.range.clone() {
+        Some(r) => r.start..r.end.min(extent.end),
+        None => extent,
+    };
+
+    let none = [false; WIDTH as usize];
+    let mut row = row_of(range.start);
+    while row < range.end {
+        let shown = row.max(range.start)..(row + WIDTH).min(range.end);
+        if mem.gaps(shown.clone()).next() == Some(shown.clone()) {
+            let gap_end = next_populated(mem, shown.start, range.end);
+            write_gap(&mut w, shown.start..gap_end, opts.color)?;
+            // a range ending partway through a row ends inside the gap
+            row = row_of(gap_end).max(row + WIDTH);
+            continue;
+        }
+
+        let labels: Vec<&str> = opts
+            .annotations
+            .map(|a| a.starting_in(shown.clone()).map(|r| r.name.as_str()).collect())
+            .unwrap_or_default();
+        write_row(&mut w, "", row, &cells(mem, row, &range), &none, None, &labels)?;
+        row += WIDTH;
+    }
+
+    Ok(())
+}
+
+/// Print the rows that differ between `old` and `new`, returning how many bytes differ
+///
+/// A byte that is populated in only one of the images counts as a difference.
+pub fn diff<W: Write>(mut w: W, old: &SparseMem, new: &SparseMem, opts: &DumpOptions<'_>) -> io::Result<u64> {
+    let extent = match (old.extent(), new.extent()) {
+        (Some(a), Some(b)) => a.start.min(b.start)..a.end.max(b.end),
+        (Some(e), None) | (None, Some(e)) => e,
+        (None, None) => return Ok(0),
+    };
+    let range = match opts.range.clone() {
+        Some(r) => r.start..r.end.min(extent.end),
+        None => extent,
+    };
+
+    let mut changed = 0;
+    let mut last_printed = None;
+    let mut row = row_of(range.start);
+    while row < range.end {
+        let a = cells(old, row, &range);
+        let b = cells(new, row, &range);
+
+        if a.iter().chain(b.iter()).all(Option::is_none) {
+            // skip ahead to the next populated byte in either image
+            let start = row.max(range.start);
+            let next = next_populated(old, start, range.end).min(next_populated(new, start, range.end));
+            row = row_of(next).max(row + WIDTH);
+            continue;
+        }
+
+        if a == b {
+            row += WIDTH;
+            continue;
+        }
+
+        let mut highlight = [false; WIDTH as usize];
+        for (i, h) in highlight.iter_mut().enumerate() {
+            *h = a[i] != b[i];
+        }
+        changed += highlight.iter().filter(|&&h| h).count() as u64;
+
+        if last_printed.is_some_and(|l| l + WIDTH != row) {
+            writeln!(w, "...")?;
+        }
+        last_printed = Some(row);
+
+        let labels: Vec<&str> = match opts.annotations {
+            Some(ann) => {
+                let mut labels: Vec<&str> = Vec::new();
+                for (i, _) in highlight.iter().enumerate().filter(|(_, h)| **h) {
+                    for r in ann.containing(row + i as u64) {
+                        if !labels.contains(&r.name.as_str()) {
+                            labels.push(&r.name);
+                        }
+                    }
+                }
+                labels
+            }
+            None => Vec::new(),
+        };
+
+        let (old_color, new_color) = if opts.color { (Some(RED), Some(GREEN)) } else { (None, None) };
+        write_row(&mut w, "-", row, &a, &highlight, old_color, &[])?;
+        write_row(&mut w, "+", row, &b, &highlight, new_color, &labels)?;
+        if !opts.color {
+            write_marks(&mut w, "+", &highlight)?;
+        }
+        row += WIDTH;
+    }
+
+    Ok(changed)
+}
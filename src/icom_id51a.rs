@@ -5,6 +5,11 @@
 //!
 //! Icom provides downlaods for some configuration files definiting repeaters: https://www.icomjapan.com/support/firmware_driver/2444/
 
+use crate::error::*;
+use crate::geo::{Grid, LatLon, Located};
+use crate::import::{import, ImportReport, OnError};
+use crate::{mhz_from_f64, mhz_to_f64, Code, Mode, Place, Repeater, Site, Status};
+
 
 /// `IRNAID51.csv`
 ///
@@ -36,3 +41,269 @@ pub struct ChannelLine {
 }
 
 
+
+/// Column names, in order
+pub const COLUMNS: [&str; 17] = [
+    "Group No", "Group Name", "Name", "Sub Name", "Repeater Call Sign", "Gateway Call Sign", "Frequency", "Dup",
+    "Offset", "Mode", "TONE", "Repeater Tone", "RPT1USE", "Position", "Latitude", "Longitude", "UTC Offset",
+];
+
+impl std::convert::TryFrom<::csv::StringRecord> for ChannelLine {
+    type Error = FreqmError;
+
+    fn try_from(s: ::csv::StringRecord) -> Result<Self, Self::Error> {
+        // files written by `write_csv()` have a trailing "Grid" column
+        if s.len() != COLUMNS.len() && s.len() != COLUMNS.len() + 1 {
+            return Err(FormatError::FieldCount { found: s.len(), expected: "17 or 18" }.into());
+        }
+
+        let text = |i: usize| s[i].trim().to_owned();
+        let number = |i: usize| {
+            s[i].trim()
+                .parse::<f64>()
+                .map_err(|_| NumberSnafu { value: &s[i] }.build().column(COLUMNS[i]))
+        };
+
+        Ok(ChannelLine {
+            group_number: s[0]
+                .trim()
+                .parse()
+                .map_err(|_| NumberSnafu { value: &s[0] }.build().column(COLUMNS[0]))?,
+            group_name: text(1),
+            name: text(2),
+            sub_name: text(3),
+            repeated_call_sign: text(4),
+            gateway_call_sign: text(5),
+            frequency: number(6)?,
+            dup: text(7),
+            offset: number(8)?,
+            mode: text(9),
+            tone: text(10),
+            repeater_tone: text(11),
+            rpt1use: text(12),
+            position: text(13),
+            latitude: number(14)?,
+            longitude: number(15)?,
+            utc_offset: text(16),
+        })
+    }
+}
+
+impl ChannelLine {
+    /// Fill in the position columns. `exact` is false for positions that are only approximate
+    /// (e.g. the center of the repeater's town).
+    pub fn set_position(&mut self, position: Option<LatLon>, exact: bool) {
+        self.position = position_column(position, exact);
+        let pos = position.unwrap_or(LatLon { lat: 0.0, lon: 0.0 });
+        self.latitude = pos.lat;
+        self.longitude = pos.lon;
+    }
+}
+
+/// The "Position" column of the ID-51A and TH-D74 lists: "Exact", "Approximate" or "None"
+pub(crate) fn position_column(position: Option<LatLon>, exact: bool) -> String {
+    match position {
+        Some(_) if exact => "Exact",
+        Some(_) => "Approximate",
+        None => "None",
+    }
+    .to_owned()
+}
+
+/// The D-STAR module letter conventionally used for a band: A for 23cm, B for 70cm, C for 2m
+pub(crate) fn dstar_module(mhz: f64) -> char {
+    if mhz >= 1000.0 {
+        'A'
+    } else if mhz >= 400.0 {
+        'B'
+    } else {
+        'C'
+    }
+}
+
+/// The module a repeater's D-STAR call signs use: the one its listing gives, or else the band's
+pub(crate) fn module_of(r: &Repeater) -> char {
+    r.dstar_module().unwrap_or_else(|| dstar_module(mhz_to_f64(r.output_freq())))
+}
+
+/// The module letter after the callsign in a D-STAR repeater call sign ("W1ABC  B")
+pub(crate) fn module_in_call_sign(call: &str) -> Option<char> {
+    let mut parts = call.split_whitespace().skip(1);
+    match (parts.next(), parts.next()) {
+        (Some(m), None) if m.len() == 1 && ('A'..='D').contains(&m.chars().next()?.to_ascii_uppercase()) => {
+            m.chars().next().map(|c| c.to_ascii_uppercase())
+        },
+        _ => None,
+    }
+}
+
+impl std::convert::TryFrom<&ChannelLine> for Repeater {
+    type Error = FreqmError;
+
+    fn try_from(l: &ChannelLine) -> Result<Self, Self::Error> {
+        let freq = |mhz: f64, column| {
+            mhz_from_f64(mhz).ok_or_else(|| FrequencySnafu { value: mhz.to_string() }.build().column(column))
+        };
+        let output_freq = freq(l.frequency, "Frequency")?;
+        let offset = freq(l.offset, "Offset")?;
+        let input_freq = match l.dup.as_str() {
+            "DUP-" => output_freq - offset,
+            "DUP+" => output_freq + offset,
+            "OFF" | "" => output_freq,
+            _ => return Err(OffsetKindSnafu { value: &l.dup }.build().column("Dup")),
+        };
+        let mode = match l.mode.as_str() {
+            "DV" => Mode::DStar,
+            "FM" => Mode::Fm,
+            "FM-N" => Mode::Nfm,
+            _ => return Err(ModeSnafu { value: &l.mode }.build().column("Mode")),
+        };
+
+        // the tone is sent with "TONE", and also required on the output with "TSQL"
+        let tone = || {
+            l.repeater_tone
+                .parse::<Code>()
+                .map_err(|_| CodeSnafu { value: &l.repeater_tone }.build().column("Repeater Tone"))
+        };
+        let (code_in, code_out) = match l.tone.as_str() {
+            "OFF" | "" => (None, None),
+            "TONE" => (Some(tone()?), None),
+            "TSQL" => (Some(tone()?), Some(tone()?)),
+            _ => return Err(CodeSnafu { value: &l.tone }.build().column("TONE")),
+        };
+
+        Ok(Repeater {
+            // "W1ABC  B" is W1ABC's module B
+            callsign: l.repeated_call_sign.split_whitespace().next().unwrap_or_default().to_owned(),
+            output_freq,
+            input_freq: Some(input_freq),
+            modes: vec![mode],
+            status: Status::On,
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in,
+            code_out,
+            dstar_module: Some(mode).filter(|&m| m == Mode::DStar).and_then(|_| module_in_call_sign(&l.repeated_call_sign)),
+            site: l.position().map(|p| {
+                let site = Site::new(&l.name, format!("{}, {}", l.name, l.sub_name), p);
+                if l.position.eq_ignore_ascii_case("approximate") { site.approximate() } else { site }
+            }),
+            place: Place::new(&l.name, &l.sub_name, ""),
+            comment: None,
+        })
+    }
+}
+
+impl From<&Repeater> for ChannelLine {
+    /// Everything the `Repeater` knows, in group 1
+    fn from(r: &Repeater) -> Self {
+        let frequency = mhz_to_f64(r.output_freq());
+        let offset = r.input_freq().map(mhz_to_f64).unwrap_or(frequency) - frequency;
+        let dstar = r.modes().contains(&Mode::DStar);
+        let (repeated_call_sign, gateway_call_sign) = if dstar {
+            (format!("{:<7}{}", r.callsign(), module_of(r)), format!("{:<7}G", r.callsign()))
+        } else {
+            (r.callsign().to_owned(), String::new())
+        };
+
+        let mut line = ChannelLine {
+            group_number: 1,
+            group_name: "Repeaters".to_owned(),
+            name: r.site().map(|s| s.name()).or(r.place().map(|p| p.town.as_str())).unwrap_or_default().to_owned(),
+            // the site's location already starts with the name
+            sub_name: r.place().map(|p| p.state.as_str()).or(r.site().map(|s| s.location())).unwrap_or_default().to_owned(),
+            repeated_call_sign,
+            gateway_call_sign,
+            frequency,
+            dup: match offset {
+                o if o < -1e-9 => "DUP-",
+                o if o > 1e-9 => "DUP+",
+                _ => "OFF",
+            }
+            .to_owned(),
+            offset: (offset.abs() * 1e6).round() / 1e6,
+            mode: if dstar { "DV" } else if r.modes().contains(&Mode::Nfm) { "FM-N" } else { "FM" }.to_owned(),
+            tone: match (r.code_in(), r.code_out()) {
+                (Some(Code::Ctcss(_)), Some(Code::Ctcss(_))) => "TSQL",
+                (Some(Code::Ctcss(_)), _) => "TONE",
+                _ => "OFF",
+            }
+            .to_owned(),
+            repeater_tone: match r.code_in() {
+                Some(tone @ Code::Ctcss(_)) => format!("{}Hz", tone),
+                _ => "88.5Hz".to_owned(),
+            },
+            rpt1use: "Yes".to_owned(),
+            position: String::new(),
+            latitude: 0.0,
+            longitude: 0.0,
+            utc_offset: String::new(),
+        };
+        line.set_position(r.position(), !r.site().is_some_and(Site::is_approximate));
+        line
+    }
+}
+
+impl Located for ChannelLine {
+    /// `None` when the "Position" column is "None"
+    fn position(&self) -> Option<LatLon> {
+        if self.position.eq_ignore_ascii_case("none") {
+            return None;
+        }
+        LatLon::new(self.latitude, self.longitude)
+    }
+}
+
+/// Read a repeater list (`IRNAID51.csv` or a file exported in the same format), with a header row
+pub fn read_csv<P: AsRef<std::path::Path>>(
+    path: P,
+    on_error: OnError,
+) -> Result<(Vec<ChannelLine>, ImportReport), FreqmError> {
+    let path = path.as_ref();
+    let mut csv = ::csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| FreqmError::from(e).with_file(path))?;
+
+    let (lines, mut report) = import(&mut csv, on_error, |r, _| ChannelLine::try_from(r))
+        .map_err(|e| e.with_file(path))?;
+    report.set_file(path);
+    Ok((lines, report))
+}
+
+/// Write a repeater list in the `IRNAID51.csv` format, with a header row
+///
+/// A "Grid" column is added after the standard columns, holding the 6 character grid locator of
+/// each repeater with a position.
+pub fn write_csv<W: std::io::Write>(w: W, lines: &[ChannelLine]) -> Result<(), FreqmError> {
+    let mut csv = ::csv::Writer::from_writer(w);
+    csv.write_record(COLUMNS.iter().copied().chain(["Grid"]))?;
+
+    for l in lines {
+        let grid = l.position().map(|p| Grid::of(p, 6).to_string()).unwrap_or_default();
+        csv.write_record([
+            l.group_number.to_string(),
+            l.group_name.clone(),
+            l.name.clone(),
+            l.sub_name.clone(),
+            l.repeated_call_sign.clone(),
+            l.gateway_call_sign.clone(),
+            format!("{:.4}", l.frequency),
+            l.dup.clone(),
+            l.offset.to_string(),
+            l.mode.clone(),
+            l.tone.clone(),
+            l.repeater_tone.clone(),
+            l.rpt1use.clone(),
+            l.position.clone(),
+            l.latitude.to_string(),
+            l.longitude.to_string(),
+            l.utc_offset.clone(),
+            grid,
+        ])?;
+    }
+
+    csv.flush()?;
+    Ok(())
+}
@@ -0,0 +1,473 @@
+//! Saving and loading `SparseMem` images
+//!
+//! Supported formats:
+//!
+//!  - raw binary: a single contiguous block starting at a base address. Gaps are filled.
+//!  - Intel HEX: text records with 16 or 32 bit addresses
+//!  - Motorola S-record: text records with 16, 24 or 32 bit addresses
+//!  - Chirp `.img`: a raw image starting at address 0, followed by Chirp's metadata trailer
+//!
+//! The record based formats keep gaps intact, so they are the better choice for archiving partial
+//! downloads.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::sparse_mem::SparseMem;
+
+/// Byte used in place of unpopulated memory in formats that can't represent gaps
+pub const FILL: u8 = 0xff;
+
+/// Data bytes per record when writing Intel HEX and S-records
+const RECORD_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Raw binary, with the first byte at `base`
+    Raw { base: u64 },
+    IntelHex,
+    SRecord,
+    ChirpImg,
+}
+
+impl ImageFormat {
+    /// Guess the format from a file extension
+    ///
+    /// Raw images are assumed to start at address 0.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        Some(match ext.as_str() {
+            "bin" | "raw" => ImageFormat::Raw { base: 0 },
+            "hex" | "ihex" | "ihx" => ImageFormat::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot" => ImageFormat::SRecord,
+            "img" => ImageFormat::ChirpImg,
+            _ => return None,
+        })
+    }
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = FreqmError;
+
+    /// "raw", "ihex", "srec" or "img". Raw images start at address 0.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" | "bin" => Ok(ImageFormat::Raw { base: 0 }),
+            "ihex" | "hex" => Ok(ImageFormat::IntelHex),
+            "srec" => Ok(ImageFormat::SRecord),
+            "img" | "chirp" => Ok(ImageFormat::ChirpImg),
+            _ => Err(ImageFormatSnafu { value: s }.build().column("format")),
+        }
+    }
+}
+
+/// Parse an address: hex with a "0x" prefix, otherwise decimal
+pub fn parse_addr(s: &str) -> Result<u64, FreqmError> {
+    let s = s.trim();
+    let v = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => s.replace('_', "").parse(),
+    };
+    v.ok().ok_or_else(|| AddressSnafu { value: s }.build().column("address"))
+}
+
+/// Metadata Chirp appends to its `.img` files
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChirpMetadata {
+    /// Chirp's driver class name
+    #[serde(default)]
+    pub rclass: String,
+    #[serde(default)]
+    pub vendor: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub variant: String,
+    #[serde(default)]
+    pub chirp_version: String,
+
+    /// Anything else Chirp stored
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Separates the image from the metadata in Chirp `.img` files
+const CHIRP_MAGIC: &[u8] = b"\x00\xffchirp\xeeimg\x00\x01";
+
+/// Parse an image, discarding any Chirp metadata
+pub fn read(bytes: &[u8], format: ImageFormat) -> Result<SparseMem, FreqmError> {
+    match format {
+        ImageFormat::Raw { base } => read_raw(bytes, base),
+        ImageFormat::IntelHex => read_ihex(bytes),
+        ImageFormat::SRecord => read_srec(bytes),
+        ImageFormat::ChirpImg => read_chirp_img(bytes).map(|(mem, _)| mem),
+    }
+}
+
+/// Write an image. Chirp images are written without metadata.
+pub fn write<W: Write>(mem: &SparseMem, format: ImageFormat, w: W) -> Result<(), FreqmError> {
+    match format {
+        ImageFormat::Raw { base } => write_raw(mem, base, w),
+        ImageFormat::IntelHex => write_ihex(mem, w),
+        ImageFormat::SRecord => write_srec(mem, w),
+        ImageFormat::ChirpImg => write_chirp_img(mem, None, w),
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P, format: ImageFormat) -> Result<SparseMem, FreqmError> {
+    load_with_metadata(path, format).map(|(mem, _)| mem)
+}
+
+pub fn save<P: AsRef<Path>>(mem: &SparseMem, path: P, format: ImageFormat) -> Result<(), FreqmError> {
+    save_with_metadata(mem, None, path, format)
+}
+
+/// Load an image, with its metadata if it's a Chirp `.img`
+pub fn load_with_metadata<P: AsRef<Path>>(
+    path: P,
+    format: ImageFormat,
+) -> Result<(SparseMem, Option<ChirpMetadata>), FreqmError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+    let loaded = match format {
+        ImageFormat::ChirpImg => read_chirp_img(&bytes),
+        _ => read(&bytes, format).map(|mem| (mem, None)),
+    };
+    loaded.map_err(|e| e.with_file(path))
+}
+
+/// Save an image, appending `metadata` if it's saved as a Chirp `.img`
+pub fn save_with_metadata<P: AsRef<Path>>(
+    mem: &SparseMem,
+    metadata: Option<&ChirpMetadata>,
+    path: P,
+    format: ImageFormat,
+) -> Result<(), FreqmError> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+    let mut w = std::io::BufWriter::new(file);
+    let written = match format {
+        ImageFormat::ChirpImg => write_chirp_img(mem, metadata, &mut w),
+        _ => write(mem, format, &mut w),
+    };
+    written
+        .and_then(|_| Ok(w.flush()?))
+        .map_err(|e| e.with_file(path))
+}
+
+pub fn read_raw(bytes: &[u8], base: u64) -> Result<SparseMem, FreqmError> {
+    let mut mem = SparseMem::default();
+    mem.insert(base, bytes)?;
+    Ok(mem)
+}
+
+/// Write everything from `base` to the highest populated address, filling gaps with `FILL`
+pub fn write_raw<W: Write>(mem: &SparseMem, base: u64, mut w: W) -> Result<(), FreqmError> {
+    let extent = match mem.extent() {
+        Some(e) => e,
+        None => return Ok(()),
+    };
+
+    if extent.start < base {
+        return Err(FormatError::BelowBase { addr: extent.start, base }.into());
+    }
+
+    w.write_all(&mem.read_filled(base..extent.end, FILL))?;
+    Ok(())
+}
+
+fn at_line(source: FormatError, line: usize) -> FreqmError {
+    FreqmError::Format {
+        location: Location {
+            line: Some(line as u64),
+            ..Default::default()
+        },
+        source,
+    }
+}
+
+fn malformed(reason: impl Into<String>, line: usize) -> FreqmError {
+    at_line(FormatError::ImageRecord { reason: reason.into() }, line)
+}
+
+/// Decode the hex digits of a record (after the leading ':' or "S<type>")
+fn decode_hex(digits: &str, line: usize) -> Result<Vec<u8>, FreqmError> {
+    if !digits.len().is_multiple_of(2) {
+        return Err(malformed("odd number of hex digits", line));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            digits
+                .get(i..i + 2)
+                .and_then(|d| u8::from_str_radix(d, 16).ok())
+                .ok_or_else(|| malformed(format!("{:?} is not hex", digits), line))
+        })
+        .collect()
+}
+
+fn text_lines(bytes: &[u8]) -> Result<impl Iterator<Item = (usize, &str)>, FreqmError> {
+    let text = std::str::from_utf8(bytes).map_err(|_| malformed("not text", 1))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty()))
+}
+
+fn be_addr(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |a, &b| (a << 8) | b as u64)
+}
+
+pub fn read_ihex(bytes: &[u8]) -> Result<SparseMem, FreqmError> {
+    let mut mem = SparseMem::default();
+    let mut base = 0u64;
+
+    for (line, text) in text_lines(bytes)? {
+        let digits = text
+            .strip_prefix(':')
+            .ok_or_else(|| malformed("missing ':'", line))?;
+        let rec = decode_hex(digits, line)?;
+        if rec.len() < 5 || rec.len() != rec[0] as usize + 5 {
+            return Err(malformed("length doesn't match byte count", line));
+        }
+
+        let sum = rec.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+        if sum != 0 {
+            let (body, found) = rec.split_at(rec.len() - 1);
+            let expected = body.iter().fold(0u8, |a, &b| a.wrapping_add(b)).wrapping_neg();
+            return Err(at_line(FormatError::ImageChecksum { found: found[0], expected }, line));
+        }
+
+        let addr = be_addr(&rec[1..3]);
+        let data = &rec[4..rec.len() - 1];
+        match rec[3] {
+            0x00 => mem.insert(base + addr, data)?,
+            0x01 => break,
+            0x02 if data.len() == 2 => base = be_addr(data) << 4,
+            0x04 if data.len() == 2 => base = be_addr(data) << 16,
+            // start addresses don't affect the contents
+            0x03 | 0x05 => {}
+            t => return Err(malformed(format!("unexpected record type {:02X}", t), line)),
+        }
+    }
+
+    Ok(mem)
+}
+
+fn write_ihex_record<W: Write>(w: &mut W, kind: u8, addr: u16, data: &[u8]) -> std::io::Result<()> {
+    let mut rec = vec![data.len() as u8];
+    rec.extend_from_slice(&addr.to_be_bytes());
+    rec.push(kind);
+    rec.extend_from_slice(data);
+    let sum = rec.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+    rec.push(sum.wrapping_neg());
+
+    write!(w, ":")?;
+    for b in rec {
+        write!(w, "{:02X}", b)?;
+    }
+    writeln!(w)
+}
+
+/// Write with extended linear address records as needed, so the image may use 32 bit addresses
+pub fn write_ihex<W: Write>(mem: &SparseMem, mut w: W) -> Result<(), FreqmError> {
+    if let Some(extent) = mem.extent()
+        && extent.end > 1 << 32
+    {
+        return Err(FormatError::AddressWidth { addr: extent.end - 1, bits: 32 }.into());
+    }
+
+    let mut upper = 0u64;
+    for (start, data) in mem.ranges() {
+        let mut addr = start;
+        let mut data = data;
+        while !data.is_empty() {
+            if addr >> 16 != upper {
+                upper = addr >> 16;
+                write_ihex_record(&mut w, 0x04, 0, &(upper as u16).to_be_bytes())?;
+            }
+
+            // records can't cross a 64 KiB boundary
+            let to_boundary = (0x1_0000 - (addr & 0xffff)) as usize;
+            let n = data.len().min(RECORD_LEN).min(to_boundary);
+            write_ihex_record(&mut w, 0x00, addr as u16, &data[..n])?;
+            addr += n as u64;
+            data = &data[n..];
+        }
+    }
+
+    write_ihex_record(&mut w, 0x01, 0, &[])?;
+    Ok(())
+}
+
+pub fn read_srec(bytes: &[u8]) -> Result<SparseMem, FreqmError> {
+    let mut mem = SparseMem::default();
+
+    for (line, text) in text_lines(bytes)? {
+        let kind = text
+            .strip_prefix('S')
+            .and_then(|t| t.chars().next())
+            .and_then(|c| c.to_digit(10))
+            .ok_or_else(|| malformed("missing 'S<type>'", line))?;
+        let rec = decode_hex(&text[2..], line)?;
+        if rec.len() < 2 || rec.len() != rec[0] as usize + 1 {
+            return Err(malformed("length doesn't match byte count", line));
+        }
+
+        let (body, found) = rec.split_at(rec.len() - 1);
+        let expected = !body.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+        if found[0] != expected {
+            return Err(at_line(FormatError::ImageChecksum { found: found[0], expected }, line));
+        }
+
+        let addr_len = match kind {
+            1 | 9 | 0 | 5 => 2,
+            2 | 8 | 6 => 3,
+            3 | 7 => 4,
+            t => return Err(malformed(format!("unexpected record type S{}", t), line)),
+        };
+        if body.len() < 1 + addr_len {
+            return Err(malformed("record too short for its address", line));
+        }
+
+        let addr = be_addr(&body[1..1 + addr_len]);
+        let data = &body[1 + addr_len..];
+        match kind {
+            1..=3 => mem.insert(addr, data)?,
+            7..=9 => break,
+            // header and record counts
+            _ => {}
+        }
+    }
+
+    Ok(mem)
+}
+
+fn write_srec_record<W: Write>(w: &mut W, kind: u8, addr: u64, addr_len: usize, data: &[u8]) -> std::io::Result<()> {
+    let mut rec = vec![(addr_len + data.len() + 1) as u8];
+    rec.extend_from_slice(&addr.to_be_bytes()[8 - addr_len..]);
+    rec.extend_from_slice(data);
+    let sum = rec.iter().fold(0u8, |a, &b| a.wrapping_add(b));
+    rec.push(!sum);
+
+    write!(w, "S{}", kind)?;
+    for b in rec {
+        write!(w, "{:02X}", b)?;
+    }
+    writeln!(w)
+}
+
+/// Write using the smallest address width that fits the image (S19, S28 or S37)
+pub fn write_srec<W: Write>(mem: &SparseMem, mut w: W) -> Result<(), FreqmError> {
+    let last = mem.extent().map(|e| e.end - 1).unwrap_or(0);
+    let (data_kind, end_kind, addr_len) = match last {
+        0..=0xffff => (1, 9, 2),
+        0x1_0000..=0xff_ffff => (2, 8, 3),
+        0x100_0000..=0xffff_ffff => (3, 7, 4),
+        _ => return Err(FormatError::AddressWidth { addr: last, bits: 32 }.into()),
+    };
+
+    write_srec_record(&mut w, 0, 0, 2, b"freqm")?;
+
+    let mut count = 0u64;
+    for (start, data) in mem.ranges() {
+        for (i, chunk) in data.chunks(RECORD_LEN).enumerate() {
+            write_srec_record(&mut w, data_kind, start + (i * RECORD_LEN) as u64, addr_len, chunk)?;
+            count += 1;
+        }
+    }
+
+    match count {
+        0..=0xffff => write_srec_record(&mut w, 5, count, 2, &[])?,
+        0x1_0000..=0xff_ffff => write_srec_record(&mut w, 6, count, 3, &[])?,
+        // the count record is optional
+        _ => {}
+    }
+
+    write_srec_record(&mut w, end_kind, 0, addr_len, &[])?;
+    Ok(())
+}
+
+/// Parse a Chirp `.img`: the memory image from address 0, then optionally the metadata
+pub fn read_chirp_img(bytes: &[u8]) -> Result<(SparseMem, Option<ChirpMetadata>), FreqmError> {
+    let split = bytes
+        .windows(CHIRP_MAGIC.len())
+        .rposition(|w| w == CHIRP_MAGIC);
+    let (data, metadata) = match split {
+        Some(i) => (&bytes[..i], Some(&bytes[i + CHIRP_MAGIC.len()..])),
+        None => (bytes, None),
+    };
+
+    let metadata = match metadata {
+        Some(m) => {
+            let json = base64_decode(m).ok_or_else(|| malformed("chirp metadata is not base64", 1))?;
+            let md = serde_json::from_slice(&json)
+                .map_err(|e| malformed(format!("chirp metadata: {}", e), 1))?;
+            Some(md)
+        }
+        None => None,
+    };
+
+    Ok((read_raw(data, 0)?, metadata))
+}
+
+/// Write a Chirp `.img`, from address 0 with gaps filled with `FILL`
+pub fn write_chirp_img<W: Write>(
+    mem: &SparseMem,
+    metadata: Option<&ChirpMetadata>,
+    mut w: W,
+) -> Result<(), FreqmError> {
+    write_raw(mem, 0, &mut w)?;
+
+    if let Some(md) = metadata {
+        // serializing a struct of strings & json values can't fail
+        let json = serde_json::to_vec(md).unwrap();
+        w.write_all(CHIRP_MAGIC)?;
+        w.write_all(base64_encode(&json).as_bytes())?;
+    }
+
+    Ok(())
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |a, (i, &b)| a | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(text: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for &c in text.iter().filter(|c| !c.is_ascii_whitespace()) {
+        if c == b'=' {
+            break;
+        }
+
+        n = (n << 6) | BASE64.iter().position(|&b| b == c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
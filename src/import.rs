@@ -0,0 +1,185 @@
+//! Reading csv sources row by row, keeping track of the rows that fail
+//!
+//! Importers convert each `csv::StringRecord` with a closure. A row that fails to convert either
+//! aborts the import (`OnError::Abort`) or is recorded in the `ImportReport` and skipped
+//! (`OnError::Continue`). Converters may also attach warnings to rows that were imported but lost
+//! some information along the way.
+
+use std::fmt;
+
+use super::*;
+
+/// What to do when a row fails to import
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Stop at the first bad row, returning its error
+    #[default]
+    Abort,
+
+    /// Skip bad rows, recording them in the `ImportReport`
+    Continue,
+}
+
+/// A problem with a single row of input
+#[derive(Debug)]
+pub struct RowIssue {
+    /// Line in the source file the row starts on
+    pub line: u64,
+
+    /// Has the file, line and record filled into its `Location`
+    pub error: FreqmError,
+
+    /// The row as it appeared in the source (re-encoded from its fields)
+    pub raw: String,
+}
+
+impl fmt::Display for RowIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.raw.is_empty() {
+            write!(f, "{}", self.error)
+        } else {
+            write!(f, "{}: {}", self.error, self.raw)
+        }
+    }
+}
+
+/// Summary of an import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+
+    /// Record number of each imported item, in order
+    pub records: Vec<u64>,
+
+    /// Rows that failed and were not imported
+    pub skipped: Vec<RowIssue>,
+
+    /// Rows that were imported, but with problems
+    pub warnings: Vec<RowIssue>,
+}
+
+impl ImportReport {
+    /// Record the file every issue came from
+    pub fn set_file<P: AsRef<std::path::Path>>(&mut self, file: P) {
+        for issue in self.skipped.iter_mut().chain(self.warnings.iter_mut()) {
+            issue.error.set_file(file.as_ref());
+        }
+    }
+
+    /// Write every skipped and warned row, followed by the summary line
+    pub fn write_details<W: std::io::Write>(&self, mut w: W) -> std::io::Result<()> {
+        for s in &self.skipped {
+            writeln!(w, "skipped: {}", s)?;
+        }
+
+        for s in &self.warnings {
+            writeln!(w, "warning: {}", s)?;
+        }
+
+        writeln!(w, "{}", self)
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "imported {}, skipped {}, warned {}",
+            self.imported,
+            self.skipped.len(),
+            self.warnings.len()
+        )
+    }
+}
+
+/// Re-encode a record as a single csv line
+fn raw_text(record: &::csv::StringRecord) -> String {
+    let mut w = ::csv::WriterBuilder::new()
+        .flexible(true)
+        .terminator(::csv::Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+
+    // writing to a `Vec` can't fail
+    w.write_record(record).unwrap();
+    let mut raw = String::from_utf8(w.into_inner().unwrap()).unwrap();
+    raw.pop();
+    raw
+}
+
+/// Convert every record from `csv` with `convert`
+///
+/// `convert` may push warnings for a row into the provided `Vec`. They are only recorded if the row
+/// is imported.
+pub fn import<R, T, F>(
+    csv: &mut ::csv::Reader<R>,
+    on_error: OnError,
+    mut convert: F,
+) -> Result<(Vec<T>, ImportReport), FreqmError>
+where
+    R: std::io::Read,
+    F: FnMut(::csv::StringRecord, &mut Vec<FreqmError>) -> Result<T, FreqmError>,
+{
+    let mut items = Vec::new();
+    let mut report = ImportReport::default();
+    let mut record = ::csv::StringRecord::new();
+
+    // the csv crate counts the header as record 0
+    let header = u64::from(csv.has_headers());
+    let row = |p: &::csv::Position| (p.line(), p.record() + 1 - header);
+
+    loop {
+        match csv.read_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(source) => {
+                // the reader can continue after utf-8 and field count errors, but not after I/O
+                // errors
+                let fatal = source.is_io_error();
+                let (line, n) = source.position().map(row).unwrap_or((0, 0));
+                let error = FreqmError::from(source).with_row(line, n);
+
+                if fatal || on_error == OnError::Abort {
+                    return Err(error);
+                }
+                report.skipped.push(RowIssue { line, error, raw: String::new() });
+                continue;
+            }
+        }
+
+        let (line, n) = record.position().map(row).unwrap_or((0, 0));
+        let mut warnings = Vec::new();
+        match convert(record.clone(), &mut warnings) {
+            Ok(item) => {
+                items.push(item);
+                report.imported += 1;
+                report.records.push(n);
+                report.warnings.extend(warnings.into_iter().map(|error| RowIssue {
+                    line,
+                    error: error.with_row(line, n),
+                    raw: raw_text(&record),
+                }));
+            }
+            Err(error) => {
+                let error = error.with_row(line, n);
+                if on_error == OnError::Abort {
+                    return Err(error);
+                }
+                report.skipped.push(RowIssue { line, error, raw: raw_text(&record) });
+            }
+        }
+    }
+
+    Ok((items, report))
+}
+
+/// Import rows by deserializing them by position into `T` (for the row types in `csv`)
+pub fn import_deserialize<R, T>(
+    csv: &mut ::csv::Reader<R>,
+    on_error: OnError,
+) -> Result<(Vec<T>, ImportReport), FreqmError>
+where
+    R: std::io::Read,
+    T: serde::de::DeserializeOwned,
+{
+    import(csv, on_error, |record, _| Ok(record.deserialize(None)?))
+}
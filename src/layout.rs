@@ -0,0 +1,243 @@
+//! Arranging a channel list: the order of the channels, their numbers, and their groups
+//!
+//! Radios organize channels differently. The AT-878 puts channels in zones, and the ID-51A and
+//! TH-D74 put repeaters in numbered groups. A `Layout` describes an arrangement without knowing the
+//! radio, and `Layout::plan()` applies it to the repeaters being written. Each format then keeps
+//! the plan's groups wherever the radio does (see `formats::Groups`).
+//!
+//! The AT-878 also gets a scan list for each group, holding the same channels.
+
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+use crate::error::*;
+use crate::geo::{LatLon, Located};
+use crate::{Band, Mode, Repeater};
+
+/// What channels are sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Output frequency
+    Frequency,
+    /// Distance from `Layout::center`, nearest first. Channels without a position go last.
+    Distance,
+    /// The town the repeater is in (its place, or else its site's name), then callsign
+    Name,
+    Callsign,
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = FreqmError;
+
+    /// "freq" (or "frequency"), "distance", "name" or "callsign", ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "freq" | "frequency" => Ok(SortKey::Frequency),
+            "distance" => Ok(SortKey::Distance),
+            "name" => Ok(SortKey::Name),
+            "callsign" => Ok(SortKey::Callsign),
+            _ => Err(SortKeySnafu { value: s }.build().column("sort")),
+        }
+    }
+}
+
+/// What channels are grouped by. Each group is named after the value its channels share.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// "2m", "70cm"
+    Band,
+    /// The repeater's digital mode if it has one ("DMR", "D-STAR"), otherwise "FM"
+    Mode,
+    County,
+    State,
+    Town,
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = FreqmError;
+
+    /// As the variants are named, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "band" => Ok(GroupBy::Band),
+            "mode" => Ok(GroupBy::Mode),
+            "county" => Ok(GroupBy::County),
+            "state" => Ok(GroupBy::State),
+            "town" => Ok(GroupBy::Town),
+            _ => Err(GroupBySnafu { value: s }.build().column("group_by")),
+        }
+    }
+}
+
+/// Name of the group for channels without the value grouped by (e.g. no county)
+pub const OTHER: &str = "Other";
+
+impl GroupBy {
+    /// The name of `r`'s group
+    pub fn group(self, r: &Repeater) -> String {
+        let place = |field: fn(&crate::Place) -> &String| {
+            r.place().map(field).filter(|s| !s.is_empty()).cloned().unwrap_or_else(|| OTHER.to_owned())
+        };
+
+        match self {
+            GroupBy::Band => Band::of(r.output_freq()).map(|b| b.to_string()).unwrap_or_else(|| OTHER.to_owned()),
+            GroupBy::Mode => {
+                let digital = r.modes().iter().copied().find(|m| m.is_digital());
+                match digital {
+                    Some(Mode::Dmr) => "DMR",
+                    Some(Mode::DStar) => "D-STAR",
+                    Some(Mode::Ysf) => "YSF",
+                    Some(Mode::Nxdn) => "NXDN",
+                    Some(Mode::P25) => "P25",
+                    _ => "FM",
+                }
+                .to_owned()
+            },
+            GroupBy::County => place(|p| &p.county),
+            GroupBy::State => place(|p| &p.state),
+            GroupBy::Town => place(|p| &p.town),
+        }
+    }
+}
+
+/// "<first>-<last>", or a single channel number
+pub fn parse_range(s: &str) -> Result<RangeInclusive<usize>, FreqmError> {
+    let bad = || ChannelRangeSnafu { value: s }.build().column("reserve");
+    let number = |n: &str| n.trim().parse::<usize>().map_err(|_| bad());
+    let range = match s.split_once('-') {
+        Some((first, last)) => number(first)?..=number(last)?,
+        None => number(s)?..=number(s)?,
+    };
+    if range.is_empty() {
+        return Err(bad());
+    }
+    Ok(range)
+}
+
+/// How to arrange a channel list. The default keeps the channels in order, numbered from 1, with
+/// no groups.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Most significant first. Channels that compare equal keep their order.
+    pub sort: Vec<SortKey>,
+    /// Where `SortKey::Distance` is measured from. Without it, distance doesn't change the order.
+    pub center: Option<LatLon>,
+    /// Number of the first channel
+    pub first: usize,
+    /// Channel numbers to leave free, e.g. for simplex channels added by hand
+    pub reserved: Vec<RangeInclusive<usize>>,
+    pub group_by: Option<GroupBy>,
+    /// Most channels in a group. Bigger groups are split in order, with numbered names
+    /// ("Middlesex 1", "Middlesex 2").
+    pub group_size: Option<usize>,
+    /// Most channels to keep: the first ones in sort order, before they're grouped
+    pub limit: Option<usize>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { sort: Vec::new(), center: None, first: 1, reserved: Vec::new(), group_by: None, group_size: None, limit: None }
+    }
+}
+
+/// Channels in list order, with their numbers and groups
+#[derive(Debug, Clone, Default)]
+pub struct Plan<'a> {
+    pub channels: Vec<Channel<'a>>,
+    pub groups: Vec<Group>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel<'a> {
+    pub repeater: &'a Repeater,
+    pub number: usize,
+    /// Index in `Plan::groups`
+    pub group: Option<usize>,
+    /// The name to show on the radio, see `names::name_channels()`. Formats name channels after
+    /// the callsign (or the site, in repeater lists) without one.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    /// Indexes in `Plan::channels`, in order
+    pub channels: Vec<usize>,
+}
+
+impl<'a> Plan<'a> {
+    /// The group `c` is in
+    pub fn group_of(&self, c: &Channel<'_>) -> Option<&Group> {
+        c.group.map(|g| &self.groups[g])
+    }
+}
+
+impl Layout {
+    /// Sort `repeaters`, keep up to `limit` of them, put them in groups (keeping each group's channels together, in the order
+    /// the groups first appear), and number them, skipping reserved numbers
+    pub fn plan<'a>(&self, repeaters: &[&'a Repeater]) -> Plan<'a> {
+        let mut sorted = repeaters.to_vec();
+        sorted.sort_by(|a, b| self.sort.iter().fold(Ordering::Equal, |o, &key| o.then_with(|| self.compare(key, a, b))));
+        sorted.truncate(self.limit.unwrap_or(usize::MAX));
+
+        let mut groups: Vec<(String, Vec<&'a Repeater>)> = Vec::new();
+        match self.group_by {
+            Some(by) => {
+                for r in sorted {
+                    let name = by.group(r);
+                    match groups.iter_mut().find(|(n, _)| *n == name) {
+                        Some((_, members)) => members.push(r),
+                        None => groups.push((name, vec![r])),
+                    }
+                }
+            },
+            None => groups.push((String::new(), sorted)),
+        }
+
+        let mut plan = Plan::default();
+        let mut number = self.first;
+        for (name, members) in groups {
+            let size = self.group_size.filter(|&s| s > 0).unwrap_or(usize::MAX);
+            let parts = members.len().div_ceil(size);
+            for (part, chunk) in members.chunks(size).enumerate() {
+                let group = self.group_by.is_some().then(|| {
+                    let name = if parts > 1 { format!("{} {}", name, part + 1) } else { name.clone() };
+                    plan.groups.push(Group { name, channels: Vec::new() });
+                    plan.groups.len() - 1
+                });
+
+                for &repeater in chunk {
+                    while self.reserved.iter().any(|r| r.contains(&number)) {
+                        number += 1;
+                    }
+                    if let Some(g) = group {
+                        plan.groups[g].channels.push(plan.channels.len());
+                    }
+                    plan.channels.push(Channel { repeater, number, group, name: None });
+                    number += 1;
+                }
+            }
+        }
+
+        plan
+    }
+
+    fn compare(&self, key: SortKey, a: &Repeater, b: &Repeater) -> Ordering {
+        match key {
+            SortKey::Frequency => a.output_freq().partial_cmp(&b.output_freq()).unwrap_or(Ordering::Equal),
+            SortKey::Distance => {
+                let Some(center) = self.center else { return Ordering::Equal };
+                let km = |r: &Repeater| r.position().map(|p| center.distance(&p).as_km()).unwrap_or(f64::INFINITY);
+                km(a).total_cmp(&km(b))
+            },
+            SortKey::Name => {
+                let town = |r: &Repeater| {
+                    let town = r.place().map(|p| p.town.as_str()).or(r.site().map(|s| s.name())).unwrap_or_default();
+                    town.to_ascii_lowercase()
+                };
+                town(a).cmp(&town(b)).then_with(|| self.compare(SortKey::Callsign, a, b))
+            },
+            SortKey::Callsign => a.callsign().to_ascii_uppercase().cmp(&b.callsign().to_ascii_uppercase()),
+        }
+    }
+}
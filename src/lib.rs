@@ -1,35 +1,257 @@
 #![warn(missing_debug_implementations)]
-use serde::de;
-use snafu::Snafu;
 
 pub mod anytone_ht;
+pub mod channel_file;
+pub mod diff;
+pub mod error;
+pub mod filter;
+pub mod formats;
+pub mod gazetteer;
+pub mod geo;
+pub mod hexdump;
 pub mod icom_id51a;
+pub mod image;
+pub mod import;
+pub mod layout;
+pub mod lint;
+pub mod memory_map;
+pub mod models;
+pub mod names;
+pub mod ne_links;
 pub mod ne_repeater;
+pub mod project;
+pub mod radioid;
+pub mod repeater_db;
+pub mod route;
+pub mod sqlite;
 pub mod sparse_mem;
 pub mod csv;
 
-// FIXME: this shouldn't have public context constructors
-#[derive(Debug, Snafu)]
-#[snafu(visibility(pub))]
-pub enum FreqmError {
-    #[snafu(display("CSV missing field(s): have {}", field_num))]
-    FieldMissing { field_num: usize },
+pub use error::{FreqmError, Location};
 
-    #[snafu(display("output frequency {:?} is not a decimal", output_freq))]
-    OutputFreqParseFailure { output_freq: String },
+/// Modulation/protocol a repeater operates with
+///
+/// Serialized as the variant's name, and read back with `FromStr`, in any case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Mode {
+    /// Analog FM, 5 kHz deviation ("wide")
+    Fm,
+    /// Analog FM, 2.5 kHz deviation ("narrow")
+    Nfm,
+    Dmr,
+    DStar,
+    /// Yaesu System Fusion (C4FM)
+    Ysf,
+    Nxdn,
+    P25,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 7] = [Mode::Fm, Mode::Nfm, Mode::Dmr, Mode::DStar, Mode::Ysf, Mode::Nxdn, Mode::P25];
+
+    pub fn is_digital(self) -> bool {
+        !matches!(self, Mode::Fm | Mode::Nfm)
+    }
+}
+
+impl std::str::FromStr for Mode {
+    type Err = FreqmError;
+
+    /// The variant's name ("Fm", "DStar"), ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Mode::ALL
+            .into_iter()
+            .find(|m| format!("{:?}", m).eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| error::ModeSnafu { value: s }.build().column("mode"))
+    }
+}
+
+impl TryFrom<String> for Mode {
+    type Error = FreqmError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// An amateur band repeaters are found on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Band {
+    M10,
+    M6,
+    M2,
+    M1_25,
+    Cm70,
+    Cm33,
+    Cm23,
+}
+
+impl Band {
+    pub const ALL: [Band; 7] = [Band::M10, Band::M6, Band::M2, Band::M1_25, Band::Cm70, Band::Cm33, Band::Cm23];
+
+    /// The band `mhz` is in (US band edges)
+    pub fn of(mhz: decimal::d128) -> Option<Band> {
+        Band::ALL.into_iter().find(|b| {
+            let (low, high) = b.edges();
+            mhz >= low && mhz <= high
+        })
+    }
 
-    #[snafu(display("offset kind {:?} unrecognized", offset_kind))]
-    InvalidOffsetKind { offset_kind: String },
+    /// Lowest and highest frequency, MHz
+    pub fn edges(self) -> (decimal::d128, decimal::d128) {
+        match self {
+            Band::M10 => (28.into(), decimal::d128!(29.7)),
+            Band::M6 => (50.into(), 54.into()),
+            Band::M2 => (144.into(), 148.into()),
+            Band::M1_25 => (219.into(), 225.into()),
+            Band::Cm70 => (420.into(), 450.into()),
+            Band::Cm33 => (902.into(), 928.into()),
+            Band::Cm23 => (1240.into(), 1300.into()),
+        }
+    }
+}
 
-    #[snafu(display("frequency {} is not in a known band", freq))]
-    FreqNotInAnyBand { freq: decimal::d128 },
+impl std::fmt::Display for Band {
+    /// "2m", "70cm"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Band::M10 => "10m",
+            Band::M6 => "6m",
+            Band::M2 => "2m",
+            Band::M1_25 => "1.25m",
+            Band::Cm70 => "70cm",
+            Band::Cm33 => "33cm",
+            Band::Cm23 => "23cm",
+        })
+    }
+}
+
+impl std::str::FromStr for Band {
+    type Err = FreqmError;
+
+    /// As `Display` writes them, ignoring case. "220" is also 1.25m.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        if text == "220" {
+            return Ok(Band::M1_25);
+        }
+        Band::ALL
+            .into_iter()
+            .find(|b| b.to_string().eq_ignore_ascii_case(text))
+            .ok_or_else(|| error::BandSnafu { value: s }.build().column("band"))
+    }
+}
 
-    #[snafu(display("comment parse failed: {:?}", comment))]
-    CommentParse { comment: String },
+/// Operational status of a repeater, serialized like `Mode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Status {
+    /// In normal operation (or the source doesn't say otherwise)
+    #[default]
+    On,
+    Off,
+    /// Intended for local coverage only
+    Local,
+    /// Receive coverage is limited
+    LimitedRx,
+    /// Transmit coverage is limited
+    LimitedTx,
+}
+
+impl Status {
+    pub const ALL: [Status; 5] = [Status::On, Status::Off, Status::Local, Status::LimitedRx, Status::LimitedTx];
+}
+
+impl std::str::FromStr for Status {
+    type Err = FreqmError;
+
+    /// The variant's name ("On", "LimitedRx"), ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Status::ALL
+            .into_iter()
+            .find(|st| format!("{:?}", st).eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| error::StatusSnafu { value: s }.build().column("status"))
+    }
+}
+
+impl TryFrom<String> for Status {
+    type Error = FreqmError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A code a repeater requires on its input, or sends on its output
+///
+/// Serialized as its `Display` text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Code {
+    /// CTCSS tone, in tenths of a Hz (88.5 Hz is 885)
+    Ctcss(u16),
+    /// DCS code, written in octal (023 is 0o23)
+    Dcs(u16),
+    /// DMR color code
+    ColorCode(u8),
+    /// P25 network access code
+    Nac(u16),
+}
+
+impl std::fmt::Display for Code {
+    /// "88.5", "D023", "CC1", "NAC293"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Code::Ctcss(t) => write!(f, "{}.{}", t / 10, t % 10),
+            Code::Dcs(c) => write!(f, "D{:03o}", c),
+            Code::ColorCode(c) => write!(f, "CC{}", c),
+            Code::Nac(n) => write!(f, "NAC{:03X}", n),
+        }
+    }
+}
+
+impl From<Code> for String {
+    fn from(c: Code) -> String {
+        c.to_string()
+    }
+}
+
+impl TryFrom<String> for Code {
+    type Error = FreqmError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::str::FromStr for Code {
+    type Err = FreqmError;
+
+    /// The forms `Display` writes, and CTCSS tones with a "Hz" suffix ("88.5Hz")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let bad = || error::CodeSnafu { value: s }.build().column("code");
+        let upper = text.to_ascii_uppercase();
+        let code = if let Some(c) = upper.strip_prefix("NAC") {
+            Code::Nac(u16::from_str_radix(c.trim(), 16).ok().filter(|&n| n <= 0xfff).ok_or_else(bad)?)
+        } else if let Some(c) = upper.strip_prefix("CC") {
+            Code::ColorCode(c.trim().parse().ok().filter(|&c| c <= 15).ok_or_else(bad)?)
+        } else if let Some(c) = upper.strip_prefix('D') {
+            Code::Dcs(u16::from_str_radix(c.trim(), 8).ok().filter(|&c| c <= 0o777).ok_or_else(bad)?)
+        } else {
+            let hz = upper.strip_suffix("HZ").unwrap_or(&upper).trim();
+            let tenths = hz.parse::<f64>().ok().map(|hz| (hz * 10.0).round()).filter(|t| (600.0..=2600.0).contains(t));
+            Code::Ctcss(tenths.ok_or_else(bad)? as u16)
+        };
+        Ok(code)
+    }
 }
 
 #[derive(Debug)]
 pub struct Repeater {
+    callsign: String,
+
     /// Frequency on which the repeater transmits
     output_freq: decimal::d128,
 
@@ -37,12 +259,60 @@ pub struct Repeater {
     ///
     /// optional because some datasets don't include this
     input_freq: Option<decimal::d128>,
+
+    /// Modes the repeater operates in. Multi-mode repeaters have more than one.
+    modes: Vec<Mode>,
+
+    status: Status,
+
+    /// IRLP node number
+    irlp_node: Option<u32>,
+
+    /// EchoLink node number
+    echolink_node: Option<u32>,
+
+    /// When the source last updated its entry for this repeater
+    updated: Option<chrono::NaiveDate>,
+
+    /// Code needed to access the repeater
+    code_in: Option<Code>,
+
+    /// Code sent with the repeater's output, if any
+    code_out: Option<Code>,
+
+    /// D-STAR module, if the listing gives it
+    dstar_module: Option<char>,
+
+    /// Where the repeater is, if known
+    site: Option<Site>,
+
+    /// The town the listing puts the repeater in
+    place: Option<Place>,
+
+    /// What the listing says about the channel, such as who uses it for what
+    comment: Option<String>,
+}
+
+/// A frequency in MHz from a listing that stores it as a float, to the nearest Hz
+pub(crate) fn mhz_from_f64(mhz: f64) -> Option<decimal::d128> {
+    let text = format!("{:.6}", mhz);
+    ne_repeater::parse_mhz(text.trim_end_matches('0').trim_end_matches('.'))
+}
+
+pub(crate) fn mhz_to_f64(mhz: decimal::d128) -> f64 {
+    mhz.to_string().parse().unwrap_or(f64::NAN)
+}
+
+impl geo::Located for Repeater {
+    fn position(&self) -> Option<geo::LatLon> {
+        self.site.as_ref().and_then(geo::Located::position)
+    }
 }
 
 /// A particular location which may have multiple inputs/outputs
 // TODO: add some refinement details, optionality. We might not have all the
 // specifics we want immediately and need to clarify
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Site {
     /// The name of the site, e.g. "Mt. Wilson"
     name: String,
@@ -55,9 +325,118 @@ pub struct Site {
 
     /// The longitude of the site
     lon: f64,
+
+    /// The position is only roughly known, e.g. it is the center of the site's town
+    #[serde(default)]
+    approximate: bool,
+}
+
+impl Site {
+    pub fn new<N: Into<String>, L: Into<String>>(name: N, location: L, position: geo::LatLon) -> Self {
+        Site {
+            name: name.into(),
+            location: location.into(),
+            lat: position.lat,
+            lon: position.lon,
+            approximate: false,
+        }
+    }
+
+    /// Mark the position as approximate
+    pub fn approximate(mut self) -> Self {
+        self.approximate = true;
+        self
+    }
+
+    pub fn is_approximate(&self) -> bool {
+        self.approximate
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    pub fn lat(&self) -> f64 {
+        self.lat
+    }
+
+    pub fn lon(&self) -> f64 {
+        self.lon
+    }
+
+    /// The 6 character grid square the site is in
+    pub fn grid(&self) -> geo::Grid {
+        geo::Grid::of(geo::LatLon { lat: self.lat, lon: self.lon }, 6)
+    }
+}
+
+/// The town, county and state a listing puts a repeater in
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Place {
+    pub town: String,
+
+    /// State or province, as the listing writes it ("MA", "New Brunswick")
+    pub state: String,
+
+    /// Empty if the listing doesn't say
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub county: String,
+}
+
+impl Place {
+    /// Trimmed. `None` if there's no town or state.
+    pub fn new(town: &str, state: &str, county: &str) -> Option<Self> {
+        let (town, state) = (town.trim(), state.trim());
+        if town.is_empty() && state.is_empty() {
+            return None;
+        }
+        Some(Place { town: town.to_owned(), state: state.to_owned(), county: county.trim().to_owned() })
+    }
+
+    /// The same town and state, ignoring case. Counties only differ if both are known.
+    pub fn same(&self, other: &Place) -> bool {
+        self.town.eq_ignore_ascii_case(&other.town)
+            && self.state.eq_ignore_ascii_case(&other.state)
+            && (self.county.is_empty() || other.county.is_empty() || self.county.eq_ignore_ascii_case(&other.county))
+    }
+}
+
+impl std::fmt::Display for Place {
+    /// "Boston, MA"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.town.is_empty(), self.state.is_empty()) {
+            (false, false) => write!(f, "{}, {}", self.town, self.state),
+            (false, true) => f.write_str(&self.town),
+            _ => f.write_str(&self.state),
+        }
+    }
+}
+
+impl geo::Located for Site {
+    fn position(&self) -> Option<geo::LatLon> {
+        Some(geo::LatLon { lat: self.lat, lon: self.lon })
+    }
 }
 
 impl Repeater {
+    pub fn callsign(&self) -> &str {
+        &self.callsign
+    }
+
+    /// MHz
+    pub fn output_freq(&self) -> decimal::d128 {
+        self.output_freq
+    }
+
+    /// MHz
+    pub fn input_freq(&self) -> Option<decimal::d128> {
+        self.input_freq
+    }
+
     pub fn freq_as_offset(&self) -> (f64, f64) {
         todo!()
     }
@@ -66,17 +445,59 @@ impl Repeater {
         todo!()
     }
 
-    pub fn mode(&self) -> usize {
-        todo!()
+    pub fn modes(&self) -> &[Mode] {
+        &self.modes
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn irlp_node(&self) -> Option<u32> {
+        self.irlp_node
+    }
+
+    pub fn echolink_node(&self) -> Option<u32> {
+        self.echolink_node
     }
 
-    /// Note: codes are limited by `mode`, consider if we should have a `mode` which contains the
+    pub fn updated(&self) -> Option<chrono::NaiveDate> {
+        self.updated
+    }
+
+    pub fn site(&self) -> Option<&Site> {
+        self.site.as_ref()
+    }
+
+    pub fn set_site(&mut self, site: Option<Site>) {
+        self.site = site;
+    }
+
+    pub fn place(&self) -> Option<&Place> {
+        self.place.as_ref()
+    }
+
+    pub fn set_place(&mut self, place: Option<Place>) {
+        self.place = place;
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Note: codes are limited by `modes`, consider if we should have a `mode` which contains the
     /// code info
-    pub fn code_in(&self) -> Option<usize> {
-        todo!()
+    pub fn code_in(&self) -> Option<Code> {
+        self.code_in
     }
 
-    pub fn code_out(&self) -> Option<usize> {
-        todo!()
+    pub fn code_out(&self) -> Option<Code> {
+        self.code_out
+    }
+
+    /// The D-STAR module the listing gives. Listings that don't give one usually use the band's
+    /// conventional module, see `dstar_module()`.
+    pub fn dstar_module(&self) -> Option<char> {
+        self.dstar_module
     }
 }
@@ -0,0 +1,177 @@
+//! Checking a channel list for likely mistakes
+//!
+//! Each `Check` looks at the channels for one kind of mistake, using the band plan (`Band`,
+//! `ne_repeater::standard_offset_new_england()`) and the CTCSS tone table. A problem is a
+//! `Finding`, with the record it's in. Errors are channels a radio can't use as listed; warnings
+//! are channels that are unusual, and may be wrong.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::error::*;
+use crate::ne_repeater::standard_offset_new_england;
+use crate::{Band, Code, Location, Mode, Repeater};
+
+/// The standard (EIA) CTCSS tones, in tenths of a Hz
+pub const CTCSS_TONES: [u16; 50] = [
+    670, 693, 719, 744, 770, 797, 825, 854, 885, 915, 948, 974, 1000, 1035, 1072, 1109, 1148, 1188, 1230, 1273,
+    1318, 1365, 1413, 1462, 1514, 1567, 1598, 1622, 1655, 1679, 1713, 1738, 1773, 1799, 1835, 1862, 1899, 1928,
+    1966, 1995, 2035, 2065, 2107, 2181, 2257, 2291, 2336, 2418, 2503, 2541,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A kind of mistake to look for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// The output isn't in an amateur band, or the input isn't in the output's band (error)
+    Band,
+    /// The input isn't the band's standard offset from the output (warning). Simplex channels are
+    /// fine.
+    Offset,
+    /// A CTCSS tone that isn't in `CTCSS_TONES` (error)
+    Tone,
+    /// The same channel as an earlier record: frequencies, code and modes (warning)
+    Duplicate,
+    /// A DMR channel without a color code (error). Mixed channels are only checked if DMR is their
+    /// first mode, the others keep their analog tones as their codes.
+    ColorCode,
+}
+
+impl Check {
+    pub const ALL: [Check; 5] = [Check::Band, Check::Offset, Check::Tone, Check::Duplicate, Check::ColorCode];
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Check::Offset | Check::Duplicate => Severity::Warning,
+            Check::Band | Check::Tone | Check::ColorCode => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    /// The name used on the command line, e.g. "color-code"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Check::Band => "band",
+            Check::Offset => "offset",
+            Check::Tone => "tone",
+            Check::Duplicate => "duplicate",
+            Check::ColorCode => "color-code",
+        })
+    }
+}
+
+impl std::str::FromStr for Check {
+    type Err = FreqmError;
+
+    /// As `Display` writes them, ignoring case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Check::ALL
+            .into_iter()
+            .find(|c| c.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| LintCheckSnafu { value: s }.build().column("check"))
+    }
+}
+
+/// A problem found in one record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub check: Check,
+    /// Where the channel came from
+    pub location: Location,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn severity(&self) -> Severity {
+        self.check.severity()
+    }
+}
+
+impl fmt::Display for Finding {
+    /// "<location>: <severity>: <message> [<check>]"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {} [{}]", self.location, self.severity(), self.message, self.check)
+    }
+}
+
+/// Run `checks` over `channels`, numbering them as records from 1
+pub fn lint(checks: &[Check], channels: &[Repeater]) -> Vec<Finding> {
+    let records = channels.iter().enumerate().map(|(i, r)| (Location { record: Some(i as u64 + 1), ..Default::default() }, r));
+    lint_records(checks, records)
+}
+
+/// Run `checks` over channels with where each came from, returning what they found in order
+pub fn lint_records<'a>(checks: &[Check], channels: impl IntoIterator<Item = (Location, &'a Repeater)>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    // the first record with each channel's settings, for `Check::Duplicate`
+    let mut seen: HashMap<String, Location> = HashMap::new();
+
+    for (location, r) in channels {
+        let mut found = |check: Check, message: String| {
+            let message = format!("{} {}: {}", r.callsign(), r.output_freq().reduce(), message);
+            findings.push(Finding { check, location: location.clone(), message });
+        };
+
+        for &check in checks {
+            match check {
+                Check::Band => match (Band::of(r.output_freq()), r.input_freq()) {
+                    (None, _) => found(check, "output is not in an amateur band".to_owned()),
+                    (Some(band), Some(input)) if Band::of(input) != Some(band) => {
+                        found(check, format!("input {} is outside the {} band", input.reduce(), band))
+                    },
+                    _ => {},
+                },
+                Check::Offset => {
+                    let Some(input) = r.input_freq() else { continue };
+                    let Some(standard) = standard_offset_new_england(r.output_freq()) else { continue };
+                    let offset = input - r.output_freq();
+                    if offset != 0.into() && offset.abs() != standard {
+                        found(check, format!("offset {} MHz is not the band's standard {} MHz", offset.reduce(), standard.reduce()));
+                    }
+                },
+                Check::Tone => {
+                    for (side, code) in [("input", r.code_in()), ("output", r.code_out())] {
+                        if let Some(Code::Ctcss(tone)) = code
+                            && !CTCSS_TONES.contains(&tone)
+                        {
+                            found(check, format!("{} tone {} is not a standard CTCSS tone", side, Code::Ctcss(tone)));
+                        }
+                    }
+                },
+                Check::Duplicate => {
+                    let input = r.input_freq().map(|f| f.reduce());
+                    let key = format!("{} {:?} {:?} {:?}", r.output_freq().reduce(), input, r.code_in(), r.modes());
+                    match seen.get(&key) {
+                        Some(first) => found(check, format!("same channel as {}", first)),
+                        None => {
+                            seen.insert(key, Location { file: None, column: None, ..location.clone() });
+                        },
+                    }
+                },
+                Check::ColorCode => {
+                    let has_cc = [r.code_in(), r.code_out()].iter().any(|c| matches!(c, Some(Code::ColorCode(_))));
+                    if r.modes().first() == Some(&Mode::Dmr) && !has_cc {
+                        found(check, "DMR without a color code".to_owned());
+                    }
+                },
+            }
+        }
+    }
+
+    findings
+}
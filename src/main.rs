@@ -1,10 +1,16 @@
 #![warn(rust_2018_idioms, missing_debug_implementations)]
 use structopt::StructOpt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::convert::TryInto;
+use std::io::IsTerminal;
 
 use freqm::*;
+use freqm::geo::{Distance, Grid, LatLon, Located};
+use freqm::image::{parse_addr, ImageFormat};
+use freqm::import::OnError;
+use freqm::memory_map::MemoryMap;
 use freqm::ne_repeater::*;
+use freqm::repeater_db::{RepeaterDb, Source};
 
 #[derive(Debug, StructOpt)]
 struct FreqmOpts {
@@ -19,37 +25,1075 @@ enum FreqmCmd {
 
     },
     
-    NeCsv { 
+    /// convert a channel list to another format, reporting what the new format can't hold
+    Convert {
+        /// format of the input: chirp, at878, uv390, ne, icom, kenwood, ics, json or yaml. Guessed
+        /// from the delimiter, header row and first records by default.
+        #[structopt(long, parse(try_from_str = freqm::formats::find))]
+        from: Option<&'static freqm::formats::Format>,
+
+        /// format to write, as for --from. The radio's format by default.
+        #[structopt(long, parse(try_from_str = freqm::formats::find), required_unless = "radio")]
+        to: Option<&'static freqm::formats::Format>,
+
+        /// radio the list is for (see `freqm models`), whose name length and characters --name
+        /// fits names to
+        #[structopt(long, parse(try_from_str = freqm::models::find))]
+        radio: Option<&'static freqm::models::Model>,
+
+        /// name channels with this template (see `freqm::names`), e.g. "{callsign} {town:4}"
+        #[structopt(long)]
+        name: Option<freqm::names::Template>,
+
+        /// skip rows that fail to import instead of stopping
+        #[structopt(long)]
+        keep_going: bool,
+
+        /// only convert channels matching this filter (see `freqm::filter`), e.g.
+        /// "band = 2m and not mode = digital"
+        #[structopt(long = "where")]
+        filter: Option<freqm::filter::Filter>,
+
+        #[structopt(flatten)]
+        layout: LayoutOpts,
+
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// where to write the converted list, standard output by default
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+
+    /// build the channel lists for every radio in a project file (see `freqm::project`)
+    ///
+    /// Filters are set in the project file, for all radios or each one, rather than with --where.
+    Build {
+        /// project file (toml)
+        #[structopt(parse(from_os_str))]
+        project: PathBuf,
+    },
+
+    /// compare two channel lists, matching channels by callsign and output frequency: the channels
+    /// added, removed, and the fields changed in the others
+    Diff {
+        /// format of both lists, as for `convert --from`. Guessed from each file by default.
+        #[structopt(long, parse(try_from_str = freqm::formats::find))]
+        from: Option<&'static freqm::formats::Format>,
+
+        /// skip rows that fail to import instead of stopping
+        #[structopt(long)]
+        keep_going: bool,
+
+        /// write the differences as JSON
+        #[structopt(long)]
+        json: bool,
+
+        /// only compare channels matching this filter (see `freqm::filter`), in both lists
+        #[structopt(long = "where")]
+        filter: Option<freqm::filter::Filter>,
+
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+    },
+
+    /// check a channel list for likely mistakes: inputs outside the band, non-standard offsets,
+    /// non-standard CTCSS tones, duplicate channels and DMR channels without a color code. Exits
+    /// with an error if any check reports an error.
+    Lint {
+        /// format of the input, as for `convert --from`. Guessed by default.
+        #[structopt(long, parse(try_from_str = freqm::formats::find))]
+        from: Option<&'static freqm::formats::Format>,
+
+        /// skip rows that fail to import instead of stopping
+        #[structopt(long)]
+        keep_going: bool,
+
+        /// only run these checks: band, offset, tone, duplicate, color-code. All by default.
+        #[structopt(long, use_delimiter = true)]
+        check: Vec<freqm::lint::Check>,
+
+        /// don't run these checks
+        #[structopt(long, use_delimiter = true)]
+        skip: Vec<freqm::lint::Check>,
+
+        /// fail on warnings too
+        #[structopt(long)]
+        strict: bool,
+
+        /// only check channels matching this filter (see `freqm::filter`). Duplicates are only
+        /// looked for among them.
+        #[structopt(long = "where")]
+        filter: Option<freqm::filter::Filter>,
+
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
+
+    /// guess the format of channel lists, with how sure the guess is
+    Sniff {
+        #[structopt(parse(from_os_str))]
+        files: Vec<PathBuf>,
+    },
+
+    /// show linked systems and remote receivers from the NE repeater listing
+    ///
+    /// With a callsign, show that repeater's links and the network it belongs to. Otherwise list
+    /// all networks.
+    Links {
+        /// NE repeater listing csv
+        #[structopt(long, parse(from_os_str))]
+        ne_csv: PathBuf,
+
+        /// emit a GraphViz DOT graph instead of text
+        #[structopt(long)]
+        dot: bool,
+
+        /// only show repeaters matching this filter (see `freqm::filter`). Networks are still
+        /// found through the others.
+        #[structopt(long = "where")]
+        filter: Option<freqm::filter::Filter>,
+
+        callsign: Option<String>,
+    },
+
+    /// list repeaters within a distance of a position, nearest first
+    Near {
+        /// "<lat>,<lon>" in decimal degrees, a grid locator (e.g. "FN42ki"), or "<town>, <state>"
+        /// (with --gazetteer)
+        center: String,
+
+        /// search radius, e.g. "30mi" or "50km"
+        #[structopt(long, default_value = "30mi")]
+        radius: Distance,
+
+        /// only show repeaters in these grid squares
+        #[structopt(long)]
+        grid: Vec<Grid>,
+
+        #[structopt(flatten)]
+        datasets: Datasets,
+    },
+
+    /// merge repeater listings into one database, matching repeaters by callsign and output
+    /// frequency, and show where the listings disagree
+    Merge {
+        #[structopt(flatten)]
+        datasets: Datasets,
+
+        /// where to save the database (json)
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// also write the merged repeaters as a channel file, with the listing each value came
+        /// from (yaml if named .yaml or .yml, json otherwise)
+        #[structopt(long, parse(from_os_str))]
+        channels: Option<PathBuf>,
+    },
+
+    /// export repeaters to a SQLite database for querying with SQL, and import them back
+    Db {
+        #[structopt(subcommand)]
+        command: DbCmd,
+    },
+
+    /// build a channel list of the repeaters along a route, in the order they're passed
+    Route {
+        /// route waypoints, in order: "<lat>,<lon>", grid locators or "<town>, <state>" (with
+        /// --gazetteer)
+        #[structopt(long, required_unless = "gpx")]
+        via: Vec<String>,
+
+        /// follow the track (or route, or waypoints) in a GPX file instead
+        #[structopt(long, parse(from_os_str), conflicts_with = "via")]
+        gpx: Option<PathBuf>,
+
+        /// how far from the route repeaters may be, e.g. "10mi" or "15km"
+        #[structopt(long, default_value = "10mi")]
+        corridor: Distance,
+
+        /// radio to build the list for (see `freqm models`). Only repeaters it can use are
+        /// included, and the list is cut to the radio's channel count by dropping the repeaters
+        /// farthest from the route.
+        #[structopt(long, parse(try_from_str = freqm::models::find))]
+        radio: Option<&'static freqm::models::Model>,
+
+        /// use at most this many channels, instead of the radio's channel count
+        #[structopt(long)]
+        channels: Option<usize>,
+
+        /// write the channel list in the radio's format here, instead of listing the repeaters
+        #[structopt(short, long, parse(from_os_str), requires = "radio")]
+        output: Option<PathBuf>,
+
+        /// name channels in the list written with this template (see `freqm::names`), fitted to
+        /// the radio
+        #[structopt(long, requires = "output")]
+        name: Option<freqm::names::Template>,
+
+        #[structopt(flatten)]
+        datasets: Datasets,
+    },
+
+    /// inspect radio memory images
+    ///
+    /// These work on an image's bytes rather than a channel list, so they don't take --where.
+    Image {
+        #[structopt(subcommand)]
+        command: ImageCmd,
+    },
+}
+
+// only made once, from the command line
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, StructOpt)]
+enum DbCmd {
+    /// write the merged repeaters, their sites and the listings they came from to a SQLite
+    /// database, replacing the repeaters already in it
+    Export {
+        /// SQLite database to write
+        #[structopt(parse(from_os_str))]
+        database: PathBuf,
+
+        /// channel lists to add after the merged repeaters, in any format `freqm convert` reads
+        #[structopt(long, parse(from_os_str))]
+        list: Vec<PathBuf>,
+
+        /// skip rows that fail to import instead of stopping
+        #[structopt(long)]
+        keep_going: bool,
+
+        #[structopt(flatten)]
+        datasets: Datasets,
+    },
+
+    /// write the repeaters in a SQLite database (from `freqm db export`) as a channel list
+    Import {
+        /// SQLite database to read
+        #[structopt(parse(from_os_str))]
+        database: PathBuf,
+
+        /// format to write, as for `freqm convert --to`
+        #[structopt(long, parse(try_from_str = freqm::formats::find))]
+        to: &'static freqm::formats::Format,
+
+        /// only write repeaters matching this filter (see `freqm::filter`)
+        #[structopt(long = "where")]
+        filter: Option<freqm::filter::Filter>,
+
+        /// where to write the list, standard output by default
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum ImageCmd {
+    /// hexdump an image, showing gaps
+    Dump {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        #[structopt(flatten)]
+        source: ImageSource,
+
+        #[structopt(flatten)]
+        view: ImageView,
+    },
+
+    /// show the bytes that differ between two images
+    Diff {
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+
+        #[structopt(flatten)]
+        source: ImageSource,
+
+        #[structopt(flatten)]
+        view: ImageView,
+    },
+
+    /// print the values in an image using a memory map
+    Decode {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        #[structopt(flatten)]
+        source: ImageSource,
+
+        /// memory map definition (toml)
+        #[structopt(long, parse(from_os_str))]
+        map: PathBuf,
+
+        /// only show values with paths starting with these
+        paths: Vec<String>,
+    },
+
+    /// change values in an image using a memory map
+    Set {
         #[structopt(parse(from_os_str))]
         file: PathBuf,
+
+        #[structopt(flatten)]
+        source: ImageSource,
+
+        /// memory map definition (toml)
+        #[structopt(long, parse(from_os_str))]
+        map: PathBuf,
+
+        /// where to write the changed image. The format is guessed from the extension, or is the
+        /// same as the input. Chirp .img metadata is kept.
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+
+        /// "<path>=<value>", e.g. "channels[3].name=MURS 1"
+        assignments: Vec<String>,
     },
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+#[derive(Debug, StructOpt)]
+struct ImageSource {
+    /// image format: raw, ihex, srec or img. Guessed from the file extension by default
+    #[structopt(long)]
+    format: Option<ImageFormat>,
+
+    /// address of the first byte of raw images
+    #[structopt(long, parse(try_from_str = parse_addr))]
+    base: Option<u64>,
+}
+
+impl ImageSource {
+    fn format(&self, path: &Path) -> Result<ImageFormat, Box<dyn std::error::Error>> {
+        let format = match self.format.or_else(|| ImageFormat::from_path(path)) {
+            Some(f) => f,
+            None => return Err(format!("{}: unknown image format, use --format", path.display()).into()),
+        };
+
+        Ok(match (format, self.base) {
+            (ImageFormat::Raw { .. }, Some(base)) => ImageFormat::Raw { base },
+            (f, _) => f,
+        })
+    }
+
+    fn load(&self, path: &Path) -> Result<freqm::sparse_mem::SparseMem, Box<dyn std::error::Error>> {
+        Ok(freqm::image::load(path, self.format(path)?)?)
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct ImageView {
+    /// first address to show
+    #[structopt(long, parse(try_from_str = parse_addr))]
+    start: Option<u64>,
+
+    /// address to stop before
+    #[structopt(long, parse(try_from_str = parse_addr))]
+    end: Option<u64>,
+
+    /// label regions using a memory map definition (.toml), or a list of regions
+    /// ("<start>..<end> <name>" per line)
+    #[structopt(long, parse(from_os_str))]
+    map: Option<PathBuf>,
+
+    /// don't color changed bytes and gaps
+    #[structopt(long)]
+    no_color: bool,
+}
+
+impl ImageView {
+    fn annotations(&self) -> Result<Option<freqm::hexdump::Annotations>, FreqmError> {
+        let map = match &self.map {
+            Some(m) => m,
+            None => return Ok(None),
+        };
+
+        if map.extension().is_some_and(|e| e == "toml") {
+            Ok(Some(MemoryMap::load(map)?.annotations()))
+        } else {
+            freqm::hexdump::Annotations::load(map).map(Some)
+        }
+    }
+
+    fn options<'a>(&self, annotations: Option<&'a freqm::hexdump::Annotations>) -> freqm::hexdump::DumpOptions<'a> {
+        let range = match (self.start, self.end) {
+            (None, None) => None,
+            (start, end) => Some(start.unwrap_or(0)..end.unwrap_or(u64::MAX)),
+        };
+
+        freqm::hexdump::DumpOptions {
+            range,
+            annotations,
+            color: !self.no_color && std::io::stdout().is_terminal(),
+        }
+    }
+}
+
+/// How to order, number and group the channels written
+#[derive(Debug, StructOpt)]
+struct LayoutOpts {
+    /// sort channels by these, most significant first: freq, distance (from --center), name
+    /// (town) or callsign. By default they stay in the input's order.
+    #[structopt(long, use_delimiter = true)]
+    sort: Vec<freqm::layout::SortKey>,
+
+    /// where distances are sorted from: "<lat>,<lon>" or a grid locator
+    #[structopt(long)]
+    center: Option<LatLon>,
+
+    /// number of the first channel
+    #[structopt(long, default_value = "1")]
+    first: usize,
+
+    /// channel numbers to leave free, e.g. "1-10"
+    #[structopt(long, parse(try_from_str = freqm::layout::parse_range))]
+    reserve: Vec<std::ops::RangeInclusive<usize>>,
+
+    /// put channels in groups (zones, banks) by band, mode, county, state or town
+    #[structopt(long)]
+    group_by: Option<freqm::layout::GroupBy>,
+
+    /// most channels in a group, bigger groups are split
+    #[structopt(long, requires = "group-by")]
+    group_size: Option<usize>,
+
+    /// where to write the groups, for formats that list them in a separate file (the AT-878 zone
+    /// list)
+    #[structopt(long, parse(from_os_str), requires = "group-by")]
+    groups_output: Option<PathBuf>,
+
+    /// where to write a scan list for each group, for radios that scan by list (the AT-878 scan
+    /// list list)
+    #[structopt(long, parse(from_os_str), requires = "group-by")]
+    scan_lists_output: Option<PathBuf>,
+}
+
+impl LayoutOpts {
+    fn layout(&self) -> Result<freqm::layout::Layout, Box<dyn std::error::Error>> {
+        if self.sort.contains(&freqm::layout::SortKey::Distance) && self.center.is_none() {
+            return Err("sorting by distance needs --center".into());
+        }
+
+        Ok(freqm::layout::Layout {
+            sort: self.sort.clone(),
+            center: self.center,
+            first: self.first,
+            reserved: self.reserve.clone(),
+            group_by: self.group_by,
+            group_size: self.group_size,
+            limit: None,
+        })
+    }
+
+    /// Write the groups of `plan` where `format` keeps them, warning if it can't
+    fn write_groups(&self, format: &freqm::formats::Format, plan: &freqm::layout::Plan<'_>) -> Result<(), Box<dyn std::error::Error>> {
+        if self.group_by.is_none() {
+            return Ok(());
+        }
+        write_groups(format, plan, self.groups_output.as_deref(), self.scan_lists_output.as_deref())
+    }
+}
+
+/// Write the groups of `plan` to `output` if `format` keeps them in a separate file, and their scan
+/// lists to `scan_lists` if its radio scans by list, warning if it can't keep them
+fn write_groups(
+    format: &freqm::formats::Format,
+    plan: &freqm::layout::Plan<'_>,
+    output: Option<&Path>,
+    scan_lists: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use freqm::formats::Groups;
+
+    let create = |path: &Path| {
+        std::fs::File::create(path).map(std::io::BufWriter::new).map_err(|e| FreqmError::from(e).with_file(path))
+    };
+    match (format.groups, output) {
+        (Groups::None, _) => eprintln!("{} has no channel groups, grouping ignored", format.name),
+        (Groups::Columns, Some(_)) => return Err(format!("{} keeps groups in its rows, not a separate file", format.name).into()),
+        (Groups::Columns, None) => {},
+        (Groups::File { .. }, None) => eprintln!("{} lists groups in a separate file, give a groups output to write it", format.name),
+        (Groups::File { .. }, Some(path)) => {
+            format.write_groups(create(path)?, plan)?;
+            eprintln!("{} groups", plan.groups.len());
+        },
+    }
+    match (format.groups, scan_lists) {
+        (Groups::File { scan_lists: Some(_), .. }, None) => {
+            eprintln!("{} channels are in their group's scan list, give a scan lists output to write them", format.name)
+        },
+        (Groups::File { scan_lists: Some(_), .. }, Some(path)) => {
+            format.write_scan_lists(create(path)?, plan)?;
+            eprintln!("{} scan lists", plan.groups.len());
+        },
+        (_, Some(_)) => return Err(format!("{} has no scan lists", format.name).into()),
+        (_, None) => {},
+    }
+    Ok(())
+}
+
+/// Repeater listings to search
+#[derive(Debug, StructOpt)]
+struct Datasets {
+    /// Icom repeater list csv (IRNAID51.csv)
+    #[structopt(long, parse(from_os_str))]
+    icom: Vec<PathBuf>,
+
+    /// saved copy of radioid.net's rptrs.json
+    #[structopt(long, parse(from_os_str))]
+    radioid: Vec<PathBuf>,
+
+    /// Kenwood TH-D74 repeater list tsv
+    #[structopt(long, parse(from_os_str))]
+    kenwood: Vec<PathBuf>,
+
+    /// NE repeater listing csv. Repeaters are placed at their town, using --gazetteer.
+    #[structopt(long, parse(from_os_str))]
+    ne_csv: Vec<PathBuf>,
+
+    /// places file for locating towns: a US Census gazetteer file, or a csv with a
+    /// "state,town,lat,lon" header
+    #[structopt(long, parse(from_os_str))]
+    gazetteer: Vec<PathBuf>,
+
+    /// merged repeater database (from `freqm merge`) to start from
+    #[structopt(long, parse(from_os_str))]
+    db: Option<PathBuf>,
+
+    /// sources whose values win when listings disagree, highest first (ne, icom, kenwood,
+    /// radioid)
+    #[structopt(long, use_delimiter = true)]
+    prefer: Vec<Source>,
+
+    /// only use repeaters matching this filter, e.g. "state = MA and (band = 2m or tone)". Tests
+    /// are band, freq, mode, tone, town, county, state, status, callsign, updated and within.
+    #[structopt(long = "where")]
+    filter: Option<freqm::filter::Filter>,
+}
+
+impl Datasets {
+    fn gazetteer(&self) -> Result<freqm::gazetteer::Gazetteer, FreqmError> {
+        let mut gazetteer = freqm::gazetteer::Gazetteer::new();
+        for path in &self.gazetteer {
+            gazetteer.load(path)?;
+        }
+        Ok(gazetteer)
+    }
+
+    /// "<lat>,<lon>", a grid locator, or "<town>, <state>"
+    fn position(&self, text: &str, gazetteer: &freqm::gazetteer::Gazetteer) -> Result<LatLon, Box<dyn std::error::Error>> {
+        match (text.parse::<LatLon>(), gazetteer.lookup_place(text)) {
+            (Ok(pos), _) | (Err(_), Some(pos)) => Ok(pos),
+            (Err(e), None) if gazetteer.is_empty() => Err(e.into()),
+            (Err(_), None) => Err(format!("{:?} is not a position, grid locator or known town", text).into()),
+        }
+    }
+
+    /// The listings, merged into one database, keeping the entries matching --where. Entries that
+    /// fail to import are reported and skipped.
+    fn load(&self, gazetteer: &freqm::gazetteer::Gazetteer) -> Result<RepeaterDb, Box<dyn std::error::Error>> {
+        fn convert<'a, T: 'a>(path: &Path, items: impl IntoIterator<Item = &'a T>, source: Source, db: &mut RepeaterDb)
+        where
+            &'a T: TryInto<Repeater, Error = FreqmError>,
+        {
+            for item in items {
+                match item.try_into() {
+                    Ok(r) => {
+                        db.add(source, r);
+                    },
+                    Err(e) => eprintln!("skipped: {}", e.with_file(path)),
+                }
+            }
+        }
+
+        let mut db = match &self.db {
+            Some(path) => RepeaterDb::load(path)?,
+            None => RepeaterDb::new(self.prefer.clone()),
+        };
+        for path in &self.icom {
+            let (lines, report) = freqm::icom_id51a::read_csv(path, OnError::Continue)?;
+            report.write_details(std::io::stderr().lock())?;
+            convert(path, &lines, Source::Icom, &mut db);
+        }
+        for path in &self.radioid {
+            let rptrs = freqm::radioid::read_rptrs(path)?;
+            convert(path, &rptrs.rptrs, Source::RadioId, &mut db);
+        }
+        for path in &self.kenwood {
+            let (rows, report) = freqm::csv::read_kenwood_tsv(path, OnError::Continue)?;
+            report.write_details(std::io::stderr().lock())?;
+            convert(path, &rows, Source::Kenwood, &mut db);
+        }
+        for path in &self.ne_csv {
+            let (records, report) = read_csv(path, OnError::Continue)?;
+            report.write_details(std::io::stderr().lock())?;
+
+            let mut unlocated = 0;
+            for record in records {
+                let site = record.site(gazetteer);
+                unlocated += site.is_none() as usize;
+                match Repeater::try_from(record) {
+                    Ok(mut r) => {
+                        r.set_site(site);
+                        db.add(Source::Ne, r);
+                    },
+                    Err(e) => eprintln!("skipped: {}", e.with_file(path)),
+                }
+            }
+            if unlocated > 0 {
+                eprintln!("{}: {} repeaters in towns not in the gazetteer", path.display(), unlocated);
+            }
+        }
+
+        if let Some(filter) = &self.filter {
+            db.retain(|e| filter.matches(&e.repeater()));
+        }
+        Ok(db)
+    }
+}
+
+/// "<callsign> <output MHz> <modes> <grid> <location>". Approximate positions have a "~" before
+/// the grid square.
+fn describe(r: &Repeater) -> String {
+    let modes: Vec<String> = r.modes().iter().map(|m| format!("{:?}", m)).collect();
+    let grid = match r.site() {
+        Some(s) if s.is_approximate() => format!("~{}", s.grid()),
+        Some(s) => format!(" {}", s.grid()),
+        None => String::new(),
+    };
+    let location = r.site().map(|s| s.location()).unwrap_or_default();
+    format!("{:<8} {:>10} {:<6} {:<7} {}", r.callsign(), r.output_freq().to_string(), modes.join("/"), grid, location)
+}
+
+/// The channels in `input`, read as `from` or the format it's sniffed to be. Import problems are
+/// reported, and the import report returned.
+fn read_list(
+    from: Option<&'static freqm::formats::Format>,
+    keep_going: bool,
+    input: &Path,
+) -> Result<freqm::formats::Channels, Box<dyn std::error::Error>> {
+    let on_error = if keep_going { OnError::Continue } else { OnError::Abort };
+    let bytes = std::fs::read(input).map_err(|e| FreqmError::from(e).with_file(input))?;
+    let from = match from {
+        Some(f) => f,
+        None => {
+            let unknown = || format!("{}: unrecognized channel list format, use --from", input.display());
+            let sniffed = freqm::formats::sniff(&bytes).ok_or_else(unknown)?;
+            if sniffed.confidence < 0.5 {
+                return Err(format!("{} (closest is {}, {:.0}% sure)", unknown(), sniffed.format.id, sniffed.confidence * 100.0).into());
+            }
+            eprintln!("reading {} as {} ({:.0}% sure)", input.display(), sniffed.format.name, sniffed.confidence * 100.0);
+            sniffed.format
+        },
+    };
+
+    let (repeaters, mut report) = from.read(&bytes, on_error).map_err(|e| e.with_file(input))?;
+    report.set_file(input);
+    report.write_details(std::io::stderr().lock())?;
+    Ok((repeaters, report))
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     let opt = FreqmOpts::from_args();
 
     match opt.command {
-        FreqmCmd::NeCsv { file } => {
-            let mut csv = csv::ReaderBuilder::new()
-                .has_headers(false)
-                .flexible(true)
-                .from_path(file)?;
+        FreqmCmd::Convert { from, to, radio, name, keep_going, filter, layout, input, output } => {
+            let to = match (to, radio) {
+                (Some(to), _) => to,
+                (None, Some(radio)) => radio.format.list_format(),
+                (None, None) => unreachable!("structopt requires --to or --radio"),
+            };
+            let (mut repeaters, _) = read_list(from, keep_going, &input)?;
+            if let Some(filter) = filter {
+                let total = repeaters.len();
+                repeaters = filter.select(repeaters).collect();
+                eprintln!("{} of {} channels match {:?}", repeaters.len(), total, filter.to_string());
+            }
+
+            let (written, report) = to.check(&repeaters);
+            let mut plan = layout.layout()?.plan(&written);
+            if let Some(name) = &name {
+                let rules = radio.map(|r| r.names).unwrap_or(freqm::names::NameRules::UNLIMITED);
+                freqm::names::name_channels(&mut plan, name, &rules);
+            }
+            match &output {
+                Some(output) => {
+                    let file = std::fs::File::create(output).map_err(|e| FreqmError::from(e).with_file(output))?;
+                    to.write_plan(std::io::BufWriter::new(file), &plan)?
+                },
+                None => to.write_plan(std::io::stdout().lock(), &plan)?,
+            };
+            report.write_details(std::io::stderr().lock())?;
+            layout.write_groups(to, &plan)?;
+        },
+        FreqmCmd::Build { project } => {
+            let project = freqm::project::Project::load(&project)?;
+            let inputs = &project.inputs;
+            let datasets = Datasets {
+                icom: inputs.icom.clone(),
+                radioid: inputs.radioid.clone(),
+                kenwood: inputs.kenwood.clone(),
+                ne_csv: inputs.ne_csv.clone(),
+                gazetteer: inputs.gazetteer.clone(),
+                db: inputs.db.clone(),
+                prefer: inputs.prefer.clone(),
+                filter: project.filter.clone(),
+            };
+            let gazetteer = datasets.gazetteer()?;
+            let mut repeaters = datasets.load(&gazetteer)?.repeaters();
+            for list in &inputs.lists {
+                let (channels, _) = read_list(list.format, false, &list.path)?;
+                let keep = |r: &Repeater| project.filter.as_ref().is_none_or(|f| f.matches(r));
+                repeaters.extend(channels.into_iter().filter(keep));
+            }
+
+            for radio in &project.radios {
+                let format = radio.format();
+                let selected = repeaters
+                    .iter()
+                    .filter(|r| radio.filter.as_ref().is_none_or(|f| f.matches(r)))
+                    .filter(|r| radio.model.is_none_or(|m| m.supports(r)));
+                let (written, report) = format.check(selected);
+                // keep the channels that come first once sorted
+                let layout = freqm::layout::Layout {
+                    limit: Some(radio.channels()),
+                    ..radio.layout.as_ref().unwrap_or(&project.layout).layout()
+                };
+                if written.len() > radio.channels() {
+                    eprintln!("{}: {} channels, keeping the first {}", radio.output.display(), written.len(), radio.channels());
+                }
+                let mut plan = layout.plan(&written);
+                if let Some(name) = &radio.name {
+                    let rules = radio.model.map(|m| m.names).unwrap_or(freqm::names::NameRules::UNLIMITED);
+                    freqm::names::name_channels(&mut plan, name, &rules);
+                }
+
+                if let Some(dir) = radio.output.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| FreqmError::from(e).with_file(dir))?;
+                }
+                let file = std::fs::File::create(&radio.output).map_err(|e| FreqmError::from(e).with_file(&radio.output))?;
+                format.write_plan(std::io::BufWriter::new(file), &plan)?;
+                eprintln!("{}: {}", radio.output.display(), report);
+                if layout.group_by.is_some() {
+                    write_groups(format, &plan, radio.groups_output.as_deref(), radio.scan_lists_output.as_deref())?;
+                }
+            }
+        },
+        FreqmCmd::Diff { from, keep_going, json, filter, old, new } => {
+            let (mut old, _) = read_list(from, keep_going, &old)?;
+            let (mut new, _) = read_list(from, keep_going, &new)?;
+            if let Some(filter) = &filter {
+                old.retain(|r| filter.matches(r));
+                new.retain(|r| filter.matches(r));
+            }
+            let diff = freqm::diff::diff(&old, &new);
+            if json {
+                serde_json::to_writer_pretty(std::io::stdout().lock(), &diff)?;
+                println!();
+            } else {
+                diff.write_text(std::io::stdout().lock())?;
+            }
+        },
+        FreqmCmd::Lint { from, keep_going, check, skip, strict, filter, input } => {
+            use freqm::lint::{Check, Severity};
+
+            let (repeaters, report) = read_list(from, keep_going, &input)?;
+            let checks: Vec<Check> = if check.is_empty() { Check::ALL.to_vec() } else { check };
+            let checks: Vec<Check> = checks.into_iter().filter(|c| !skip.contains(c)).collect();
+
+            // refer to records as numbered in the file
+            let selected: Vec<(u64, &Repeater)> = report
+                .records
+                .iter()
+                .copied()
+                .zip(&repeaters)
+                .filter(|(_, r)| filter.as_ref().is_none_or(|f| f.matches(r)))
+                .collect();
+            let checked = selected.len();
+
+            let located = selected.into_iter().map(|(record, r)| {
+                (Location { file: Some(input.clone()), record: Some(record), ..Default::default() }, r)
+            });
+            let findings = freqm::lint::lint_records(&checks, located);
+            for f in &findings {
+                println!("{}", f);
+            }
+
+            let errors = findings.iter().filter(|f| f.severity() == Severity::Error).count();
+            let warnings = findings.len() - errors;
+            eprintln!("{} channels: {} errors, {} warnings", checked, errors, warnings);
+            if errors > 0 || (strict && warnings > 0) {
+                std::process::exit(1);
+            }
+        },
+        FreqmCmd::Sniff { files } => {
+            for file in files {
+                let bytes = std::fs::read(&file).map_err(|e| FreqmError::from(e).with_file(&file))?;
+                let found = freqm::formats::sniff_all(&bytes);
+                let guesses: Vec<String> = found
+                    .iter()
+                    .map(|s| format!("{} ({:.0}%)", s.format.id, s.confidence * 100.0))
+                    .collect();
+                if guesses.is_empty() {
+                    println!("{}: unrecognized", file.display());
+                } else {
+                    println!("{}: {}", file.display(), guesses.join(", "));
+                }
+            }
+        },
+        FreqmCmd::Links { ne_csv, dot, filter, callsign } => {
+            let (records, report) = read_csv(ne_csv, OnError::Continue)?;
+            report.write_details(std::io::stderr().lock())?;
+            let graph = freqm::ne_links::LinkGraph::new(&records);
+            let nodes = graph.nodes();
 
-            for r in csv.records() {
-                let r = r?;
-                println!("{:?}", r);
-                let record: NeRepeaterRecord = r.try_into()?;
+            // the graph keeps every repeater, so links through the ones not shown are still followed
+            let shown: Vec<bool> = records
+                .iter()
+                .map(|r| filter.as_ref().is_none_or(|f| Repeater::try_from(r.clone()).is_ok_and(|r| f.matches(&r))))
+                .collect();
 
-                println!("{:?}", record); 
+            let selected = match &callsign {
+                Some(callsign) => {
+                    let mut found = graph.find_callsign(callsign);
+                    found.retain(|&n| shown[n]);
+                    if found.is_empty() {
+                        return Err(format!("callsign {} not found", callsign).into());
+                    }
+                    Some(found)
+                },
+                None => None,
+            };
 
-                let r2: Repeater = record.try_into()?;
+            // all the networks the selected repeaters participate in
+            let network = selected.as_ref().map(|found| {
+                let mut network: Vec<usize> = found.iter().flat_map(|&n| graph.network_of(n)).filter(|&n| shown[n]).collect();
+                network.sort();
+                network.dedup();
+                network
+            });
 
-                println!("{:?}", r2);
+            if dot {
+                let only = network.or_else(|| filter.is_some().then(|| (0..nodes.len()).filter(|&n| shown[n]).collect()));
+                graph.write_dot(std::io::stdout().lock(), only.as_deref())?;
+            } else if let Some(found) = selected {
+                for n in found {
+                    println!("{}", nodes[n]);
+                    for e in graph.edges_of(n).filter(|e| shown[e.from] && shown[e.to]) {
+                        let kind = match e.kind {
+                            freqm::ne_links::EdgeKind::Link { .. } => "link",
+                            freqm::ne_links::EdgeKind::RemoteRx if e.from == n => "remote rx for",
+                            freqm::ne_links::EdgeKind::RemoteRx => "remote rx",
+                        };
+                        let other = if e.from == n { e.to } else { e.from };
+                        println!("  {}: {}", kind, nodes[other]);
+                    }
+                    for net in graph.nets_of(n) {
+                        println!("  net: {}", net);
+                    }
+                }
 
+                println!("network:");
+                for m in network.unwrap() {
+                    println!("  {}", nodes[m]);
+                }
+            } else {
+                for (i, network) in graph.networks().iter().enumerate() {
+                    if !network.iter().any(|&m| shown[m]) {
+                        continue;
+                    }
+                    println!("network {}:", i);
+                    for &m in network.iter().filter(|&&m| shown[m]) {
+                        println!("  {}", nodes[m]);
+                    }
+                }
+
+                for u in graph.unresolved().iter().filter(|u| shown[u.from]) {
+                    println!("unresolved: {}: {}", nodes[u.from], u.reference);
+                }
+            }
+        },
+        FreqmCmd::Near { center, radius, grid, datasets } => {
+            let gazetteer = datasets.gazetteer()?;
+            let center = datasets.position(&center, &gazetteer)?;
+            let mut repeaters = datasets.load(&gazetteer)?.repeaters();
+            if !grid.is_empty() {
+                repeaters.retain(|r| r.position().is_some_and(|p| grid.iter().any(|g| g.contains(&p))));
+            }
+
+            for found in freqm::geo::near(&repeaters, center, radius) {
+                println!(
+                    "{:>8} {:>3.0}° {:<3} {}",
+                    found.distance.to_string(),
+                    found.bearing,
+                    freqm::geo::compass(found.bearing),
+                    describe(found.item),
+                );
+            }
+        },
+        FreqmCmd::Merge { datasets, output, channels } => {
+            let db = datasets.load(&datasets.gazetteer()?)?;
+            let conflicted: Vec<_> = db.entries().iter().filter(|e| !e.conflicts().is_empty()).collect();
+            for e in &conflicted {
+                println!("{} {}:", e.callsign, e.output_freq);
+                for c in e.conflicts() {
+                    println!("  {}", c);
+                }
+            }
+
+            let merged = db.entries().iter().filter(|e| e.sources.len() > 1).count();
+            eprintln!(
+                "{} repeaters, {} found in more than one listing, {} with conflicts",
+                db.entries().len(),
+                merged,
+                conflicted.len(),
+            );
+
+            if let Some(output) = output {
+                db.save(output)?;
+            }
+            if let Some(channels) = channels {
+                freqm::channel_file::ChannelFile::from_db(&db).save(channels)?;
+            }
+        },
+        FreqmCmd::Db { command: DbCmd::Export { database, list, keep_going, datasets } } => {
+            let db = datasets.load(&datasets.gazetteer()?)?;
+            let merged = db.repeaters();
+            let mut lists = Vec::new();
+            for path in &list {
+                let (channels, _) = read_list(None, keep_going, path)?;
+                lists.extend(channels.into_iter().filter(|r| datasets.filter.as_ref().is_none_or(|f| f.matches(r))));
+            }
+
+            let sources = db.entries().iter().map(|e| e.sources.as_slice());
+            let channels = merged.iter().zip(sources).chain(lists.iter().map(|r| (r, &[][..])));
+            freqm::sqlite::save(&database, channels)?;
+            eprintln!("{}: {} merged repeaters, {} from channel lists", database.display(), merged.len(), lists.len());
+        },
+        FreqmCmd::Db { command: DbCmd::Import { database, to, filter, output } } => {
+            let mut repeaters = freqm::sqlite::load(&database)?;
+            if let Some(filter) = &filter {
+                repeaters.retain(|r| filter.matches(r));
             }
+            let report = match &output {
+                Some(output) => {
+                    let file = std::fs::File::create(output).map_err(|e| FreqmError::from(e).with_file(output))?;
+                    to.write(std::io::BufWriter::new(file), &repeaters)?
+                },
+                None => to.write(std::io::stdout().lock(), &repeaters)?,
+            };
+            report.write_details(std::io::stderr().lock())?;
+        },
+        FreqmCmd::Route { via, gpx, corridor, radio, channels, output, name, datasets } => {
+            let gazetteer = datasets.gazetteer()?;
+            let route = match gpx {
+                Some(gpx) => freqm::route::Route::load_gpx(gpx)?,
+                None => {
+                    let points = via
+                        .iter()
+                        .map(|v| datasets.position(v, &gazetteer))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    freqm::route::Route::new(points).ok_or("no waypoints")?
+                },
+            };
+
+            let mut repeaters = datasets.load(&gazetteer)?.repeaters();
+            if let Some(radio) = radio {
+                repeaters.retain(|r| radio.supports(r));
+            }
+
+            let found = freqm::route::corridor(&repeaters, &route, corridor);
+            let total = found.len();
+            let channels = channels.or(radio.map(|r| r.channels)).unwrap_or(usize::MAX);
+            let found = freqm::route::fit(found, channels);
+            if found.len() < total {
+                eprintln!("{} repeaters along the route, keeping the {} nearest to it", total, found.len());
+            }
+
+            match output {
+                Some(output) => {
+                    let radio = radio.expect("structopt requires --radio with --output");
+                    let selected: Vec<&Repeater> = found.iter().map(|f| f.item).collect();
+                    let mut plan = freqm::layout::Layout::default().plan(&selected);
+                    if let Some(name) = &name {
+                        freqm::names::name_channels(&mut plan, name, &radio.names);
+                    }
+                    let file = std::fs::File::create(&output)
+                        .map_err(|e| FreqmError::from(e).with_file(&output))?;
+                    radio.format.list_format().write_plan(std::io::BufWriter::new(file), &plan)?;
+                },
+                None => {
+                    println!("route: {} points, {}", route.points().len(), route.length().in_unit(corridor.unit()));
+                    for (n, f) in found.iter().enumerate() {
+                        println!("{:>4} {:>8} {:>7} off  {}", n + 1, f.along.to_string(), f.off.to_string(), describe(f.item));
+                    }
+                },
+            }
+        },
+        FreqmCmd::Image { command: ImageCmd::Dump { file, source, view } } => {
+            let mem = source.load(&file)?;
+            let annotations = view.annotations()?;
+            freqm::hexdump::dump(std::io::stdout().lock(), &mem, &view.options(annotations.as_ref()))?;
+        },
+        FreqmCmd::Image { command: ImageCmd::Diff { old, new, source, view } } => {
+            let old = source.load(&old)?;
+            let new = source.load(&new)?;
+            let annotations = view.annotations()?;
+            let changed = freqm::hexdump::diff(std::io::stdout().lock(), &old, &new, &view.options(annotations.as_ref()))?;
+            println!("{} bytes differ", changed);
+        },
+        FreqmCmd::Image { command: ImageCmd::Decode { file, source, map, paths } } => {
+            let mem = source.load(&file)?;
+            let map = MemoryMap::load(map)?;
+            for (path, value) in map.decode(&mem) {
+                if paths.is_empty() || paths.iter().any(|p| path.starts_with(p.as_str())) {
+                    println!("{} = {}", path, value);
+                }
+            }
+        },
+        FreqmCmd::Image { command: ImageCmd::Set { file, source, map, output, assignments } } => {
+            let input = source.format(&file)?;
+            let (mut mem, metadata) = freqm::image::load_with_metadata(&file, input)?;
+            let map = MemoryMap::load(map)?;
+            for a in &assignments {
+                let (path, value) = match a.split_once('=') {
+                    Some(pv) => pv,
+                    None => return Err(format!("{:?} is not <path>=<value>", a).into()),
+                };
+                map.set(&mut mem, path.trim(), value)?;
+            }
+
+            // --format is the input's, the output's comes from its own extension
+            let format = match ImageFormat::from_path(&output) {
+                Some(ImageFormat::Raw { .. }) => ImageFormat::Raw { base: source.base.unwrap_or(0) },
+                Some(f) => f,
+                None => input,
+            };
+            freqm::image::save_with_metadata(&mem, metadata.as_ref(), &output, format)?;
         },
         FreqmCmd::Models { } => {
-            todo!("list-models");
+            for m in freqm::models::MODELS {
+                let modes: Vec<String> = m.modes.iter().map(|m| format!("{:?}", m)).collect();
+                println!(
+                    "{:<8} {:<32} {:>5} channels  {:>2} char names  {}",
+                    m.id,
+                    m.name,
+                    m.channels,
+                    m.names.len,
+                    modes.join("/")
+                );
+            }
         }
     }
 
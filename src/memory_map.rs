@@ -0,0 +1,625 @@
+//! Declarative memory maps for decoding and encoding radio images
+//!
+//! A map is a TOML document describing where each setting lives in a radio's memory, so a new
+//! radio can be supported by writing a map rather than a decoder:
+//!
+//! ```toml
+//! name = "Example HT"
+//!
+//! [enums.power]
+//! low = 0
+//! high = 1
+//!
+//! [structs.channel]
+//! size = 32
+//! field = [
+//!     { name = "rx_freq", at = 0, type = "bcd", bytes = 4, decimals = 5 },
+//!     { name = "power", at = 8, type = "uint", bit = 2, width = 1, enum = "power" },
+//!     { name = "name", at = 16, type = "str", len = 8, pad = 0xff },
+//! ]
+//!
+//! [[field]]
+//! name = "channels"
+//! at = 0x2000
+//! type = "struct"
+//! struct = "channel"
+//! count = 200
+//! ```
+//!
+//! Field types:
+//!
+//!  - `uint`, `int`: `bytes` (default 1) wide integers, with `endian` "little" (default) or
+//!    "big". A `uint` may be a bitfield (`bit`, `width`) and may name one of the `enums`.
+//!  - `bcd`: packed BCD, two digits per byte with the high nibble first. `decimals` places a
+//!    decimal point, and a field where every byte is `blank` decodes as empty.
+//!  - `str`: `len` bytes of text padded with `pad` (default 0). `encoding` is "ascii" (default),
+//!    "utf16le", or "charset", where byte N is the Nth character of `charset`.
+//!  - `struct`: an instance of one of the `structs`
+//!
+//! Any field can be an array with `count`, and `stride` if the elements aren't packed. `at` is
+//! relative to the enclosing struct, or absolute for top level fields. Values are addressed by
+//! paths like "channels[3].rx_freq".
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use serde::Deserialize;
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::hexdump::{Annotations, Region};
+use crate::image::parse_addr;
+use crate::sparse_mem::SparseMem;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MemoryMap {
+    #[serde(default)]
+    pub name: String,
+
+    /// enum name => variant name => value
+    #[serde(default)]
+    pub enums: BTreeMap<String, BTreeMap<String, u64>>,
+
+    #[serde(default)]
+    pub structs: BTreeMap<String, StructDef>,
+
+    #[serde(default, rename = "field")]
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StructDef {
+    /// Defaults to the end of the last field
+    pub size: Option<u64>,
+
+    #[serde(rename = "field")]
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Field {
+    pub name: String,
+
+    /// Offset within the enclosing struct, or the address of a top level field
+    pub at: u64,
+
+    #[serde(flatten)]
+    pub kind: Kind,
+
+    /// Number of elements, if this is an array
+    pub count: Option<u64>,
+
+    /// Distance between array elements. Defaults to the element size.
+    pub stride: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    #[default]
+    Ascii,
+    Utf16le,
+    Charset,
+}
+
+fn one() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Kind {
+    Uint {
+        #[serde(default = "one")]
+        bytes: u8,
+        #[serde(default)]
+        endian: Endian,
+        /// lowest bit of a bitfield
+        bit: Option<u8>,
+        /// bits in a bitfield. Defaults to the rest of the integer.
+        width: Option<u8>,
+        #[serde(rename = "enum")]
+        enumeration: Option<String>,
+    },
+    Int {
+        #[serde(default = "one")]
+        bytes: u8,
+        #[serde(default)]
+        endian: Endian,
+    },
+    Bcd {
+        bytes: u8,
+        #[serde(default)]
+        endian: Endian,
+        #[serde(default)]
+        decimals: u32,
+        blank: Option<u8>,
+    },
+    Str {
+        len: u64,
+        #[serde(default)]
+        encoding: Encoding,
+        charset: Option<String>,
+        #[serde(default)]
+        pad: u8,
+    },
+    Struct {
+        #[serde(rename = "struct")]
+        name: String,
+    },
+}
+
+impl Kind {
+    /// (lowest bit, width) of the part of an integer this field uses
+    fn bits(&self) -> (u32, u32) {
+        match *self {
+            Kind::Uint { bytes, bit, width, .. } => {
+                let bit = bit.unwrap_or(0) as u32;
+                (bit, width.map(u32::from).unwrap_or((bytes as u32 * 8).saturating_sub(bit)))
+            }
+            Kind::Int { bytes, .. } => (0, bytes as u32 * 8),
+            _ => (0, 0),
+        }
+    }
+}
+
+/// A decoded value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Uint(u64),
+    Int(i64),
+    /// BCD, with the decimal point placed
+    Decimal(decimal::d128),
+    /// A `uint` with an enum, when the value is one of the enum's variants
+    Enum(String),
+    Str(String),
+    /// Every byte was the field's `blank` value
+    Blank,
+    /// The bytes don't decode (BCD digits above 9)
+    Invalid(Vec<u8>),
+    /// Some of the bytes aren't populated in the image
+    Missing,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Uint(v) => write!(f, "{}", v),
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Decimal(v) => write!(f, "{}", v),
+            Value::Enum(v) => write!(f, "{}", v),
+            Value::Str(v) => write!(f, "{:?}", v),
+            Value::Blank => write!(f, "-"),
+            Value::Invalid(b) => write!(f, "invalid {:02x?}", b),
+            Value::Missing => write!(f, "?"),
+        }
+    }
+}
+
+/// A single value in the map: a field that isn't a struct, with arrays expanded
+#[derive(Debug, Clone)]
+pub struct Leaf<'a> {
+    pub path: String,
+    pub addr: u64,
+    pub kind: &'a Kind,
+}
+
+impl Leaf<'_> {
+    pub fn range(&self) -> Range<u64> {
+        let len = match *self.kind {
+            Kind::Uint { bytes, .. } | Kind::Int { bytes, .. } | Kind::Bcd { bytes, .. } => bytes as u64,
+            Kind::Str { len, .. } => len,
+            Kind::Struct { .. } => 0,
+        };
+        self.addr..self.addr + len
+    }
+}
+
+fn definition(name: &str, reason: impl Into<String>) -> MapError {
+    MapError::Definition { name: name.to_owned(), reason: reason.into() }
+}
+
+fn to_uint(bytes: &[u8], endian: Endian) -> u64 {
+    let fold = |a: u64, &b: &u8| (a << 8) | b as u64;
+    match endian {
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+        Endian::Big => bytes.iter().fold(0, fold),
+    }
+}
+
+fn from_uint(v: u64, len: usize, endian: Endian) -> Vec<u8> {
+    let le = &v.to_le_bytes()[..len];
+    match endian {
+        Endian::Little => le.to_vec(),
+        Endian::Big => le.iter().rev().copied().collect(),
+    }
+}
+
+fn mask(width: u32) -> u64 {
+    if width >= 64 { u64::MAX } else { (1 << width) - 1 }
+}
+
+/// BCD bytes to a string of digits, most significant first
+fn bcd_digits(bytes: &[u8], endian: Endian) -> Option<String> {
+    let mut ordered = bytes.to_vec();
+    if endian == Endian::Little {
+        ordered.reverse();
+    }
+
+    let mut digits = String::new();
+    for b in ordered {
+        for nibble in [b >> 4, b & 0xf] {
+            digits.push(char::from_digit(nibble as u32, 10)?);
+        }
+    }
+    Some(digits)
+}
+
+/// Insert a decimal point `decimals` digits from the right, trimming leading zeros
+fn place_point(digits: &str, decimals: u32) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (int, frac) = digits.split_at(digits.len() - decimals);
+    let int = match int.trim_start_matches('0') {
+        "" => "0",
+        i => i,
+    };
+
+    if frac.is_empty() {
+        int.to_owned()
+    } else {
+        format!("{}.{}", int, frac)
+    }
+}
+
+/// The inverse of `place_point`: "146.52" with 5 decimals is "14652000"
+fn scale_digits(text: &str, decimals: u32) -> Option<String> {
+    let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (int.is_empty() && frac.is_empty()) || !all_digits(int) || !all_digits(frac) {
+        return None;
+    }
+
+    let decimals = decimals as usize;
+    if frac.len() > decimals && !frac[decimals..].bytes().all(|b| b == b'0') {
+        return None;
+    }
+
+    let frac = &frac[..frac.len().min(decimals)];
+    Some(format!("{}{:0<width$}", int, frac, width = decimals))
+}
+
+impl MemoryMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FreqmError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        Ok(text.parse()?)
+    }
+
+    fn check_fields(&self, owner: &str, fields: &[Field], stack: &mut Vec<String>) -> Result<(), MapError> {
+        for f in fields {
+            let name = if owner.is_empty() {
+                f.name.clone()
+            } else {
+                format!("{}.{}", owner, f.name)
+            };
+            match &f.kind {
+                Kind::Uint { bytes, enumeration, .. } => {
+                    if !(1..=8).contains(bytes) {
+                        return Err(definition(&name, "bytes must be 1 to 8"));
+                    }
+
+                    let (bit, width) = f.kind.bits();
+                    if width == 0 || bit + width > *bytes as u32 * 8 {
+                        return Err(definition(&name, "bitfield doesn't fit in its bytes"));
+                    }
+
+                    if let Some(e) = enumeration
+                        && !self.enums.contains_key(e)
+                    {
+                        return Err(definition(&name, format!("no enum {:?}", e)));
+                    }
+                }
+                Kind::Int { bytes, .. } | Kind::Bcd { bytes, .. } => {
+                    if !(1..=8).contains(bytes) {
+                        return Err(definition(&name, "bytes must be 1 to 8"));
+                    }
+                }
+                Kind::Str { len, encoding, charset, .. } => {
+                    if *encoding == Encoding::Charset && charset.is_none() {
+                        return Err(definition(&name, "charset encoding needs a charset"));
+                    }
+
+                    if *encoding == Encoding::Utf16le && len % 2 != 0 {
+                        return Err(definition(&name, "utf16le strings need an even len"));
+                    }
+                }
+                Kind::Struct { name: s } => {
+                    let def = self
+                        .structs
+                        .get(s)
+                        .ok_or_else(|| definition(&name, format!("no struct {:?}", s)))?;
+                    if stack.contains(s) {
+                        return Err(definition(&name, format!("struct {:?} contains itself", s)));
+                    }
+
+                    stack.push(s.clone());
+                    self.check_fields(s, &def.fields, stack)?;
+                    stack.pop();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn size_of(&self, kind: &Kind) -> u64 {
+        match kind {
+            Kind::Struct { name } => {
+                let def = &self.structs[name];
+                def.size.unwrap_or_else(|| {
+                    def.fields
+                        .iter()
+                        .map(|f| f.at + self.span_of(f))
+                        .max()
+                        .unwrap_or(0)
+                })
+            }
+            k => Leaf { path: String::new(), addr: 0, kind: k }.range().end,
+        }
+    }
+
+    fn stride_of(&self, f: &Field) -> u64 {
+        f.stride.unwrap_or_else(|| self.size_of(&f.kind))
+    }
+
+    /// Bytes covered by a field, including every array element
+    fn span_of(&self, f: &Field) -> u64 {
+        match f.count {
+            Some(0) => 0,
+            Some(n) => self.stride_of(f) * (n - 1) + self.size_of(&f.kind),
+            None => self.size_of(&f.kind),
+        }
+    }
+
+    fn expand<'a>(&'a self, fields: &'a [Field], base: u64, prefix: &str, out: &mut Vec<Leaf<'a>>) {
+        for f in fields {
+            let name = if prefix.is_empty() {
+                f.name.clone()
+            } else {
+                format!("{}.{}", prefix, f.name)
+            };
+
+            let elements: Vec<(String, u64)> = match f.count {
+                Some(n) => (0..n)
+                    .map(|i| (format!("{}[{}]", name, i), base + f.at + i * self.stride_of(f)))
+                    .collect(),
+                None => vec![(name, base + f.at)],
+            };
+
+            for (path, addr) in elements {
+                match &f.kind {
+                    Kind::Struct { name } => self.expand(&self.structs[name].fields, addr, &path, out),
+                    kind => out.push(Leaf { path, addr, kind }),
+                }
+            }
+        }
+    }
+
+    /// Every value in the map, in definition order
+    pub fn leaves(&self) -> Vec<Leaf<'_>> {
+        let mut out = Vec::new();
+        self.expand(&self.fields, 0, "", &mut out);
+        out
+    }
+
+    pub fn leaf(&self, path: &str) -> Option<Leaf<'_>> {
+        self.leaves().into_iter().find(|l| l.path == path)
+    }
+
+    /// Label every value, for hexdumps
+    pub fn annotations(&self) -> Annotations {
+        Annotations::new(
+            self.leaves()
+                .into_iter()
+                .map(|l| Region { range: l.range(), name: l.path })
+                .collect(),
+        )
+    }
+
+    fn decode_leaf(&self, mem: &SparseMem, leaf: &Leaf<'_>) -> Value {
+        let bytes = match mem.read(leaf.range()) {
+            Ok(b) => b,
+            Err(_) => return Value::Missing,
+        };
+
+        match leaf.kind {
+            Kind::Uint { endian, enumeration, .. } => {
+                let (bit, width) = leaf.kind.bits();
+                let v = (to_uint(bytes, *endian) >> bit) & mask(width);
+                let variant = enumeration
+                    .as_ref()
+                    .and_then(|e| self.enums[e].iter().find(|&(_, &x)| x == v));
+                match variant {
+                    Some((name, _)) => Value::Enum(name.clone()),
+                    None => Value::Uint(v),
+                }
+            }
+            Kind::Int { bytes: n, endian } => {
+                let shift = 64 - 8 * *n as u32;
+                Value::Int(((to_uint(bytes, *endian) << shift) as i64) >> shift)
+            }
+            Kind::Bcd { endian, decimals, blank, .. } => {
+                if let Some(blank) = blank
+                    && bytes.iter().all(|b| b == blank)
+                {
+                    return Value::Blank;
+                }
+
+                match bcd_digits(bytes, *endian) {
+                    // digits and a decimal point always parse
+                    Some(d) => Value::Decimal(place_point(&d, *decimals).parse().unwrap()),
+                    None => Value::Invalid(bytes.to_vec()),
+                }
+            }
+            Kind::Str { encoding, charset, pad, .. } => {
+                let mut end = bytes.len();
+                while end > 0 && bytes[end - 1] == *pad {
+                    end -= 1;
+                }
+                let bytes = &bytes[..end];
+
+                Value::Str(match encoding {
+                    Encoding::Ascii => bytes
+                        .iter()
+                        .map(|&b| if b.is_ascii() && !b.is_ascii_control() { b as char } else { '?' })
+                        .collect(),
+                    Encoding::Utf16le => {
+                        let units: Vec<u16> = bytes.chunks(2).map(|c| to_uint(c, Endian::Little) as u16).collect();
+                        String::from_utf16_lossy(&units)
+                    }
+                    Encoding::Charset => {
+                        let charset: Vec<char> = charset.as_deref().unwrap_or_default().chars().collect();
+                        bytes.iter().map(|&b| charset.get(b as usize).copied().unwrap_or('?')).collect()
+                    }
+                })
+            }
+            Kind::Struct { .. } => unreachable!("structs are expanded into leaves"),
+        }
+    }
+
+    /// Decode every value in the map
+    pub fn decode(&self, mem: &SparseMem) -> Vec<(String, Value)> {
+        self.leaves()
+            .into_iter()
+            .map(|l| {
+                let v = self.decode_leaf(mem, &l);
+                (l.path, v)
+            })
+            .collect()
+    }
+
+    pub fn get(&self, mem: &SparseMem, path: &str) -> Result<Value, MapError> {
+        let leaf = self
+            .leaf(path)
+            .ok_or_else(|| MapError::UnknownField { path: path.to_owned() })?;
+        Ok(self.decode_leaf(mem, &leaf))
+    }
+
+    /// Encode `text` into the value at `path`
+    ///
+    /// `text` is written the way `Value` displays: numbers (or "0x" hex for `uint`), enum variant
+    /// names, decimals for BCD ("-" for blank), and plain text for strings. Bitfields keep the
+    /// other bits of their bytes.
+    pub fn set(&self, mem: &mut SparseMem, path: &str, text: &str) -> Result<(), FreqmError> {
+        let leaf = self
+            .leaf(path)
+            .ok_or_else(|| MapError::UnknownField { path: path.to_owned() })?;
+        let bad = |reason| MapError::Value { path: path.to_owned(), value: text.to_owned(), reason };
+
+        let range = leaf.range();
+        let len = (range.end - range.start) as usize;
+        let old = mem.read_filled(range.clone(), 0);
+        let bytes = match leaf.kind {
+            Kind::Uint { endian, enumeration, .. } => {
+                let variant = enumeration.as_ref().and_then(|e| self.enums[e].get(text));
+                let v = match variant {
+                    Some(&v) => v,
+                    None => parse_addr(text).map_err(|_| bad("is not a number or enum variant"))?,
+                };
+
+                let (bit, width) = leaf.kind.bits();
+                if v & !mask(width) != 0 {
+                    return Err(bad("is too large").into());
+                }
+
+                let m = mask(width) << bit;
+                from_uint((to_uint(&old, *endian) & !m) | (v << bit), len, *endian)
+            }
+            Kind::Int { endian, .. } => {
+                let v: i64 = text.parse().map_err(|_| bad("is not a number"))?;
+                let bits = 8 * len as u32;
+                if bits < 64 && (v < -(1 << (bits - 1)) || v >= 1 << (bits - 1)) {
+                    return Err(bad("is out of range").into());
+                }
+                from_uint(v as u64 & mask(bits), len, *endian)
+            }
+            Kind::Bcd { endian, decimals, blank, .. } => {
+                if text.is_empty() || text == "-" {
+                    vec![blank.ok_or_else(|| bad("can't be blank"))?; len]
+                } else {
+                    let digits = scale_digits(text, *decimals).ok_or_else(|| bad("is not a decimal with that precision"))?;
+                    let digits = digits.trim_start_matches('0');
+                    if digits.len() > len * 2 {
+                        return Err(bad("is too large").into());
+                    }
+
+                    let digits = format!("{:0>width$}", digits, width = len * 2);
+                    let mut packed: Vec<u8> = digits
+                        .as_bytes()
+                        .chunks(2)
+                        .map(|d| ((d[0] - b'0') << 4) | (d[1] - b'0'))
+                        .collect();
+                    if *endian == Endian::Little {
+                        packed.reverse();
+                    }
+                    packed
+                }
+            }
+            Kind::Str { encoding, charset, pad, .. } => {
+                let mut encoded = Vec::new();
+                match encoding {
+                    Encoding::Ascii => {
+                        if !text.is_ascii() {
+                            return Err(bad("is not ascii").into());
+                        }
+                        encoded.extend_from_slice(text.as_bytes());
+                    }
+                    Encoding::Utf16le => {
+                        encoded.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+                    }
+                    Encoding::Charset => {
+                        let charset: Vec<char> = charset.as_deref().unwrap_or_default().chars().collect();
+                        for c in text.chars() {
+                            let i = charset
+                                .iter()
+                                .position(|&x| x == c)
+                                .ok_or_else(|| bad("has characters not in the charset"))?;
+                            encoded.push(i as u8);
+                        }
+                    }
+                }
+
+                if encoded.len() > len {
+                    return Err(bad("is too long").into());
+                }
+                encoded.resize(len, *pad);
+                encoded
+            }
+            Kind::Struct { .. } => unreachable!("structs are expanded into leaves"),
+        };
+
+        mem.write(range.start, &bytes)?;
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for MemoryMap {
+    type Err = MapError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let map: MemoryMap = toml::from_str(s).context(TomlSnafu)?;
+        map.check_fields("", &map.fields, &mut Vec::new())?;
+        Ok(map)
+    }
+}
@@ -0,0 +1,115 @@
+//! Radio models we can build channel lists for, and how to write them
+
+use std::io::Write;
+
+use crate::error::*;
+use crate::formats::Format;
+use crate::layout::Layout;
+use crate::names::{Charset, NameRules};
+use crate::{Mode, Repeater};
+
+/// File format a model's channel list is loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelFormat {
+    /// `IRNAID51.csv` style repeater list (see `icom_id51a`)
+    IcomCsv,
+    /// Kenwood TH-D74 repeater list, tab separated (see `csv::KenwoodTh74aRow`)
+    KenwoodTsv,
+    /// Channels exported by the TYT CPS (see `csv::Uv390Row`)
+    Uv390Csv,
+    /// Channels exported by the AnyTone CPS (see `csv::At878Row`)
+    At878Csv,
+    /// Chirp's csv, for the radios Chirp programs (see `csv::ChirpRow`)
+    ChirpCsv,
+}
+
+impl ChannelFormat {
+    /// The channel list format in `formats::FORMATS`
+    pub fn list_format(self) -> &'static Format {
+        let id = match self {
+            ChannelFormat::IcomCsv => "icom",
+            ChannelFormat::KenwoodTsv => "kenwood",
+            ChannelFormat::Uv390Csv => "uv390",
+            ChannelFormat::At878Csv => "at878",
+            ChannelFormat::ChirpCsv => "chirp",
+        };
+        crate::formats::find(id).expect("every channel format is in FORMATS")
+    }
+
+    pub fn write<W: Write>(self, w: W, repeaters: &[&Repeater]) -> Result<(), FreqmError> {
+        self.list_format().write_plan(w, &Layout::default().plan(repeaters))
+    }
+}
+
+#[derive(Debug)]
+pub struct Model {
+    /// Short name used on the command line
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Entries in the list `format` loads
+    pub channels: usize,
+    pub modes: &'static [Mode],
+    pub format: ChannelFormat,
+    /// What the radio shows of a channel's name
+    pub names: NameRules,
+}
+
+/// The AnyTone HT (TERMN-8R, see `anytone_ht`) isn't here yet: there's no channel list format for
+/// it, and its channel memory and name length aren't in `maps/anytone_termn8r.toml` so far.
+pub const MODELS: &[Model] = &[
+    Model {
+        id: "id51a",
+        name: "Icom ID-51A (repeater list)",
+        channels: 2500,
+        modes: &[Mode::Fm, Mode::Nfm, Mode::DStar],
+        format: ChannelFormat::IcomCsv,
+        names: NameRules { len: 16, chars: Charset::Ascii },
+    },
+    Model {
+        id: "th-d74",
+        name: "Kenwood TH-D74 (repeater list)",
+        channels: 1500,
+        modes: &[Mode::Fm, Mode::Nfm, Mode::DStar],
+        format: ChannelFormat::KenwoodTsv,
+        names: NameRules { len: 16, chars: Charset::Ascii },
+    },
+    Model {
+        id: "uv390",
+        name: "TYT MD-UV390",
+        channels: 3000,
+        modes: &[Mode::Fm, Mode::Nfm, Mode::Dmr],
+        format: ChannelFormat::Uv390Csv,
+        names: NameRules { len: 16, chars: Charset::Ascii },
+    },
+    Model {
+        id: "at878",
+        name: "AnyTone AT-878UV II",
+        channels: 4000,
+        modes: &[Mode::Fm, Mode::Nfm, Mode::Dmr],
+        format: ChannelFormat::At878Csv,
+        names: NameRules { len: crate::csv::AT878_NAME_LEN, chars: Charset::Ascii },
+    },
+    Model {
+        id: "uv5r",
+        name: "Baofeng UV-5R (with Chirp)",
+        channels: 128,
+        modes: &[Mode::Fm, Mode::Nfm],
+        format: ChannelFormat::ChirpCsv,
+        names: NameRules { len: 7, chars: Charset::Upper },
+    },
+];
+
+impl Model {
+    /// Whether the model can use any of the repeater's modes
+    pub fn supports(&self, r: &Repeater) -> bool {
+        r.modes().iter().any(|m| self.modes.contains(m))
+    }
+}
+
+/// Look up a model by `id` (case insensitive)
+pub fn find(id: &str) -> Result<&'static Model, FreqmError> {
+    MODELS
+        .iter()
+        .find(|m| m.id.eq_ignore_ascii_case(id.trim()))
+        .ok_or_else(|| ModelSnafu { value: id }.build().column("model"))
+}
@@ -0,0 +1,260 @@
+//! Channel names made from templates, fitted to a radio's display
+//!
+//! A template is text with fields in braces, e.g. `{callsign} {town:4}`. The fields are
+//! `callsign`, `town`, `state`, `county`, `freq` (output MHz), `band`, `mode` and `tone`. A width
+//! after a colon is the most characters the field takes: towns are abbreviated to fit (see
+//! `abbreviate()`), other fields are cut.
+//!
+//! The name is then fitted to the radio (`NameRules`): characters it can't show are dropped, and
+//! the name is cut to its length. Names repeated in a channel list get a number at the end.
+
+use std::collections::HashSet;
+
+use crate::error::*;
+use crate::layout::{GroupBy, Plan};
+use crate::{Band, Repeater};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Callsign,
+    Town,
+    State,
+    County,
+    Freq,
+    Band,
+    Mode,
+    Tone,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    /// With the most characters it takes
+    Field(Field, Option<usize>),
+}
+
+/// A parsed name template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl std::str::FromStr for Template {
+    type Err = FreqmError;
+
+    /// "{{" and "}}" are literal braces
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = |reason: String| NameTemplateSnafu { reason }.build().column("name");
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = s;
+
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                text.push(c);
+                rest = &rest[2..];
+            } else if c == '}' {
+                return Err(bad("\"}\" without \"{\"".to_owned()));
+            } else if c == '{' {
+                let end = rest.find('}').ok_or_else(|| bad("\"{\" without \"}\"".to_owned()))?;
+                let (name, width) = match rest[1..end].split_once(':') {
+                    Some((name, width)) => {
+                        let width = width.trim().parse().ok().filter(|&w| w > 0);
+                        (name, Some(width.ok_or_else(|| bad(format!("width in {:?} is not a number", &rest[..=end])))?))
+                    },
+                    None => (&rest[1..end], None),
+                };
+                let field = match name.trim().to_ascii_lowercase().as_str() {
+                    "callsign" => Field::Callsign,
+                    "town" => Field::Town,
+                    "state" => Field::State,
+                    "county" => Field::County,
+                    "freq" => Field::Freq,
+                    "band" => Field::Band,
+                    "mode" => Field::Mode,
+                    "tone" => Field::Tone,
+                    _ => return Err(bad(format!("unknown field {:?}", name))),
+                };
+
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Field(field, width));
+                rest = &rest[end + 1..];
+            } else {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+}
+
+impl Template {
+    /// `r`'s name, before fitting it to a radio. Fields `r` doesn't have are left empty, and the
+    /// spaces around them are collapsed.
+    pub fn name(&self, r: &Repeater) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(t) => name.push_str(t),
+                Part::Field(field, width) => {
+                    let value = match field {
+                        Field::Callsign => r.callsign().to_owned(),
+                        Field::Town => {
+                            let town = r.place().map(|p| p.town.as_str()).or(r.site().map(|s| s.name()));
+                            town.unwrap_or_default().to_owned()
+                        },
+                        Field::State => r.place().map(|p| p.state.clone()).unwrap_or_default(),
+                        Field::County => r.place().map(|p| p.county.clone()).unwrap_or_default(),
+                        Field::Freq => r.output_freq().to_string(),
+                        Field::Band => Band::of(r.output_freq()).map(|b| b.to_string()).unwrap_or_default(),
+                        Field::Mode => GroupBy::Mode.group(r),
+                        Field::Tone => r.code_in().map(|c| c.to_string()).unwrap_or_default(),
+                    };
+                    name.push_str(&match (field, width) {
+                        (Field::Town, Some(w)) => abbreviate(&value, *w),
+                        (_, Some(w)) => cut(&value, *w),
+                        (_, None) => value,
+                    });
+                },
+            }
+        }
+        name.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// The first `len` characters of `s`
+fn cut(s: &str, len: usize) -> String {
+    s.chars().take(len).collect()
+}
+
+/// Words in town names with a usual abbreviation
+const TOWN_WORDS: [(&str, &str); 18] = [
+    ("North", "N"),
+    ("South", "S"),
+    ("East", "E"),
+    ("West", "W"),
+    ("Mount", "Mt"),
+    ("Saint", "St"),
+    ("Fort", "Ft"),
+    ("Port", "Pt"),
+    ("Center", "Ctr"),
+    ("Centre", "Ctr"),
+    ("Village", "Vlg"),
+    ("Falls", "Fls"),
+    ("Junction", "Jct"),
+    ("Springs", "Spgs"),
+    ("Heights", "Hts"),
+    ("Harbor", "Hbr"),
+    ("Lake", "Lk"),
+    ("Beach", "Bch"),
+];
+
+/// A town name in at most `len` characters. Each step is only taken if the name doesn't fit yet:
+///
+///  1. words with a usual abbreviation are abbreviated ("North Andover" is "N Andover")
+///  2. spaces are removed
+///  3. vowels are removed, except at the start of a word ("Boston" is "Bstn")
+///  4. the end is cut off
+pub fn abbreviate(town: &str, len: usize) -> String {
+    let fits = |s: &str| s.chars().count() <= len;
+    let town = town.split_whitespace().collect::<Vec<_>>().join(" ");
+    if fits(&town) {
+        return town;
+    }
+
+    let words: Vec<&str> = town
+        .split(' ')
+        .map(|w| {
+            let bare = w.trim_end_matches('.');
+            TOWN_WORDS.iter().find(|(word, _)| word.eq_ignore_ascii_case(bare)).map_or(w, |(_, abbr)| *abbr)
+        })
+        .collect();
+    let short = words.join(" ");
+    if fits(&short) {
+        return short;
+    }
+
+    let joined = words.concat();
+    if fits(&joined) {
+        return joined;
+    }
+
+    let consonants: String = words
+        .iter()
+        .flat_map(|w| {
+            let mut chars = w.chars();
+            chars.next().into_iter().chain(chars.filter(|c| !"aeiouAEIOU".contains(*c)))
+        })
+        .collect();
+    cut(&consonants, len)
+}
+
+/// Characters a radio can show in channel names
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Printable ASCII
+    Ascii,
+    /// Upper case letters, digits, space and "-/+*_."; lower case letters are made upper case
+    Upper,
+}
+
+impl Charset {
+    fn allows(self, c: char) -> bool {
+        match self {
+            Charset::Ascii => c == ' ' || c.is_ascii_graphic(),
+            Charset::Upper => c.is_ascii_uppercase() || c.is_ascii_digit() || " -/+*_.".contains(c),
+        }
+    }
+}
+
+/// What a radio's channel names can hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameRules {
+    /// Most characters
+    pub len: usize,
+    pub chars: Charset,
+}
+
+impl NameRules {
+    /// Any printable ASCII, of any length
+    pub const UNLIMITED: NameRules = NameRules { len: usize::MAX, chars: Charset::Ascii };
+
+    /// `name` with the characters the radio can't show dropped, cut to its length
+    pub fn fit(&self, name: &str) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| if self.chars == Charset::Upper { c.to_ascii_uppercase() } else { c })
+            .filter(|&c| self.chars.allows(c))
+            .collect();
+        cut(name.split_whitespace().collect::<Vec<_>>().join(" ").as_str(), self.len).trim_end().to_owned()
+    }
+}
+
+/// Name each channel of `plan` with `template`, fitted to `rules`. A name already used in the plan
+/// gets the lowest number that makes it unique, after a space if there's room.
+pub fn name_channels(plan: &mut Plan<'_>, template: &Template, rules: &NameRules) {
+    let mut used = HashSet::new();
+    for c in &mut plan.channels {
+        let base = rules.fit(&template.name(c.repeater));
+        let mut name = base.clone();
+        let mut n = 2;
+        while used.contains(&name) {
+            let suffix = n.to_string();
+            let room = rules.len.saturating_sub(suffix.len());
+            name = if base.chars().count() < room {
+                format!("{} {}", base, suffix)
+            } else {
+                format!("{}{}", cut(&base, room).trim_end(), suffix)
+            };
+            n += 1;
+        }
+        used.insert(name.clone());
+        c.name = Some(name);
+    }
+}
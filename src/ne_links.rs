@@ -0,0 +1,303 @@
+//! Graph of linked systems and remote receivers, built from the NE repeater listing comments
+//!
+//! Nodes are the individual listing entries. Edges come from the `links_and_comments` field:
+//!
+//!  - "<freq> (<town>, <state>)": a link to the repeater on `freq` in `town`
+//!  - "Remote RX for <callsign> <town>, <state>": this entry is a receiver feeding `callsign`
+//!
+//! Nets ("MMRA Net") are kept as named groups of nodes rather than edges. Networks are the
+//! connected components formed by edges and shared nets.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::ne_repeater::*;
+
+/// A single entry in the listing
+#[derive(Debug, Clone)]
+pub struct LinkNode {
+    pub callsign: String,
+    pub output_freq: Option<decimal::d128>,
+    pub town: String,
+    pub state: String,
+}
+
+impl fmt::Display for LinkNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.callsign)?;
+        if let Some(freq) = self.output_freq {
+            write!(f, " {}", freq)?;
+        }
+        write!(f, " {}, {}", self.town, self.state)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// `from` lists `to` as a linked repeater
+    Link { bracketed: bool },
+
+    /// `from` is a receiver feeding `to`
+    RemoteRx,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkEdge {
+    /// index into `LinkGraph::nodes()`
+    pub from: usize,
+    /// index into `LinkGraph::nodes()`
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A link or remote receiver we couldn't match to any entry in the listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unresolved {
+    /// index into `LinkGraph::nodes()` of the entry that mentioned it
+    pub from: usize,
+    /// the text from the comments field
+    pub reference: String,
+}
+
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    nodes: Vec<LinkNode>,
+    edges: Vec<LinkEdge>,
+    nets: BTreeMap<String, Vec<usize>>,
+    unresolved: Vec<Unresolved>,
+}
+
+fn same_town(node: &LinkNode, town: &NeTown) -> bool {
+    node.town.eq_ignore_ascii_case(&town.town)
+        && town.state.as_ref().is_none_or(|s| node.state.eq_ignore_ascii_case(s))
+}
+
+impl LinkGraph {
+    /// Build the graph from a full listing
+    ///
+    /// Entries with comments that don't parse are kept as nodes without any edges.
+    pub fn new(records: &[NeRepeaterRecord]) -> Self {
+        let mut g = LinkGraph {
+            nodes: records
+                .iter()
+                .map(|r| LinkNode {
+                    callsign: r.callsign.trim().to_owned(),
+                    output_freq: parse_mhz(&r.output_freq),
+                    town: r.location_town.trim().to_owned(),
+                    state: r.location_state.trim().to_owned(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        for (from, r) in records.iter().enumerate() {
+            let comments = match r.comments() {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+
+            for link in comments.links {
+                let to = g.find(from, |n| {
+                    n.output_freq == Some(link.freq)
+                        && (link.locations.is_empty() || link.locations.iter().any(|t| same_town(n, t)))
+                });
+
+                g.add_edges(from, to, EdgeKind::Link { bracketed: link.bracketed }, || {
+                    let mut reference = link.freq.to_string();
+                    for t in &link.locations {
+                        reference.push(' ');
+                        reference.push_str(&t.town);
+                    }
+                    reference
+                });
+            }
+
+            for rrx in comments.remote_rx {
+                let mut to = g.find(from, |n| {
+                    n.callsign.eq_ignore_ascii_case(&rrx.callsign) && same_town(n, &rrx.location)
+                });
+                if to.is_empty() {
+                    // the town in the comment doesn't always match the listed town exactly
+                    to = g.find(from, |n| n.callsign.eq_ignore_ascii_case(&rrx.callsign));
+                }
+
+                g.add_edges(from, to, EdgeKind::RemoteRx, || {
+                    format!("Remote RX for {} {}", rrx.callsign, rrx.location.town)
+                });
+            }
+
+            for net in comments.nets {
+                g.nets.entry(net.name).or_default().push(from);
+            }
+        }
+
+        g
+    }
+
+    fn find<F: Fn(&LinkNode) -> bool>(&self, from: usize, f: F) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(i, n)| *i != from && f(n))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn add_edges<F: FnOnce() -> String>(&mut self, from: usize, to: Vec<usize>, kind: EdgeKind, reference: F) {
+        if to.is_empty() {
+            self.unresolved.push(Unresolved { from, reference: reference() });
+        }
+
+        for to in to {
+            self.edges.push(LinkEdge { from, to, kind });
+        }
+    }
+
+    pub fn nodes(&self) -> &[LinkNode] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[LinkEdge] {
+        &self.edges
+    }
+
+    /// Net name => indexes of the nodes that participate in it
+    pub fn nets(&self) -> &BTreeMap<String, Vec<usize>> {
+        &self.nets
+    }
+
+    pub fn unresolved(&self) -> &[Unresolved] {
+        &self.unresolved
+    }
+
+    /// Indexes of nodes with the given callsign
+    pub fn find_callsign(&self, callsign: &str) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.callsign.eq_ignore_ascii_case(callsign))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Edges that start or end at `node`
+    pub fn edges_of(&self, node: usize) -> impl Iterator<Item = &LinkEdge> {
+        self.edges.iter().filter(move |e| e.from == node || e.to == node)
+    }
+
+    /// Names of the nets `node` participates in
+    pub fn nets_of(&self, node: usize) -> impl Iterator<Item = &str> {
+        self.nets
+            .iter()
+            .filter(move |(_, members)| members.contains(&node))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Group nodes into networks: sets of nodes connected by edges or by a shared net
+    ///
+    /// Nodes without any links are omitted. Each network is sorted, and networks are ordered by
+    /// their first node.
+    pub fn networks(&self) -> Vec<Vec<usize>> {
+        let mut parent: Vec<usize> = (0..self.nodes.len()).collect();
+
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        fn union(parent: &mut [usize], a: usize, b: usize) {
+            let a = root(parent, a);
+            let b = root(parent, b);
+            if a != b {
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+
+        for e in &self.edges {
+            union(&mut parent, e.from, e.to);
+        }
+
+        for members in self.nets.values() {
+            for w in members.windows(2) {
+                union(&mut parent, w[0], w[1]);
+            }
+        }
+
+        let mut linked = vec![false; self.nodes.len()];
+        for e in &self.edges {
+            linked[e.from] = true;
+            linked[e.to] = true;
+        }
+        for &m in self.nets.values().flatten() {
+            linked[m] = true;
+        }
+
+        let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for i in (0..self.nodes.len()).filter(|&i| linked[i]) {
+            let r = root(&mut parent, i);
+            groups.entry(r).or_default().push(i);
+        }
+
+        groups.into_values().collect()
+    }
+
+    /// The network containing `node`, or just `node` if it isn't linked to anything
+    pub fn network_of(&self, node: usize) -> Vec<usize> {
+        self.networks()
+            .into_iter()
+            .find(|n| n.contains(&node))
+            .unwrap_or_else(|| vec![node])
+    }
+
+    /// Render as a GraphViz `digraph`
+    ///
+    /// If `only` is given, just those nodes (and the edges & nets between them) are emitted.
+    pub fn write_dot<W: std::io::Write>(&self, mut w: W, only: Option<&[usize]>) -> std::io::Result<()> {
+        let included = |i: usize| only.is_none_or(|o| o.contains(&i));
+
+        writeln!(w, "digraph links {{")?;
+
+        for (i, n) in self.nodes.iter().enumerate().filter(|(i, _)| included(*i)) {
+            let freq = n.output_freq.map(|f| f.to_string()).unwrap_or_default();
+            writeln!(
+                w,
+                "    n{} [label=\"{}\\n{}\\n{}, {}\"];",
+                i,
+                dot_escape(&n.callsign),
+                freq,
+                dot_escape(&n.town),
+                dot_escape(&n.state)
+            )?;
+        }
+
+        for e in self.edges.iter().filter(|e| included(e.from) && included(e.to)) {
+            let attrs = match e.kind {
+                EdgeKind::Link { bracketed: false } => "dir=none",
+                EdgeKind::Link { bracketed: true } => "dir=none, style=dashed",
+                EdgeKind::RemoteRx => "style=dotted, label=\"RX\"",
+            };
+            writeln!(w, "    n{} -> n{} [{}];", e.from, e.to, attrs)?;
+        }
+
+        for (ni, (name, members)) in self.nets.iter().enumerate() {
+            let members: Vec<_> = members.iter().copied().filter(|&m| included(m)).collect();
+            if members.is_empty() {
+                continue;
+            }
+
+            writeln!(w, "    net{} [shape=box, label=\"{}\"];", ni, dot_escape(name))?;
+            for m in members {
+                writeln!(w, "    n{} -> net{} [dir=none, color=gray];", m, ni)?;
+            }
+        }
+
+        writeln!(w, "}}")
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -1,8 +1,9 @@
 use serde::{Serialize, Deserialize};
-use snafu::ensure;
+use snafu::{ensure, OptionExt, ResultExt};
 use super::*;
+use crate::error::*;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NeRepeaterRecord {
     pub output_freq: String,
 
@@ -27,6 +28,11 @@ pub struct NeRepeaterRecord {
     /// "NFM" = Analog 2.5kHz narrow FM
     ///
     /// often has trailing spaces
+    ///
+    /// Multi-mode repeaters concatenate the digital modes and add an analog mode after a '/':
+    /// "YSF/FM ", "P25YSFD-STARNXDNDMR/FM ", "DMR/NFM"
+    ///
+    /// See `NeRepeaterRecord::modes()`
     pub mode: String,
 
     pub callsign: String,
@@ -37,12 +43,16 @@ pub struct NeRepeaterRecord {
     ///
     pub code_out: String,
 
-    /// "Local", "OFF"
+    /// "Local", "OFF", "Limited RX", "Limited TX"
+    /// "" = operating normally
     pub status: String,
 
     pub location_county: String,
 
+    /// IRLP node number
     pub irlp: String,
+
+    /// EchoLink node number, sometimes followed by the node callsign: "4133/W1MRA-R"
     pub echo: String,
 
     /// common forms:
@@ -51,7 +61,8 @@ pub struct NeRepeaterRecord {
     ///  - fields may be bracketed
     ///    "'['
     ///  - "<freq> (<town>,<state>)": linked repeater
-    /// 
+    ///
+    /// See `LinksAndComments` for the parsed form.
     pub links_and_comments: String,
 
     /// timestamp := <year> "/" <month> "/" <date>
@@ -92,9 +103,438 @@ pub fn standard_offset_new_england(mhz: decimal::d128) -> Option<decimal::d128>
     }
 }
 
-impl std::convert::TryFrom<csv::StringRecord> for NeRepeaterRecord {
-    type Error = Box<dyn std::error::Error>;
-    fn try_from(s: csv::StringRecord) -> Result<Self, Self::Error> {
+impl NeRepeaterRecord {
+    /// The repeater's town, located approximately with `gazetteer`. `None` if the town isn't in it.
+    pub fn site(&self, gazetteer: &crate::gazetteer::Gazetteer) -> Option<Site> {
+        let (town, state) = (self.location_town.trim(), self.location_state.trim());
+        let pos = gazetteer.lookup(state, town)?;
+        Some(Site::new(town, format!("{}, {}", town, state), pos).approximate())
+    }
+
+    /// Parse `links_and_comments` into its structured form
+    pub fn comments(&self) -> Result<LinksAndComments, FreqmError> {
+        self.links_and_comments.parse()
+    }
+
+    pub fn modes(&self) -> Result<Vec<Mode>, FreqmError> {
+        let mode = self.mode.trim();
+        let invalid = || Err(ModeSnafu { value: self.mode.clone() }.build().column("mode"));
+
+        let (digital, analog) = match mode.split_once('/') {
+            Some((d, a)) => (d, Some(a)),
+            None if mode.is_empty() => ("", Some("FM")),
+            None if mode == "NFM" => ("", Some("NFM")),
+            None => (mode, None),
+        };
+
+        let mut modes = Vec::new();
+
+        // digital modes are concatenated without a separator
+        let mut rest = digital;
+        'outer: while !rest.is_empty() {
+            for (name, m) in NE_DIGITAL_MODES {
+                if let Some(r) = rest.strip_prefix(name) {
+                    modes.push(*m);
+                    rest = r;
+                    continue 'outer;
+                }
+            }
+
+            return invalid();
+        }
+
+        match analog {
+            Some("FM") => modes.push(Mode::Fm),
+            Some("NFM") => modes.push(Mode::Nfm),
+            Some(_) => return invalid(),
+            None => {}
+        }
+
+        Ok(modes)
+    }
+
+    pub fn status(&self) -> Result<Status, FreqmError> {
+        Ok(match self.status.trim() {
+            "" => Status::On,
+            "OFF" => Status::Off,
+            "Local" => Status::Local,
+            "Limited RX" => Status::LimitedRx,
+            "Limited TX" => Status::LimitedTx,
+            _ => return Err(StatusSnafu { value: self.status.clone() }.build().column("status")),
+        })
+    }
+
+    pub fn irlp_node(&self) -> Result<Option<u32>, FreqmError> {
+        parse_node(&self.irlp).map_err(|e| e.column("irlp"))
+    }
+
+    pub fn echolink_node(&self) -> Result<Option<u32>, FreqmError> {
+        // drop the trailing "/<callsign>", if any
+        let node = self.echo.split('/').next().unwrap();
+        parse_node(node).map_err(|_| NodeSnafu { value: self.echo.clone() }.build().column("echo"))
+    }
+
+    pub fn code_in(&self) -> Result<Option<Code>, FreqmError> {
+        self.code(&self.code_in, "code_in")
+    }
+
+    pub fn code_out(&self) -> Result<Option<Code>, FreqmError> {
+        self.code(&self.code_out, "code_out")
+    }
+
+    /// The module letter D-STAR repeaters list in their code fields ("B", "C/77.0")
+    pub fn dstar_module(&self) -> Result<Option<char>, FreqmError> {
+        if !self.modes()?.contains(&Mode::DStar) {
+            return Ok(None);
+        }
+
+        Ok([&self.code_in, &self.code_out]
+            .into_iter()
+            .flat_map(|c| c.split('/'))
+            .map(str::trim)
+            .find(|part| is_dstar_module(part))
+            .and_then(|module| module.chars().next()))
+    }
+
+    /// Bare numbers are color codes on DMR repeaters, NACs on P25 repeaters and CTCSS tones
+    /// otherwise
+    ///
+    /// Multi-mode repeaters list a code per mode, separated by '/' ("NAC293/RAN1/CC9/67.0"); the
+    /// first one we can represent is used. D-STAR module letters ("B") and NXDN RANs ("RAN1") aren't
+    /// codes, and a trailing '*' ("67.0*") is dropped.
+    fn code(&self, text: &str, column: &'static str) -> Result<Option<Code>, FreqmError> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+
+        let modes = self.modes()?;
+        let mut parts = text
+            .split('/')
+            .map(|part| part.trim().trim_end_matches('*').trim())
+            .filter(|part| !part.is_empty() && !is_dstar_module(part) && !is_nxdn_ran(part))
+            .peekable();
+        if parts.peek().is_none() {
+            return Ok(None);
+        }
+
+        parts
+            .find_map(|part| {
+                let bare = part.chars().all(|c| c.is_ascii_digit());
+                let part = if bare && modes.contains(&Mode::Dmr) {
+                    format!("CC{}", part)
+                } else if bare && modes.contains(&Mode::P25) {
+                    format!("NAC{}", part)
+                } else {
+                    part.to_owned()
+                };
+                part.parse().ok()
+            })
+            .map(Some)
+            .ok_or_else(|| CodeSnafu { value: text }.build().column(column))
+    }
+
+    /// Parse `update_timestamp`
+    pub fn updated(&self) -> Result<Option<chrono::NaiveDate>, FreqmError> {
+        match self.update_timestamp.as_deref().map(str::trim) {
+            None | Some("") => Ok(None),
+            Some(ts) => chrono::NaiveDate::parse_from_str(ts, "%Y/%m/%d")
+                .map(Some)
+                .map_err(|_| TimestampSnafu { value: ts.to_owned() }.build().column("update_timestamp")),
+        }
+    }
+}
+
+/// Names used for digital modes in the `mode` field
+const NE_DIGITAL_MODES: &[(&str, Mode)] = &[
+    ("D-STAR", Mode::DStar),
+    ("DMR", Mode::Dmr),
+    ("YSF", Mode::Ysf),
+    ("NXDN", Mode::Nxdn),
+    ("P25", Mode::P25),
+];
+
+/// A D-STAR module letter, listed in the code fields of D-STAR repeaters
+fn is_dstar_module(s: &str) -> bool {
+    matches!(s, "A" | "B" | "C" | "D")
+}
+
+/// An NXDN radio access number ("RAN1"), listed in the code fields of NXDN repeaters
+fn is_nxdn_ran(s: &str) -> bool {
+    s.strip_prefix("RAN").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// IRLP and EchoLink node numbers, empty if the repeater doesn't have one
+fn parse_node(s: &str) -> Result<Option<u32>, ParseError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(None);
+    }
+
+    s.parse()
+        .map(Some)
+        .map_err(|_| NodeSnafu { value: s.to_owned() }.build())
+}
+
+/// Parse a frequency in MHz as it appears in the NE repeater listing (eg: "147.030")
+///
+/// `d128`'s `FromStr` never fails (it produces a NaN instead), so we check the shape ourselves.
+pub(crate) fn parse_mhz(s: &str) -> Option<decimal::d128> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+
+    let v: decimal::d128 = s.parse().ok()?;
+    if v.is_nan() {
+        None
+    } else {
+        Some(v)
+    }
+}
+
+/// `parse_mhz()` for a frequency among other words, where it must have a decimal point so bare
+/// numbers (eg: "2 meters") aren't taken for one
+fn parse_dotted_mhz(s: &str) -> Option<decimal::d128> {
+    parse_mhz(s).filter(|_| s.contains('.'))
+}
+
+/// A town named in the comments, with the state if it was given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeTown {
+    pub town: String,
+    pub state: Option<String>,
+}
+
+impl NeTown {
+    /// "<town>, <state>" or "<town>"
+    fn parse(s: &str) -> Self {
+        match s.rsplit_once(',') {
+            Some((town, state)) if is_state(state.trim()) => NeTown {
+                town: town.trim().to_owned(),
+                state: Some(state.trim().to_owned()),
+            },
+            _ => NeTown {
+                town: s.trim().to_owned(),
+                state: None,
+            },
+        }
+    }
+}
+
+/// A 2 letter state abbreviation, eg: "CT"
+fn is_state(s: &str) -> bool {
+    s.len() == 2 && s.chars().all(|c| c.is_ascii_uppercase())
+}
+
+/// A repeater this one is linked to
+///
+/// "<freq> (<town>, <state>)", "<freq> <town>", or a bare "<freq>"
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeLink {
+    pub freq: decimal::d128,
+
+    /// Usually one, but some systems list multiple transmitter sites:
+    /// "147.345 (Farmington, ME)  (Livermore Falls, ME)"
+    pub locations: Vec<NeTown>,
+
+    /// The entry was bracketed (`[...]`) in the listing
+    pub bracketed: bool,
+}
+
+/// A net or linked system this repeater participates in: "<name> Net"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeNet {
+    /// Full name, including the trailing "Net"/"Network"
+    pub name: String,
+
+    /// The entry was bracketed (`[...]`) in the listing
+    pub bracketed: bool,
+}
+
+/// This entry is a receiver feeding another repeater
+///
+/// "Remote RX for <callsign> <town>, <state>" and "Aux RX for <callsign> <town>, <state>"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeRemoteRx {
+    /// Callsign of the repeater fed by this receiver
+    pub callsign: String,
+
+    /// Location of the repeater fed by this receiver
+    pub location: NeTown,
+}
+
+/// Parsed form of `NeRepeaterRecord::links_and_comments`
+///
+/// grammar (roughly):
+///
+/// ```norust
+/// comments := [ field { ',' field } ]
+/// field := '[' item ']' | item
+/// item := custom-input | link | remote-rx | net | note
+/// custom-input := "*Input: " <freq> [ '(' <offset> ')' ]
+/// link := <freq> [ <town> | { '(' <town> ',' <state> ')' } ]
+/// remote-rx := ("Remote RX for " | "Aux RX for ") <callsign> <town> [ ',' <state> ]
+/// net := <name> (" Net" | " Network")
+/// note := anything else
+/// ```
+///
+/// Commas inside `()` and `[]` don't split fields. Because the remote receiver and unparenthesized
+/// link forms use a bare comma before the state, a field that is only a state abbreviation is
+/// joined back onto the town of the preceding item.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinksAndComments {
+    /// Non-standard input frequency, from "*Input: <freq>"
+    pub input_freq: Option<decimal::d128>,
+    pub links: Vec<NeLink>,
+    pub nets: Vec<NeNet>,
+    pub remote_rx: Vec<NeRemoteRx>,
+
+    /// Fields that didn't match any other form ("Closed", "AllStar Node 50532", "RB:2m/440", ...)
+    pub notes: Vec<String>,
+}
+
+/// Split on commas that are not nested inside `()` or `[]`
+fn split_fields(s: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                fields.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&s[start..]);
+    fields
+}
+
+/// Parse the remainder of a link after the frequency
+fn parse_link_locations(rest: &str) -> Option<Vec<NeTown>> {
+    let mut rest = rest.trim();
+    if rest.is_empty() || rest == "Simplex" {
+        return Some(Vec::new());
+    }
+
+    if !rest.starts_with('(') {
+        // "<freq> <town>": only accept something that looks like a town name
+        if rest.chars().all(|c| c.is_alphabetic() || c == ' ' || c == '.') {
+            return Some(vec![NeTown { town: rest.to_owned(), state: None }]);
+        }
+        return None;
+    }
+
+    let mut locations = Vec::new();
+    while let Some(r) = rest.strip_prefix('(') {
+        let (inner, after) = r.split_once(')')?;
+        locations.push(NeTown::parse(inner));
+        rest = after.trim_start();
+    }
+
+    if rest.is_empty() {
+        Some(locations)
+    } else {
+        None
+    }
+}
+
+impl std::str::FromStr for LinksAndComments {
+    type Err = FreqmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut r = LinksAndComments::default();
+        if s.trim().is_empty() {
+            return Ok(r);
+        }
+
+        let comment_parse = || CommentSnafu { value: s.to_owned() }.build().column("links_and_comments");
+
+        // which item the previous field produced, so a lone state can be joined back onto it
+        #[derive(PartialEq)]
+        enum Prev {
+            Link,
+            RemoteRx,
+            Other,
+        }
+        let mut prev = Prev::Other;
+
+        for field in split_fields(s) {
+            let field = field.trim();
+            if field.is_empty() {
+                continue;
+            }
+
+            if is_state(field) {
+                let town = match prev {
+                    Prev::Link => r.links.last_mut().and_then(|l| l.locations.last_mut()),
+                    Prev::RemoteRx => r.remote_rx.last_mut().map(|rrx| &mut rrx.location),
+                    Prev::Other => None,
+                };
+
+                if let Some(town) = town.filter(|t| t.state.is_none()) {
+                    town.state = Some(field.to_owned());
+                    prev = Prev::Other;
+                    continue;
+                }
+            }
+            prev = Prev::Other;
+
+            let (item, bracketed) = match field.strip_prefix('[').and_then(|f| f.strip_suffix(']')) {
+                Some(inner) => (inner.trim(), true),
+                None => (field, false),
+            };
+
+            if let Some(rest) = item.strip_prefix("*Input: ") {
+                let freq = rest.split(' ').next().and_then(parse_mhz).ok_or_else(comment_parse)?;
+                if r.input_freq.is_some() {
+                    return Err(comment_parse());
+                }
+                r.input_freq = Some(freq);
+                continue;
+            }
+
+            let (first, rest) = item.split_once(' ').unwrap_or((item, ""));
+            // a linked repeater: "146.790 Weston" or "147.390 (Moultonborough, NH)"
+            if let Some(freq) = parse_dotted_mhz(first)
+                && let Some(locations) = parse_link_locations(rest)
+            {
+                r.links.push(NeLink { freq, locations, bracketed });
+                prev = Prev::Link;
+                continue;
+            }
+
+            let remote = item
+                .strip_prefix("Remote RX for ")
+                .or_else(|| item.strip_prefix("Aux RX for "));
+            if let Some(remote) = remote
+                && let Some((callsign, town)) = remote.split_once(' ')
+            {
+                r.remote_rx.push(NeRemoteRx {
+                    callsign: callsign.to_owned(),
+                    location: NeTown::parse(town),
+                });
+                prev = Prev::RemoteRx;
+                continue;
+            }
+
+            if item.ends_with(" Net") || item.ends_with(" Network") {
+                r.nets.push(NeNet { name: item.to_owned(), bracketed });
+                continue;
+            }
+
+            r.notes.push(field.to_owned());
+        }
+
+        Ok(r)
+    }
+}
+
+impl std::convert::TryFrom<::csv::StringRecord> for NeRepeaterRecord {
+    type Error = FreqmError;
+    fn try_from(s: ::csv::StringRecord) -> Result<Self, Self::Error> {
         // variations:
         //  - update time stamp omitted
         //  - trailing comma omitted
@@ -103,7 +543,7 @@ impl std::convert::TryFrom<csv::StringRecord> for NeRepeaterRecord {
         // field
         ensure!(
             s.len() == 13 || s.len() == 14 || s.len() == 15,
-            FieldMissing { field_num: s.len()}
+            FieldCountSnafu { found: s.len(), expected: "13 to 15" }
         );
 
         Ok(Self {
@@ -128,50 +568,180 @@ impl std::convert::TryFrom<csv::StringRecord> for NeRepeaterRecord {
     }
 }
 
+/// Read all the records from a NE repeater listing
+///
+/// Each row is also checked for conversion to a `Repeater`, so rows that would fail later are
+/// reported here.
+pub fn import<R: std::io::Read>(
+    reader: R,
+    on_error: crate::import::OnError,
+) -> Result<(Vec<NeRepeaterRecord>, crate::import::ImportReport), FreqmError> {
+    let mut csv = ::csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+
+    crate::import::import(&mut csv, on_error, |r, warnings| {
+        let record = NeRepeaterRecord::try_from(r)?;
+        let repeater = Repeater::try_from(record.clone())?;
+
+        if record.input_offset_dir == "*" && repeater.input_freq.is_none() {
+            warnings.push(
+                CustomSplitUnknownSnafu { comment: record.links_and_comments.clone() }
+                    .build()
+                    .column("links_and_comments"),
+            );
+        }
+
+        Ok(record)
+    })
+}
+
+/// Read all the records from a NE repeater listing csv file
+pub fn read_csv<P: AsRef<std::path::Path>>(
+    path: P,
+    on_error: crate::import::OnError,
+) -> Result<(Vec<NeRepeaterRecord>, crate::import::ImportReport), FreqmError> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+
+    let (records, mut report) = import(std::io::BufReader::new(file), on_error).map_err(|e| e.with_file(path))?;
+    report.set_file(path);
+    Ok((records, report))
+}
+
 impl std::convert::TryFrom<NeRepeaterRecord> for Repeater {
     type Error = FreqmError;
     fn try_from(nerr: NeRepeaterRecord) -> Result<Self, Self::Error> {
-        let output_freq: decimal::d128 = nerr.output_freq.parse()
-            .map_err(|_| FreqmError::OutputFreqParseFailure { output_freq: nerr.output_freq.clone() })?;
+        let output_freq = parse_mhz(&nerr.output_freq)
+            .context(FrequencySnafu { value: nerr.output_freq.clone() })
+            .map_err(|e| e.column("output_freq"))?;
+        let not_in_band = || FreqNotInAnyBandSnafu { freq: output_freq }.build().column("output_freq");
 
         let input_freq = match &nerr.input_offset_dir[..] {
             "+" => {
                 let shift = standard_offset_new_england(output_freq)
-                    .ok_or(FreqmError::FreqNotInAnyBand { freq: output_freq })?;
+                    .ok_or_else(not_in_band)?;
                 Some(output_freq + shift)
             },
             "-" => {
                 let shift = standard_offset_new_england(output_freq)
-                    .ok_or(FreqmError::FreqNotInAnyBand { freq: output_freq })?;
+                    .ok_or_else(not_in_band)?;
                 Some(output_freq - shift)
             },
             "*" => {
-                // examine the `note` field
-                let prefix = "*Input: ";
-                if nerr.links_and_comments.starts_with(prefix) {
-                    // remove the prefix, take the first number as the frequency in MHz
-                    let s = &nerr.links_and_comments[prefix.len()..];
-                    let mut si = s.split(" ");
-                    let n = si.next().ok_or(FreqmError::CommentParse { comment: nerr.links_and_comments.clone() })?;
-
-                    Some(n.parse().map_err(|_| FreqmError::CommentParse { comment: nerr.links_and_comments.clone() })?)
-                } else {
-                    // some entries indicate a special split but
-                    None
-                }
+                // examine the `note` field for "*Input: <freq>"
+                //
+                // some entries indicate a special split but don't say what it is, leaving us
+                // with `None`
+                nerr.comments()?.input_freq
             },
             "S" => {
                 // simplex?
                 Some(output_freq)
             },
-            other => {
-                return InvalidOffsetKind { offset_kind: nerr.input_offset_dir.clone() }.fail()?;
+            _ => {
+                return Err(OffsetKindSnafu { value: nerr.input_offset_dir.clone() }.build().column("input_offset_dir"));
             }
         };
 
         Ok(Self {
+            callsign: nerr.callsign.trim().to_owned(),
             output_freq,
-            input_freq
+            input_freq,
+            modes: nerr.modes()?,
+            status: nerr.status()?,
+            irlp_node: nerr.irlp_node()?,
+            echolink_node: nerr.echolink_node()?,
+            updated: nerr.updated()?,
+            code_in: nerr.code_in()?,
+            code_out: nerr.code_out()?,
+            dstar_module: nerr.dstar_module()?,
+            site: None,
+            place: Place::new(&nerr.location_town, &nerr.location_state, &nerr.location_county),
+            comment: None,
         })
     }
 }
+
+impl From<&Repeater> for NeRepeaterRecord {
+    /// Repeaters with an input that isn't the standard offset get a "*Input: <freq>" comment. The
+    /// town, state and county come from the repeater's place, or else the site's location, if it is
+    /// "<town>, <state>".
+    fn from(r: &Repeater) -> Self {
+        let output = r.output_freq();
+        let (input_offset_dir, links_and_comments) = match r.input_freq() {
+            None => ("*", String::new()),
+            Some(input) if input == output => ("S", String::new()),
+            Some(input) if standard_offset_new_england(output) == Some(input - output) => ("+", String::new()),
+            Some(input) if standard_offset_new_england(output) == Some(output - input) => ("-", String::new()),
+            Some(input) => ("*", format!("*Input: {}", input)),
+        };
+
+        let (town, state) = match (r.place(), r.site()) {
+            (Some(place), _) => (place.town.clone(), place.state.clone()),
+            (None, Some(site)) => match site.location().rsplit_once(',') {
+                Some((town, state)) if is_state(state.trim()) => (town.trim().to_owned(), state.trim().to_owned()),
+                _ => (site.name().to_owned(), String::new()),
+            },
+            (None, None) => (String::new(), String::new()),
+        };
+
+        let digital: String = NE_DIGITAL_MODES
+            .iter()
+            .filter(|(_, m)| r.modes().contains(m))
+            .map(|(name, _)| *name)
+            .collect();
+        let analog = if r.modes().contains(&Mode::Fm) {
+            Some("FM")
+        } else if r.modes().contains(&Mode::Nfm) {
+            Some("NFM")
+        } else {
+            None
+        };
+        let mode = match (digital.is_empty(), analog) {
+            (true, Some("FM")) => String::new(),
+            (true, a) => a.unwrap_or_default().to_owned(),
+            (false, Some(a)) => format!("{}/{}", digital, a),
+            (false, None) => digital,
+        };
+
+        // color codes and NACs are bare numbers on DMR and P25 repeaters
+        let code = |c: Option<Code>| match c {
+            Some(Code::ColorCode(c)) if r.modes().contains(&Mode::Dmr) => c.to_string(),
+            Some(Code::Nac(n)) if r.modes().contains(&Mode::P25) => format!("{:X}", n),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        };
+        // the D-STAR module comes first, as in "C/77.0"
+        let code_in = match (r.dstar_module().filter(|_| r.modes().contains(&Mode::DStar)), code(r.code_in())) {
+            (Some(module), code) if code.is_empty() => module.to_string(),
+            (Some(module), code) => format!("{}/{}", module, code),
+            (None, code) => code,
+        };
+
+        NeRepeaterRecord {
+            output_freq: output.to_string(),
+            input_offset_dir: input_offset_dir.to_owned(),
+            location_state: state,
+            location_town: town,
+            mode,
+            callsign: r.callsign().to_owned(),
+            code_in,
+            code_out: code(r.code_out()),
+            status: match r.status() {
+                Status::On => "",
+                Status::Off => "OFF",
+                Status::Local => "Local",
+                Status::LimitedRx => "Limited RX",
+                Status::LimitedTx => "Limited TX",
+            }
+            .to_owned(),
+            location_county: r.place().map(|p| p.county.clone()).unwrap_or_default(),
+            irlp: r.irlp_node().map(|n| n.to_string()).unwrap_or_default(),
+            echo: r.echolink_node().map(|n| n.to_string()).unwrap_or_default(),
+            links_and_comments,
+            update_timestamp: r.updated().map(|d| d.format("%Y/%m/%d").to_string()),
+        }
+    }
+}
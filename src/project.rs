@@ -0,0 +1,250 @@
+//! Project files: the sources, filters and layout for building channel lists for several radios
+//!
+//! A project is a TOML document:
+//!
+//! ```toml
+//! # repeaters from these listings are merged (see `repeater_db`)
+//! [inputs]
+//! ne_csv = ["NERepeaters.csv"]
+//! radioid = ["rptrs.json"]
+//! gazetteer = ["towns.csv"]
+//! prefer = ["ne", "radioid"]
+//! # channel lists in any `formats` format, added as they are after the merged repeaters
+//! lists = [{ path = "marathon.csv", format = "ics" }]
+//!
+//! # only repeaters matching this filter (see `filter`)
+//! where = "state = MA"
+//!
+//! [layout]
+//! sort = ["freq"]
+//! group_by = "band"
+//!
+//! [[radio]]
+//! model = "uv5r"
+//! output = "out/uv5r.csv"
+//! where = "band = 2m or band = 70cm"
+//! name = "{callsign} {town:3}"
+//!
+//! [[radio]]
+//! format = "at878"
+//! output = "out/at878.csv"
+//! groups_output = "out/at878-zones.csv"
+//! scan_lists_output = "out/at878-scan-lists.csv"
+//! layout = { sort = ["name"], group_by = "county", group_size = 64 }
+//! ```
+//!
+//! Each `[[radio]]` needs a `model` (see `models`) or a `format` (see `formats`), and may have its
+//! own `where` (on top of the project's), `layout` (instead of the project's), channel name
+//! template and channel count. Relative paths are relative to the project file.
+//!
+//! Building a project reads the same inputs the same way each time, so the outputs only change
+//! when the inputs or the project do.
+
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::filter::Filter;
+use crate::formats::Format;
+use crate::geo::LatLon;
+use crate::layout::{GroupBy, Layout, SortKey};
+use crate::models::Model;
+use crate::names::Template;
+use crate::repeater_db::Source;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    #[serde(default)]
+    pub inputs: Inputs,
+    /// Repeaters every radio's list is chosen from
+    #[serde(default, rename = "where", deserialize_with = "parsed_option")]
+    pub filter: Option<Filter>,
+    /// The layout of radios without their own
+    #[serde(default)]
+    pub layout: LayoutSpec,
+    #[serde(default, rename = "radio")]
+    pub radios: Vec<Target>,
+}
+
+/// Where the repeaters come from, as for the `--ne-csv`, `--radioid` etc. options
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Inputs {
+    #[serde(default)]
+    pub ne_csv: Vec<PathBuf>,
+    #[serde(default)]
+    pub radioid: Vec<PathBuf>,
+    #[serde(default)]
+    pub icom: Vec<PathBuf>,
+    #[serde(default)]
+    pub kenwood: Vec<PathBuf>,
+    #[serde(default)]
+    pub gazetteer: Vec<PathBuf>,
+    /// Merged database to start from
+    pub db: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parsed_vec")]
+    pub prefer: Vec<Source>,
+    /// Channel lists that aren't merged
+    #[serde(default)]
+    pub lists: Vec<ListInput>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListInput {
+    pub path: PathBuf,
+    /// Guessed from the file if missing
+    #[serde(default, deserialize_with = "format_option")]
+    pub format: Option<&'static Format>,
+}
+
+/// A `Layout`, as the `--sort`, `--group-by` etc. options give it
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutSpec {
+    #[serde(default, deserialize_with = "parsed_vec")]
+    pub sort: Vec<SortKey>,
+    #[serde(default, deserialize_with = "parsed_option")]
+    pub center: Option<LatLon>,
+    pub first: Option<usize>,
+    /// "<first>-<last>" or single channel numbers
+    #[serde(default, deserialize_with = "ranges")]
+    pub reserve: Vec<RangeInclusive<usize>>,
+    #[serde(default, deserialize_with = "parsed_option")]
+    pub group_by: Option<GroupBy>,
+    pub group_size: Option<usize>,
+}
+
+impl LayoutSpec {
+    pub fn layout(&self) -> Layout {
+        Layout {
+            sort: self.sort.clone(),
+            center: self.center,
+            first: self.first.unwrap_or(1),
+            reserved: self.reserve.clone(),
+            group_by: self.group_by,
+            group_size: self.group_size,
+            limit: None,
+        }
+    }
+}
+
+/// A channel list to build
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    #[serde(default, deserialize_with = "model_option")]
+    pub model: Option<&'static Model>,
+    /// The model's format by default
+    #[serde(default, deserialize_with = "format_option")]
+    pub format: Option<&'static Format>,
+    pub output: PathBuf,
+    /// Where to write the groups, for formats that keep them in a separate file
+    pub groups_output: Option<PathBuf>,
+    /// Where to write a scan list for each group, for radios that scan by list
+    pub scan_lists_output: Option<PathBuf>,
+    #[serde(default, rename = "where", deserialize_with = "parsed_option")]
+    pub filter: Option<Filter>,
+    pub layout: Option<LayoutSpec>,
+    #[serde(default, deserialize_with = "parsed_option")]
+    pub name: Option<Template>,
+    /// Most channels to write, the model's channel count by default
+    pub channels: Option<usize>,
+}
+
+impl Target {
+    pub fn format(&self) -> &'static Format {
+        match (self.format, self.model) {
+            (Some(format), _) => format,
+            (None, Some(model)) => model.format.list_format(),
+            (None, None) => unreachable!("checked by Project::from_str()"),
+        }
+    }
+
+    /// The model's, or no limit
+    pub fn channels(&self) -> usize {
+        self.channels.or(self.model.map(|m| m.channels)).unwrap_or(usize::MAX)
+    }
+}
+
+impl FromStr for Project {
+    type Err = FreqmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let project: Project = toml::from_str(s).context(TomlReadSnafu)?;
+        for (n, radio) in project.radios.iter().enumerate() {
+            if radio.model.is_none() && radio.format.is_none() {
+                return Err(ProjectSnafu { reason: format!("radio {} has neither a model nor a format", n + 1) }.build().into());
+            }
+        }
+        Ok(project)
+    }
+}
+
+impl Project {
+    /// Read a project file, making its relative paths relative to it
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FreqmError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        let mut project: Project = text.parse().map_err(|e: FreqmError| e.with_file(path))?;
+        if let Some(dir) = path.parent() {
+            project.resolve(dir);
+        }
+        Ok(project)
+    }
+
+    /// Make relative paths relative to `dir`
+    pub fn resolve(&mut self, dir: &Path) {
+        let inputs = &mut self.inputs;
+        let lists = inputs.lists.iter_mut().map(|l| &mut l.path);
+        let outputs = self.radios.iter_mut().flat_map(|r| std::iter::once(&mut r.output).chain(r.groups_output.as_mut()).chain(r.scan_lists_output.as_mut()));
+        for path in inputs
+            .ne_csv
+            .iter_mut()
+            .chain(&mut inputs.radioid)
+            .chain(&mut inputs.icom)
+            .chain(&mut inputs.kenwood)
+            .chain(&mut inputs.gazetteer)
+            .chain(inputs.db.as_mut())
+            .chain(lists)
+            .chain(outputs)
+        {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        }
+    }
+}
+
+fn parsed<T: FromStr<Err: Display>, E: serde::de::Error>(text: &str) -> Result<T, E> {
+    text.parse().map_err(E::custom)
+}
+
+fn parsed_option<'de, D: Deserializer<'de>, T: FromStr<Err: Display>>(d: D) -> Result<Option<T>, D::Error> {
+    Option::<String>::deserialize(d)?.map(|s| parsed(&s)).transpose()
+}
+
+fn parsed_vec<'de, D: Deserializer<'de>, T: FromStr<Err: Display>>(d: D) -> Result<Vec<T>, D::Error> {
+    Vec::<String>::deserialize(d)?.iter().map(|s| parsed(s)).collect()
+}
+
+fn format_option<'de, D: Deserializer<'de>>(d: D) -> Result<Option<&'static Format>, D::Error> {
+    Option::<String>::deserialize(d)?.map(|s| crate::formats::find(&s).map_err(serde::de::Error::custom)).transpose()
+}
+
+fn model_option<'de, D: Deserializer<'de>>(d: D) -> Result<Option<&'static Model>, D::Error> {
+    Option::<String>::deserialize(d)?.map(|s| crate::models::find(&s).map_err(serde::de::Error::custom)).transpose()
+}
+
+fn ranges<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<RangeInclusive<usize>>, D::Error> {
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|s| crate::layout::parse_range(s).map_err(serde::de::Error::custom))
+        .collect()
+}
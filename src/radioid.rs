@@ -1,6 +1,14 @@
 //! Interfaces to the radioid.net web service.
 
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::geo::{LatLon, Located};
+use crate::ne_repeater::parse_mhz;
+use crate::{Code, Mode, Place, Repeater, Site, Status};
 
 /// https://radioid.net/static/rptrs.json
 #[derive(Deserialize, Serialize, Debug)]
@@ -50,3 +58,57 @@ pub struct Rptr {
     // NOTE: needs normalization. "BM" vs "Brandmeister" vs "BrandMeister"
     pub ipsc_network: String,
 }
+
+/// Read a saved copy of `rptrs.json`
+pub fn read_rptrs<P: AsRef<Path>>(path: P) -> Result<RptrsResponse, FreqmError> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+    let rptrs = serde_json::from_reader(std::io::BufReader::new(file))
+        .map_err(|source| FreqmError::from(FormatError::Json { source }).with_file(path))?;
+    Ok(rptrs)
+}
+
+impl Located for Rptr {
+    /// From the first two decimal numbers in `map_info` (latitude, then longitude). Repeaters
+    /// without map info have none.
+    fn position(&self) -> Option<LatLon> {
+        let mut numbers = self
+            .map_info
+            .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .filter(|s| s.contains('.'))
+            .filter_map(|s| s.parse::<f64>().ok());
+        LatLon::new(numbers.next()?, numbers.next()?)
+    }
+}
+
+impl std::convert::TryFrom<&Rptr> for Repeater {
+    type Error = FreqmError;
+
+    fn try_from(r: &Rptr) -> Result<Self, Self::Error> {
+        let output_freq = parse_mhz(r.frequency.trim())
+            .ok_or_else(|| FrequencySnafu { value: &r.frequency }.build().column("frequency"))?;
+        let offset = r.offset.trim();
+        let bad_offset = || FrequencySnafu { value: offset }.build().column("offset");
+        let input_freq = match offset.strip_prefix('-') {
+            Some(o) => output_freq - parse_mhz(o).ok_or_else(bad_offset)?,
+            None => output_freq + parse_mhz(offset.trim_start_matches('+')).ok_or_else(bad_offset)?,
+        };
+
+        Ok(Repeater {
+            callsign: r.callsign.trim().to_owned(),
+            output_freq,
+            input_freq: Some(input_freq),
+            modes: vec![Mode::Dmr],
+            status: if r.status.eq_ignore_ascii_case("active") { Status::On } else { Status::Off },
+            irlp_node: None,
+            echolink_node: None,
+            updated: None,
+            code_in: Some(Code::ColorCode(r.color_code as u8)),
+            code_out: Some(Code::ColorCode(r.color_code as u8)),
+            dstar_module: None,
+            site: r.position().map(|p| Site::new(&r.city, format!("{}, {}", r.city, r.state), p)),
+            place: Place::new(&r.city, &r.state, ""),
+            comment: None,
+        })
+    }
+}
@@ -0,0 +1,381 @@
+//! A repeater database merged from several listings
+//!
+//! The same repeater usually appears in more than one listing. Records are matched by callsign
+//! and output frequency, and, when both have a position, by being within `MATCH_KM` of each other
+//! (so a callsign reused at distant sites stays separate).
+//!
+//! Each field of a merged entry keeps the value from the highest precedence source that has one,
+//! along with the source it came from. Values from other sources that disagree are kept as
+//! conflicts, for review. Exact positions always win over approximate ones (like town centers
+//! from a gazetteer), whatever their source.
+//!
+//! The database is saved as JSON, so it can be built once and then queried and exported from.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::geo::Located;
+use crate::{Code, Mode, Place, Repeater, Site, Status};
+
+/// Records further apart than this are different repeaters
+const MATCH_KM: f64 = 50.0;
+
+/// Positions closer than this agree
+const SAME_POSITION_KM: f64 = 2.0;
+
+const VERSION: u32 = 1;
+
+/// Where a record came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// NE repeater listing
+    Ne,
+    /// Icom repeater list
+    Icom,
+    /// Kenwood repeater list
+    Kenwood,
+    /// radioid.net
+    RadioId,
+}
+
+impl Source {
+    /// The default precedence, highest first
+    pub const ALL: [Source; 4] = [Source::Ne, Source::Icom, Source::Kenwood, Source::RadioId];
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Ne => "ne",
+            Source::Icom => "icom",
+            Source::Kenwood => "kenwood",
+            Source::RadioId => "radioid",
+        })
+    }
+}
+
+impl std::str::FromStr for Source {
+    type Err = FreqmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Source::ALL
+            .iter()
+            .copied()
+            .find(|source| source.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| SourceSnafu { value: s }.build().column("source"))
+    }
+}
+
+/// A field's value and the source it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sourced<T> {
+    pub value: T,
+    pub source: Source,
+    /// Values from other sources that disagree
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<(Source, T)>,
+}
+
+/// A repeater merged from one or more records
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub callsign: String,
+    /// MHz
+    pub output_freq: decimal::d128,
+    /// Every source with a record of this repeater
+    pub sources: Vec<Source>,
+    pub input_freq: Option<Sourced<decimal::d128>>,
+    pub modes: Option<Sourced<Vec<Mode>>>,
+    pub status: Option<Sourced<Status>>,
+    pub code_in: Option<Sourced<Code>>,
+    pub code_out: Option<Sourced<Code>>,
+    pub dstar_module: Option<Sourced<char>>,
+    pub irlp_node: Option<Sourced<u32>>,
+    pub echolink_node: Option<Sourced<u32>>,
+    pub updated: Option<Sourced<chrono::NaiveDate>>,
+    pub site: Option<Sourced<Site>>,
+    pub place: Option<Sourced<Place>>,
+    pub comment: Option<Sourced<String>>,
+}
+
+/// A field with values that disagree, formatted for display
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub field: &'static str,
+    /// The value used, and where it came from
+    pub value: (Source, String),
+    pub others: Vec<(Source, String)>,
+}
+
+impl fmt::Display for Conflict {
+    /// "code_in: 88.5 (ne), 100.0 (icom)"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.field, self.value.1, self.value.0)?;
+        for (source, value) in &self.others {
+            write!(f, ", {} ({})", value, source)?;
+        }
+        Ok(())
+    }
+}
+
+fn conflict<T, F: Fn(&T) -> String>(field: &'static str, sourced: &Option<Sourced<T>>, show: F) -> Option<Conflict> {
+    let s = sourced.as_ref().filter(|s| !s.conflicts.is_empty())?;
+    Some(Conflict {
+        field,
+        value: (s.source, show(&s.value)),
+        others: s.conflicts.iter().map(|(source, v)| (*source, show(v))).collect(),
+    })
+}
+
+impl Entry {
+    /// The merged repeater
+    pub fn repeater(&self) -> Repeater {
+        fn value<T: Clone>(s: &Option<Sourced<T>>) -> Option<T> {
+            s.as_ref().map(|s| s.value.clone())
+        }
+
+        Repeater {
+            callsign: self.callsign.clone(),
+            output_freq: self.output_freq,
+            input_freq: value(&self.input_freq),
+            modes: value(&self.modes).unwrap_or_default(),
+            status: value(&self.status).unwrap_or_default(),
+            irlp_node: value(&self.irlp_node),
+            echolink_node: value(&self.echolink_node),
+            updated: value(&self.updated),
+            code_in: value(&self.code_in),
+            code_out: value(&self.code_out),
+            dstar_module: value(&self.dstar_module),
+            site: value(&self.site),
+            place: value(&self.place),
+            comment: value(&self.comment),
+        }
+    }
+
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let modes = |m: &Vec<Mode>| m.iter().map(|m| format!("{:?}", m)).collect::<Vec<_>>().join("/");
+        let site = |s: &Site| format!("{} ({})", s.location(), s.grid());
+        [
+            conflict("input_freq", &self.input_freq, |f| f.to_string()),
+            conflict("modes", &self.modes, modes),
+            conflict("status", &self.status, |s| format!("{:?}", s)),
+            conflict("code_in", &self.code_in, |c| c.to_string()),
+            conflict("code_out", &self.code_out, |c| c.to_string()),
+            conflict("dstar_module", &self.dstar_module, |m| m.to_string()),
+            conflict("irlp_node", &self.irlp_node, |n| n.to_string()),
+            conflict("echolink_node", &self.echolink_node, |n| n.to_string()),
+            conflict("site", &self.site, site),
+            conflict("place", &self.place, Place::to_string),
+            conflict("comment", &self.comment, String::clone),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    fn matches(&self, r: &Repeater) -> bool {
+        let here = self.site.as_ref().and_then(|s| s.value.position());
+        match (here, r.position()) {
+            (Some(a), Some(b)) => a.distance(&b).as_km() <= MATCH_KM,
+            _ => true,
+        }
+    }
+}
+
+/// Merge `new` from `source` into `field`
+fn merge<T: Clone>(
+    field: &mut Option<Sourced<T>>,
+    new: Option<T>,
+    source: Source,
+    outranks: impl Fn(Source, Source) -> bool,
+    same: impl Fn(&T, &T) -> bool,
+) {
+    let Some(new) = new else { return };
+    let Some(current) = field else {
+        *field = Some(Sourced { value: new, source, conflicts: Vec::new() });
+        return;
+    };
+
+    if same(&current.value, &new) {
+        if outranks(source, current.source) {
+            current.source = source;
+        }
+    } else if outranks(source, current.source) {
+        let old = std::mem::replace(&mut current.value, new);
+        current.conflicts.push((current.source, old));
+        current.source = source;
+    } else {
+        current.conflicts.push((source, new));
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeaterDb {
+    version: u32,
+    /// Highest first
+    precedence: Vec<Source>,
+    entries: Vec<Entry>,
+
+    /// (callsign, output frequency in Hz) to entries
+    #[serde(skip)]
+    index: HashMap<(String, i64), Vec<usize>>,
+}
+
+/// Identifies a repeater across listings: callsign (ignoring case) and output frequency in Hz
+pub(crate) fn key(callsign: &str, output_freq: decimal::d128) -> (String, i64) {
+    (callsign.trim().to_ascii_uppercase(), (crate::mhz_to_f64(output_freq) * 1e6).round() as i64)
+}
+
+impl RepeaterDb {
+    /// An empty database, preferring values from sources earlier in `precedence`. Sources that
+    /// aren't listed come after the listed ones, in the default order.
+    pub fn new(mut precedence: Vec<Source>) -> Self {
+        for source in Source::ALL {
+            if !precedence.contains(&source) {
+                precedence.push(source);
+            }
+        }
+
+        RepeaterDb { version: VERSION, precedence, entries: Vec::new(), index: HashMap::new() }
+    }
+
+    pub fn precedence(&self) -> &[Source] {
+        &self.precedence
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The merged repeaters
+    pub fn repeaters(&self) -> Vec<Repeater> {
+        self.entries.iter().map(Entry::repeater).collect()
+    }
+
+    /// Merge a record into the matching entry, or add a new entry for it. Returns the entry's
+    /// index.
+    pub fn add(&mut self, source: Source, r: Repeater) -> usize {
+        let key = key(&r.callsign, r.output_freq);
+        let found = self
+            .index
+            .get(&key)
+            .and_then(|entries| entries.iter().copied().find(|&i| self.entries[i].matches(&r)));
+
+        let i = match found {
+            Some(i) => i,
+            None => {
+                self.entries.push(Entry {
+                    callsign: r.callsign.clone(),
+                    output_freq: r.output_freq,
+                    sources: Vec::new(),
+                    input_freq: None,
+                    modes: None,
+                    status: None,
+                    code_in: None,
+                    code_out: None,
+                    dstar_module: None,
+                    irlp_node: None,
+                    echolink_node: None,
+                    updated: None,
+                    site: None,
+                    place: None,
+                    comment: None,
+                });
+                self.index.entry(key).or_default().push(self.entries.len() - 1);
+                self.entries.len() - 1
+            }
+        };
+
+        let precedence = self.precedence.clone();
+        let outranks = |a: Source, b: Source| {
+            let rank = |s| precedence.iter().position(|&p| p == s);
+            rank(a) < rank(b)
+        };
+
+        let e = &mut self.entries[i];
+        if !e.sources.contains(&source) {
+            e.sources.push(source);
+        }
+
+        let mut modes = r.modes;
+        modes.sort();
+        modes.dedup();
+        let modes = Some(modes).filter(|m| !m.is_empty());
+
+        merge(&mut e.input_freq, r.input_freq, source, outranks, |a, b| a == b);
+        merge(&mut e.modes, modes, source, outranks, |a, b| a == b);
+        merge(&mut e.status, Some(r.status), source, outranks, |a, b| a == b);
+        merge(&mut e.code_in, r.code_in, source, outranks, |a, b| a == b);
+        merge(&mut e.code_out, r.code_out, source, outranks, |a, b| a == b);
+        merge(&mut e.dstar_module, r.dstar_module, source, outranks, |a, b| a == b);
+        merge(&mut e.irlp_node, r.irlp_node, source, outranks, |a, b| a == b);
+        merge(&mut e.echolink_node, r.echolink_node, source, outranks, |a, b| a == b);
+        merge(&mut e.comment, r.comment, source, outranks, |a, b| a == b);
+        // each source has its own update date, they don't conflict
+        merge(&mut e.updated, r.updated, source, outranks, |_, _| true);
+        // listings that agree on the town don't all give its county
+        if let (Some(current), Some(new)) = (&mut e.place, &r.place)
+            && current.value.county.is_empty()
+            && current.value.same(new)
+        {
+            current.value.county = new.county.clone();
+        }
+        merge(&mut e.place, r.place, source, outranks, Place::same);
+
+        match (&mut e.site, r.site) {
+            (_, None) => {}
+            // exact positions win over approximate ones
+            (Some(current), Some(new)) if current.value.is_approximate() != new.is_approximate() => {
+                if current.value.is_approximate() {
+                    *current = Sourced { value: new, source, conflicts: Vec::new() };
+                }
+            }
+            (site, new) => merge(site, new, source, outranks, |a, b| {
+                let (a, b) = (a.position().unwrap(), b.position().unwrap());
+                a.distance(&b).as_km() < SAME_POSITION_KM
+            }),
+        }
+
+        i
+    }
+
+    /// Keep only the entries for which `keep` is true
+    pub fn retain(&mut self, keep: impl FnMut(&Entry) -> bool) {
+        self.entries.retain(keep);
+        self.reindex();
+    }
+
+    fn reindex(&mut self) {
+        self.index.clear();
+        for (i, e) in self.entries.iter().enumerate() {
+            self.index.entry(key(&e.callsign, e.output_freq)).or_default().push(i);
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FreqmError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        let mut db: RepeaterDb = serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|source| FreqmError::from(FormatError::Json { source }).with_file(path))?;
+        if db.version > VERSION {
+            return Err(FreqmError::from(DatabaseVersionSnafu { found: db.version, supported: VERSION }.build()).with_file(path));
+        }
+        db.reindex();
+        Ok(db)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), FreqmError> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        let mut w = std::io::BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut w, self)
+            .map_err(|source| FreqmError::from(FormatError::Json { source }).with_file(path))?;
+        std::io::Write::flush(&mut w).context(IoSnafu { path: Some(path.to_owned()) })
+    }
+}
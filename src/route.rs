@@ -0,0 +1,208 @@
+//! Selecting repeaters along a travel route
+//!
+//! A route is a path through a sequence of points, either waypoints given by hand or the points of
+//! a GPX track. Repeaters within a corridor around the path are listed in the order they're passed.
+//!
+//! Each leg between two points is treated as a straight line on a local flat projection. That is
+//! accurate for the leg lengths of a GPX track or a road trip's waypoints, but not for legs
+//! crossing a continent.
+
+use std::path::Path;
+
+use snafu::ResultExt;
+
+use crate::error::*;
+use crate::geo::{Distance, LatLon, Located};
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    points: Vec<LatLon>,
+    /// Distance along the route to each point, in km
+    along: Vec<f64>,
+}
+
+/// Where a position is relative to a route
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoutePosition {
+    /// Distance along the route to the nearest point on it
+    pub along: Distance,
+    /// Distance from the nearest point on the route
+    pub off: Distance,
+}
+
+impl Route {
+    /// `None` if there are no points
+    pub fn new(points: Vec<LatLon>) -> Option<Self> {
+        if points.is_empty() {
+            return None;
+        }
+
+        let mut along = vec![0.0];
+        for leg in points.windows(2) {
+            along.push(along.last().unwrap() + leg[0].distance(&leg[1]).as_km());
+        }
+        Some(Route { points, along })
+    }
+
+    /// Load the track points of a GPX file, or its route points or waypoints if it has no track
+    pub fn load_gpx<P: AsRef<Path>>(path: P) -> Result<Self, FreqmError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).context(IoSnafu { path: Some(path.to_owned()) })?;
+        Route::parse_gpx(&text).map_err(|e| e.with_file(path))
+    }
+
+    /// Points from the `<trkpt>`, `<rtept>` or `<wpt>` elements of GPX text, whichever come first
+    /// in that order
+    ///
+    /// Only the `lat` and `lon` attributes are read, so this doesn't need a full XML parser.
+    pub fn parse_gpx(text: &str) -> Result<Self, FreqmError> {
+        for tag in ["trkpt", "rtept", "wpt"] {
+            let points = gpx_points(text, tag)?;
+            if let Some(route) = Route::new(points) {
+                return Ok(route);
+            }
+        }
+
+        Err(FormatError::Gpx { reason: "no trkpt, rtept or wpt elements".to_owned() }.into())
+    }
+
+    pub fn points(&self) -> &[LatLon] {
+        &self.points
+    }
+
+    pub fn length(&self) -> Distance {
+        Distance::km(*self.along.last().unwrap())
+    }
+
+    /// The nearest point on the route to `pos`
+    pub fn locate(&self, pos: &LatLon) -> RoutePosition {
+        let (along, off) = if self.points.len() == 1 {
+            (0.0, self.points[0].distance(pos).as_km())
+        } else {
+            self.points
+                .windows(2)
+                .zip(&self.along)
+                .map(|(leg, start)| {
+                    let nearest = nearest_on_leg(&leg[0], &leg[1], pos);
+                    (start + leg[0].distance(&nearest).as_km(), nearest.distance(pos).as_km())
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap()
+        };
+
+        RoutePosition { along: Distance::km(along), off: Distance::km(off) }
+    }
+}
+
+/// The point on the leg `a` to `b` nearest to `p`
+fn nearest_on_leg(a: &LatLon, b: &LatLon, p: &LatLon) -> LatLon {
+    // flat projection around `a`, in degrees of latitude
+    let scale = a.lat.to_radians().cos();
+    let (bx, by) = ((b.lon - a.lon) * scale, b.lat - a.lat);
+    let (px, py) = ((p.lon - a.lon) * scale, p.lat - a.lat);
+
+    let len2 = bx * bx + by * by;
+    let t = if len2 == 0.0 { 0.0 } else { ((px * bx + py * by) / len2).clamp(0.0, 1.0) };
+    LatLon { lat: a.lat + t * (b.lat - a.lat), lon: a.lon + t * (b.lon - a.lon) }
+}
+
+/// The `lat` and `lon` attributes of every `<tag ...>` element
+fn gpx_points(text: &str, tag: &'static str) -> Result<Vec<LatLon>, FreqmError> {
+    let open = format!("<{}", tag);
+    let mut points = Vec::new();
+    let mut from = 0;
+
+    while let Some(i) = text[from..].find(&open) {
+        let start = from + i + open.len();
+        from = start;
+        if !text[start..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            continue;
+        }
+
+        let line = text[..start].matches('\n').count() as u64 + 1;
+        let attrs = &text[start..start + text[start..].find('>').unwrap_or(text.len() - start)];
+        let bad = || {
+            let mut e = PositionSnafu { value: attrs.trim() }.build().column(tag);
+            e.set_row(line, points.len() as u64 + 1);
+            e
+        };
+
+        let coordinate = |name| attr(attrs, name).and_then(|v| v.trim().parse::<f64>().ok());
+        let pos = match (coordinate("lat"), coordinate("lon")) {
+            (Some(lat), Some(lon)) => LatLon::new(lat, lon).ok_or_else(bad)?,
+            _ => return Err(bad()),
+        };
+        points.push(pos);
+    }
+
+    Ok(points)
+}
+
+/// The value of attribute `name` in the text of an element's start tag
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    while let Some(i) = rest.find(name) {
+        let before = rest[..i].chars().next_back();
+        let after = rest[i + name.len()..].trim_start();
+        rest = &rest[i + name.len()..];
+
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=') else { continue };
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|&q| q == '"' || q == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    None
+}
+
+/// An item found by `corridor()`
+#[derive(Debug, Clone)]
+pub struct OnRoute<T> {
+    pub item: T,
+    /// In the unit of the corridor width
+    pub along: Distance,
+    /// In the unit of the corridor width
+    pub off: Distance,
+}
+
+/// Items within `width` of the route, in the order they're passed. Items without a position are
+/// skipped.
+pub fn corridor<T, I>(items: I, route: &Route, width: Distance) -> Vec<OnRoute<T>>
+where
+    T: Located,
+    I: IntoIterator<Item = T>,
+{
+    let mut found: Vec<OnRoute<T>> = items
+        .into_iter()
+        .filter_map(|item| {
+            let at = route.locate(&item.position()?);
+            let off = at.off.in_unit(width.unit());
+            (off <= width).then(|| OnRoute { along: at.along.in_unit(width.unit()), off, item })
+        })
+        .collect();
+
+    found.sort_by(|a, b| {
+        a.along.as_km().total_cmp(&b.along.as_km()).then(a.off.as_km().total_cmp(&b.off.as_km()))
+    });
+    found
+}
+
+/// At most `channels` of `found`, dropping the items farthest from the route first. The rest stay
+/// in route order.
+pub fn fit<T>(found: Vec<OnRoute<T>>, channels: usize) -> Vec<OnRoute<T>> {
+    if found.len() <= channels {
+        return found;
+    }
+
+    let mut by_off: Vec<usize> = (0..found.len()).collect();
+    by_off.sort_by(|&a, &b| found[a].off.as_km().total_cmp(&found[b].off.as_km()));
+    let mut keep = vec![false; found.len()];
+    for &i in &by_off[..channels] {
+        keep[i] = true;
+    }
+
+    found.into_iter().zip(keep).filter(|(_, k)| *k).map(|(f, _)| f).collect()
+}
@@ -0,0 +1,306 @@
+//! Repeaters in a SQLite database, for querying with SQL
+//!
+//! The database is normalized: sites and places shared by several repeaters are stored once, and
+//! a repeater's modes and the listings it was merged from (see `repeater_db`) have tables of their
+//! own:
+//!
+//! ```sql
+//! sites (id, name, location, lat, lon, approximate, grid)
+//! places (id, town, state, county)
+//! channels (id, callsign, output_freq, input_freq, output_mhz, input_mhz, band, status, code_in,
+//!           code_out, dstar_module, irlp_node, echolink_node, updated, site_id, place_id, comment)
+//! channel_modes (channel_id, position, mode)
+//! channel_sources (channel_id, source)
+//! ```
+//!
+//! Frequencies are kept as text, exactly as the listings give them, with `output_mhz`, `input_mhz`
+//! and `band` alongside for arithmetic and grouping. Codes are written as `Code` writes them,
+//! statuses and modes by name, and dates as "YYYY-MM-DD". The grid, band and MHz columns are
+//! derived, and ignored when reading the channels back.
+//!
+//! Talkgroups aren't part of the channel model (a `Repeater` has none), so there's no table for
+//! them yet.
+//!
+//! `save()` replaces the tables above, leaving anything else in the database alone. The schema
+//! version is kept in `PRAGMA user_version`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OpenFlags};
+
+use crate::error::*;
+use crate::repeater_db::Source;
+use crate::{Band, Code, Mode, Place, Repeater, Site};
+
+/// The schema version written, and the newest one read
+pub const VERSION: u32 = 1;
+
+const SCHEMA: &str = "
+DROP TABLE IF EXISTS channel_sources;
+DROP TABLE IF EXISTS channel_modes;
+DROP TABLE IF EXISTS channels;
+DROP TABLE IF EXISTS places;
+DROP TABLE IF EXISTS sites;
+
+CREATE TABLE sites (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    location TEXT NOT NULL,
+    lat REAL NOT NULL,
+    lon REAL NOT NULL,
+    approximate INTEGER NOT NULL,
+    grid TEXT NOT NULL
+);
+
+CREATE TABLE places (
+    id INTEGER PRIMARY KEY,
+    town TEXT NOT NULL,
+    state TEXT NOT NULL,
+    county TEXT NOT NULL,
+    UNIQUE (town, state, county)
+);
+
+CREATE TABLE channels (
+    id INTEGER PRIMARY KEY,
+    callsign TEXT NOT NULL,
+    output_freq TEXT NOT NULL,
+    input_freq TEXT,
+    output_mhz REAL NOT NULL,
+    input_mhz REAL,
+    band TEXT,
+    status TEXT NOT NULL,
+    code_in TEXT,
+    code_out TEXT,
+    dstar_module TEXT,
+    irlp_node INTEGER,
+    echolink_node INTEGER,
+    updated TEXT,
+    site_id INTEGER REFERENCES sites (id),
+    place_id INTEGER REFERENCES places (id),
+    comment TEXT
+);
+CREATE INDEX channels_callsign ON channels (callsign);
+CREATE INDEX channels_output_mhz ON channels (output_mhz);
+
+CREATE TABLE channel_modes (
+    channel_id INTEGER NOT NULL REFERENCES channels (id),
+    position INTEGER NOT NULL,
+    mode TEXT NOT NULL,
+    PRIMARY KEY (channel_id, position)
+);
+
+CREATE TABLE channel_sources (
+    channel_id INTEGER NOT NULL REFERENCES channels (id),
+    source TEXT NOT NULL,
+    PRIMARY KEY (channel_id, source)
+);
+";
+
+/// Write the channels, with the listings each was merged from (empty for channels that weren't),
+/// replacing the ones already in the database. The file is created if it doesn't exist.
+pub fn save<'a, P: AsRef<Path>>(
+    path: P,
+    channels: impl IntoIterator<Item = (&'a Repeater, &'a [Source])>,
+) -> Result<(), FreqmError> {
+    let path = path.as_ref();
+    write(path, channels).map_err(|e| e.with_file(path))
+}
+
+fn write<'a>(path: &Path, channels: impl IntoIterator<Item = (&'a Repeater, &'a [Source])>) -> Result<(), FreqmError> {
+    let mut conn = Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute_batch(SCHEMA)?;
+    tx.pragma_update(None, "user_version", VERSION)?;
+
+    // sites by their fields (with the coordinates' bits), places by theirs
+    let mut sites: HashMap<(String, String, u64, u64, bool), i64> = HashMap::new();
+    let mut places: HashMap<(String, String, String), i64> = HashMap::new();
+    {
+        let mut insert_site = tx.prepare(
+            "INSERT INTO sites (name, location, lat, lon, approximate, grid) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        let mut insert_place = tx.prepare("INSERT INTO places (town, state, county) VALUES (?1, ?2, ?3)")?;
+        let mut insert_channel = tx.prepare(
+            "INSERT INTO channels (callsign, output_freq, input_freq, output_mhz, input_mhz, band, status, code_in,
+                code_out, dstar_module, irlp_node, echolink_node, updated, site_id, place_id, comment)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        )?;
+        let mut insert_mode = tx.prepare("INSERT INTO channel_modes (channel_id, position, mode) VALUES (?1, ?2, ?3)")?;
+        let mut insert_source = tx.prepare("INSERT INTO channel_sources (channel_id, source) VALUES (?1, ?2)")?;
+
+        for (r, sources) in channels {
+            let site_id = match r.site() {
+                Some(s) => {
+                    let key = (s.name().to_owned(), s.location().to_owned(), s.lat().to_bits(), s.lon().to_bits(), s.is_approximate());
+                    match sites.get(&key) {
+                        Some(&id) => Some(id),
+                        None => {
+                            let grid = s.grid().to_string();
+                            let id = insert_site.insert(params![s.name(), s.location(), s.lat(), s.lon(), s.is_approximate(), grid])?;
+                            sites.insert(key, id);
+                            Some(id)
+                        },
+                    }
+                },
+                None => None,
+            };
+            let place_id = match r.place() {
+                Some(p) => {
+                    let key = (p.town.clone(), p.state.clone(), p.county.clone());
+                    match places.get(&key) {
+                        Some(&id) => Some(id),
+                        None => {
+                            let id = insert_place.insert(params![p.town, p.state, p.county])?;
+                            places.insert(key, id);
+                            Some(id)
+                        },
+                    }
+                },
+                None => None,
+            };
+
+            let id = insert_channel.insert(params![
+                r.callsign(),
+                r.output_freq().to_string(),
+                r.input_freq().map(|f| f.to_string()),
+                crate::mhz_to_f64(r.output_freq()),
+                r.input_freq().map(crate::mhz_to_f64),
+                Band::of(r.output_freq()).map(|b| b.to_string()),
+                format!("{:?}", r.status()),
+                r.code_in().map(|c| c.to_string()),
+                r.code_out().map(|c| c.to_string()),
+                r.dstar_module().map(String::from),
+                r.irlp_node(),
+                r.echolink_node(),
+                r.updated(),
+                site_id,
+                place_id,
+                r.comment(),
+            ])?;
+            for (position, mode) in r.modes().iter().enumerate() {
+                insert_mode.execute(params![id, position, format!("{:?}", mode)])?;
+            }
+            for source in sources {
+                insert_source.execute(params![id, source.to_string()])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Read the channels back, in the order they were written
+///
+/// Errors in a channel's values have the channel's `id` as their record number.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Repeater>, FreqmError> {
+    let path = path.as_ref();
+    read(path).map_err(|e| e.with_file(path))
+}
+
+/// A channel's columns, before they're checked
+struct Row {
+    id: i64,
+    callsign: String,
+    output_freq: String,
+    input_freq: Option<String>,
+    status: String,
+    code_in: Option<String>,
+    code_out: Option<String>,
+    dstar_module: Option<String>,
+    irlp_node: Option<u32>,
+    echolink_node: Option<u32>,
+    updated: Option<chrono::NaiveDate>,
+    site: Option<Site>,
+    place: Option<Place>,
+    comment: Option<String>,
+}
+
+fn read(path: &Path) -> Result<Vec<Repeater>, FreqmError> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > VERSION {
+        return Err(DatabaseVersionSnafu { found: version, supported: VERSION }.build().into());
+    }
+
+    let mut modes: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut stmt = conn.prepare("SELECT channel_id, mode FROM channel_modes ORDER BY channel_id, position")?;
+    for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        let (id, mode) = row?;
+        modes.entry(id).or_default().push(mode);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.callsign, c.output_freq, c.input_freq, c.status, c.code_in, c.code_out, c.dstar_module,
+            c.irlp_node, c.echolink_node, c.updated, s.name, s.location, s.lat, s.lon, s.approximate, p.town, p.state,
+            p.county, c.comment
+        FROM channels c
+        LEFT JOIN sites s ON s.id = c.site_id
+        LEFT JOIN places p ON p.id = c.place_id
+        ORDER BY c.id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let site = row.get::<_, Option<String>>(11)?.map(|name| -> rusqlite::Result<Site> {
+            let position = crate::geo::LatLon { lat: row.get(13)?, lon: row.get(14)? };
+            let site = Site::new(name, row.get::<_, String>(12)?, position);
+            Ok(if row.get(15)? { site.approximate() } else { site })
+        });
+        let place = row.get::<_, Option<String>>(16)?.map(|town| -> rusqlite::Result<Place> {
+            Ok(Place { town, state: row.get(17)?, county: row.get(18)? })
+        });
+        Ok(Row {
+            id: row.get(0)?,
+            callsign: row.get(1)?,
+            output_freq: row.get(2)?,
+            input_freq: row.get(3)?,
+            status: row.get(4)?,
+            code_in: row.get(5)?,
+            code_out: row.get(6)?,
+            dstar_module: row.get(7)?,
+            irlp_node: row.get(8)?,
+            echolink_node: row.get(9)?,
+            updated: row.get(10)?,
+            site: site.transpose()?,
+            place: place.transpose()?,
+            comment: row.get(19)?,
+        })
+    })?;
+
+    let mut repeaters = Vec::new();
+    for row in rows {
+        let row = row?;
+        let id = row.id;
+        let modes = modes.remove(&id).unwrap_or_default();
+        repeaters.push(repeater(row, &modes).map_err(|e| e.with_record(id as u64))?);
+    }
+    Ok(repeaters)
+}
+
+fn repeater(row: Row, modes: &[String]) -> Result<Repeater, FreqmError> {
+    let freq = |text: &str, column| {
+        crate::ne_repeater::parse_mhz(text.trim()).ok_or_else(|| FrequencySnafu { value: text }.build().column(column))
+    };
+    let code = |text: Option<&String>, column| text.map(|c| c.parse::<Code>().map_err(|_| CodeSnafu { value: c }.build().column(column))).transpose();
+    let modes = modes.iter().map(|m| m.parse()).collect::<Result<Vec<Mode>, _>>()?;
+
+    Ok(Repeater {
+        callsign: row.callsign,
+        output_freq: freq(&row.output_freq, "output_freq")?,
+        input_freq: row.input_freq.map(|f| freq(&f, "input_freq")).transpose()?,
+        modes,
+        status: row.status.parse()?,
+        irlp_node: row.irlp_node,
+        echolink_node: row.echolink_node,
+        updated: row.updated,
+        code_in: code(row.code_in.as_ref(), "code_in")?,
+        code_out: code(row.code_out.as_ref(), "code_out")?,
+        dstar_module: match row.dstar_module.as_deref().map(|m| (m.len(), m.chars().next())) {
+            None => None,
+            Some((1, Some(m))) if ('A'..='D').contains(&m) => Some(m),
+            Some(_) => return Err(DStarModuleSnafu { value: row.dstar_module.unwrap_or_default() }.build().column("dstar_module")),
+        },
+        site: row.site,
+        place: row.place,
+        comment: row.comment,
+    })
+}
@@ -0,0 +1,142 @@
+use std::convert::TryFrom;
+
+use freqm::channel_file::*;
+use freqm::formats::{find, sniff};
+use freqm::icom_id51a::ChannelLine;
+use freqm::import::OnError;
+use freqm::repeater_db::{RepeaterDb, Source};
+use freqm::{Code, Repeater};
+
+mod common;
+
+fn records(repeaters: &[Repeater]) -> Vec<ChannelRecord> {
+    repeaters.iter().map(ChannelRecord::from).collect()
+}
+
+#[test]
+fn round_trip() {
+    for (format, path) in [("ne", "data/NERepeaters.csv"), ("at878", "data/at878uvii_3.06_export_channels.csv")] {
+        let (repeaters, report) = find(format).unwrap().load(path, OnError::Continue).unwrap();
+        assert!(report.skipped.is_empty(), "{}: {}", path, report.skipped[0].error);
+        assert!(!repeaters.is_empty());
+
+        for id in ["json", "yaml"] {
+            let channel_file = find(id).unwrap();
+            let mut out = Vec::new();
+            let report = channel_file.write(&mut out, &repeaters).unwrap();
+            assert_eq!(report.written, repeaters.len(), "{} as {}", path, id);
+            assert!(report.unrepresented.is_empty(), "{} as {}: {}", path, id, report.unrepresented[0]);
+
+            let (back, report) = channel_file.read(&out, OnError::Abort).unwrap();
+            assert_eq!(report.imported, repeaters.len());
+            assert_eq!(records(&back), records(&repeaters), "{} as {}", path, id);
+        }
+
+        let mut out = Vec::new();
+        find("json").unwrap().write(&mut out, &repeaters).unwrap();
+        let sniffed = sniff(&out).unwrap();
+        assert_eq!((sniffed.format.id, sniffed.confidence), ("json", 1.0));
+    }
+}
+
+#[test]
+fn schema() {
+    let text = r#"{
+  "version": 1,
+  "channels": [
+    { "number": 7, "name": "Boston", "callsign": "W1BOS", "output_freq": "146.610", "input_freq": "146.010",
+      "modes": ["Fm"], "tone_in": "88.5", "place": { "town": "Boston", "state": "MA" } },
+    { "callsign": "W1DMR", "output_freq": "446.500", "input_freq": "441.500", "modes": ["Dmr"],
+      "dmr": { "color_code_in": 1, "color_code_out": 2 } },
+    { "callsign": "W1DV", "output_freq": "145.250", "modes": ["DStar"], "dstar": { "module": "C" } }
+  ]
+}"#;
+    let file = ChannelFile::from_json(text.as_bytes()).unwrap();
+    assert_eq!(file.channels[0].number, Some(7));
+    let (repeaters, _) = file.repeaters(OnError::Abort).unwrap();
+    assert_eq!(repeaters[0].code_in(), Some(Code::Ctcss(885)));
+    assert_eq!(repeaters[0].place().unwrap().to_string(), "Boston, MA");
+    assert_eq!((repeaters[1].code_in(), repeaters[1].code_out()), (Some(Code::ColorCode(1)), Some(Code::ColorCode(2))));
+    assert_eq!(ChannelRecord::from(&repeaters[1]).dmr, file.channels[1].dmr);
+    assert_eq!(ChannelRecord::from(&repeaters[2]).dstar, Some(DStar { module: 'C' }));
+    // JSON is YAML
+    assert_eq!(ChannelFile::from_yaml(text.as_bytes()).unwrap(), file);
+
+    let newer = text.replace("\"version\": 1", "\"version\": 2");
+    let e = ChannelFile::from_json(newer.as_bytes()).unwrap_err();
+    assert!(e.to_string().contains("version 2"), "{}", e);
+    let typo = text.replace("tone_in", "tone_inn");
+    assert!(ChannelFile::from_json(typo.as_bytes()).is_err());
+    // modes are read in any case, but must be modes
+    let lower = ChannelFile::from_json(text.replace("[\"Dmr\"]", "[\"dmr\"]").as_bytes()).unwrap();
+    assert_eq!(lower, file);
+    let e = ChannelFile::from_json(text.replace("[\"Dmr\"]", "[\"Dmx\"]").as_bytes()).unwrap_err();
+    assert!(e.to_string().contains("mode \"Dmx\" unrecognized"), "{}", e);
+
+    for (from, to, column) in [
+        ("\"tone_in\": \"88.5\"", "\"tone_in\": \"CC1\"", "tone_in"),
+        ("\"modes\": [\"Dmr\"],", "\"modes\": [\"Dmr\"], \"tone_in\": \"88.5\",", "dmr"),
+        ("\"module\": \"C\"", "\"module\": \"Z\"", "dstar"),
+        ("\"modes\": [\"DStar\"]", "\"modes\": [\"Fm\"]", "dstar"),
+    ] {
+        let bad = ChannelFile::from_json(text.replace(from, to).as_bytes()).unwrap();
+        let e = bad.repeaters(OnError::Abort).unwrap_err();
+        assert_eq!(e.location().unwrap().column, Some(column), "{}", e);
+        assert!(e.location().unwrap().record.is_some(), "{}", e);
+
+        let (repeaters, report) = bad.repeaters(OnError::Continue).unwrap();
+        assert_eq!((repeaters.len(), report.skipped.len()), (2, 1));
+    }
+}
+
+#[test]
+fn dstar_module() {
+    // modules come from the listing, which doesn't always use the band's usual one
+    let lines = r#""145.600","S","MA","North Attleborough","D-STAR    ","K1WIZ","B","",,"Bristol","","","","2014/05/08",
+"145.670","S","MA","Boston","D-STAR    ","W1DV","","",,"Suffolk","","","","2014/05/08",
+"#;
+    let ne = common::ne(lines);
+    assert_eq!(ne[0].dstar_module(), Some('B'));
+    assert_eq!(ChannelRecord::from(&ne[0]).dstar, Some(DStar { module: 'B' }));
+    assert_eq!(ne[1].dstar_module(), None);
+    assert_eq!(ChannelRecord::from(&ne[1]).dstar, Some(DStar { module: 'C' }));
+
+    let file = ChannelFile::new(records(&ne));
+    let (back, _) = file.repeaters(OnError::Abort).unwrap();
+    assert_eq!(back[0].dstar_module(), Some('B'));
+
+    // and are written back to the listing
+    let mut out = Vec::new();
+    find("ne").unwrap().write(&mut out, &ne).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("K1WIZ,B,"));
+}
+
+#[test]
+fn provenance() {
+    let (ne, _) = find("ne")
+        .unwrap()
+        .read(br#""146.790","-","MA","Weston","","N1BE","146.2","",,"Middlesex","4136","","","2016/02/15","#, OnError::Abort)
+        .unwrap();
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Weston", "MA", "N1BE", "", "146.79", "DUP-", "0.6", "FM", "TONE", "100.0Hz", "Yes", "Exact", "42.365",
+        "-71.295", "-05:00",
+    ]);
+    let icom = Repeater::try_from(&ChannelLine::try_from(record).unwrap()).unwrap();
+
+    let mut db = RepeaterDb::new(vec![]);
+    db.add(Source::Ne, ne.into_iter().next().unwrap());
+    db.add(Source::Icom, icom);
+    let file = ChannelFile::from_db(&db);
+    assert_eq!(file.version, VERSION);
+
+    let provenance = file.channels[0].provenance.as_ref().unwrap();
+    assert_eq!(provenance.sources, vec![Source::Ne, Source::Icom]);
+    assert_eq!(provenance.fields["tone_in"], Source::Ne);
+    assert_eq!(provenance.fields["site"], Source::Icom);
+    assert!(!provenance.fields.contains_key("dmr"));
+    assert_eq!(provenance.conflicts, vec!["code_in: 146.2 (ne), 100.0 (icom)"]);
+
+    let mut out = Vec::new();
+    file.write_json(&mut out).unwrap();
+    assert_eq!(ChannelFile::from_json(&out).unwrap(), file);
+}
@@ -0,0 +1,8 @@
+use freqm::formats::find;
+use freqm::import::OnError;
+use freqm::Repeater;
+
+/// The repeaters in rows of the NE repeater listing
+pub fn ne(rows: &str) -> Vec<Repeater> {
+    find("ne").unwrap().read(rows.as_bytes(), OnError::Abort).unwrap().0
+}
@@ -0,0 +1,48 @@
+use freqm::diff::*;
+
+mod common;
+
+const OLD: &str = r#""146.610","-","MA","Boston","","W1BOS","88.5","","","Suffolk","","","","2019/01/01",
+"147.000","+","MA","Acton","","K1ABC","100.0","","","Middlesex","","","","2019/01/01",
+"29.680","-","MA","Marlborough","","W1MRA","131.8","","","Middlesex","","","","2019/01/01",
+"29.680","-","MA","Weston","","W1MRA","131.8","","","Middlesex","","","","2019/01/01",
+"#;
+
+const NEW: &str = r#""146.500","-","MA","Boston","","W1NEW","88.5","","","Suffolk","","","","2020/02/02",
+"146.61","-","MA","Boston","","w1bos","88.5","","","Suffolk","","","","2019/01/01",
+"29.680","-","MA","Marlborough","","W1MRA","131.8","","OFF","Middlesex","","","","2020/02/02",
+"29.680","-","MA","Weston","","W1MRA","131.8","","","Middlesex","","","","2019/01/01",
+"#;
+
+#[test]
+fn lists() {
+    let diff = diff(&common::ne(OLD), &common::ne(NEW));
+    let mut text = Vec::new();
+    diff.write_text(&mut text).unwrap();
+    assert_eq!(
+        String::from_utf8(text).unwrap(),
+        "removed: K1ABC 147.000 (Acton, MA)
+added: W1NEW 146.500 (Boston, MA)
+modified: W1MRA 29.680 (Marlborough, MA): status: On -> Off
+modified: W1MRA 29.680 (Marlborough, MA): updated: 2019-01-01 -> 2020-02-02
+added 1, removed 1, modified 1, unchanged 2
+"
+    );
+
+    let json = serde_json::to_value(&diff).unwrap();
+    assert_eq!(json["removed"][0]["callsign"], "K1ABC");
+    assert_eq!(json["modified"][0]["changes"][0], serde_json::json!({ "field": "status", "old": "On", "new": "Off" }));
+    assert_eq!(json["unchanged"], 2);
+
+    assert!(freqm::diff::diff(&common::ne(OLD), &common::ne(OLD)).is_empty());
+}
+
+#[test]
+fn fields() {
+    let old = common::ne(r#""147.000","+","MA","Acton","","K1ABC","100.0","","","Middlesex","","","",
+"#);
+    let new = common::ne(r#""147.000","*","MA","Acton","","K1ABC","","","","Middlesex","","","*Input: 147.6000",
+"#);
+    let changes: Vec<String> = changes(&old[0], &new[0]).iter().map(|c| c.to_string()).collect();
+    assert_eq!(changes, ["code_in: 100.0 -> none"]);
+}
@@ -0,0 +1,82 @@
+use freqm::filter::Filter;
+use freqm::formats::find;
+use freqm::import::OnError;
+use freqm::Repeater;
+
+mod common;
+
+const NE: &str = r#""29.640","-","CT","Bristol","","KB1CDI","","88.5",,"Hartford","","13782","RX in Terryville CT","2012/06/09",
+"29.640","-","RI","Providence","","N1BS","67.0","","OFF","Providence","","","","2019/06/10",
+"145.230","-","MA","Boston","DMR","W1BOS","CC1","CC1","","Suffolk","","","","2021/03/01",
+"146.610","-","MA","Waltham","","W1XYZ","","","","Middlesex","","","",
+"447.775","-","MA","New Haven","","K1ABC","D023","","","New Haven","","","","2020/01/01",
+"#;
+
+/// Callsigns of the repeaters matching `filter`
+fn select(filter: &str) -> Vec<String> {
+    let filter: Filter = filter.parse().unwrap();
+    filter.select(&common::ne(NE)).map(|r| r.callsign().to_owned()).collect()
+}
+
+#[test]
+fn tests() {
+    assert_eq!(select("band = 10m"), ["KB1CDI", "N1BS"]);
+    assert_eq!(select("band != 10m"), ["W1BOS", "W1XYZ", "K1ABC"]);
+    assert_eq!(select("freq in 144..148"), ["W1BOS", "W1XYZ"]);
+    assert_eq!(select("freq >= 146.61"), ["W1XYZ", "K1ABC"]);
+    assert_eq!(select("mode = dmr"), ["W1BOS"]);
+    assert_eq!(select("mode = analog and band = 2m"), ["W1XYZ"]);
+    // a color code isn't a tone
+    assert_eq!(select("tone"), ["N1BS", "K1ABC"]);
+    assert_eq!(select("tone = D023"), ["K1ABC"]);
+    assert_eq!(select("state = ma"), ["W1BOS", "W1XYZ", "K1ABC"]);
+    assert_eq!(select(r#"county = "new haven""#), ["K1ABC"]);
+    assert_eq!(select("town ~ *o?"), ["KB1CDI", "W1BOS"]);
+    assert_eq!(select("status = off"), ["N1BS"]);
+    assert_eq!(select("callsign ~ w1*"), ["W1BOS", "W1XYZ"]);
+    // repeaters without an update date fail date tests either way
+    assert_eq!(select("updated >= 2020-01-01"), ["W1BOS", "K1ABC"]);
+    assert_eq!(select("updated < 2020-01-01"), ["KB1CDI", "N1BS"]);
+    assert!(select("within 1000km of FN42").is_empty(), "NE records have no position");
+    assert_eq!(select("").len(), 5);
+}
+
+#[test]
+fn precedence() {
+    assert_eq!(select("state = MA and band = 2m or status = off"), ["N1BS", "W1BOS", "W1XYZ"]);
+    assert_eq!(select("state = MA and (band = 2m or status = off)"), ["W1BOS", "W1XYZ"]);
+    assert_eq!(select("not tone and not mode = digital"), ["KB1CDI", "W1XYZ"]);
+    assert_eq!(select("NOT (tone OR state = CT)"), ["W1BOS", "W1XYZ"]);
+}
+
+#[test]
+fn within() {
+    let (repeaters, _) = find("icom").unwrap().read(
+        b"Group No,Group Name,Name,Sub Name,Repeater Call Sign,Gateway Call Sign,Frequency,Dup,Offset,Mode,TONE,Repeater Tone,RPT1USE,Position,Latitude,Longitude,UTC Offset
+1,Repeaters,Boston,MA,W1BOS  B,W1BOS  G,442.5,DUP+,5,DV,OFF,88.5Hz,Yes,Exact,42.3601,-71.0589,-05:00
+1,Repeaters,Springfield,MA,W1SPR  B,W1SPR  G,443.5,DUP+,5,DV,OFF,88.5Hz,Yes,Exact,42.1015,-72.5898,-05:00
+",
+        OnError::Abort,
+    )
+    .unwrap();
+    let filter: Filter = "within 20mi of 42.36,-71.06 and town = boston".parse().unwrap();
+    let found: Vec<&Repeater> = filter.select(&repeaters).collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].callsign(), "W1BOS");
+    let filter: Filter = "within 100km of FN42".parse().unwrap();
+    assert_eq!(filter.select(repeaters).count(), 1);
+}
+
+#[test]
+fn errors() {
+    let error = |text: &str| text.parse::<Filter>().unwrap_err().to_string();
+    assert_eq!(error("band = 3m"), r#"column where: "band = 3m": band "3m" unknown"#);
+    assert_eq!(error("colour = red"), r#"column where: "colour = red": unknown field "colour""#);
+    assert_eq!(error("band < 2m"), r#"column where: "band < 2m": "<" can't be used with band"#);
+    assert_eq!(error("(tone"), r#"column where: "(tone": missing ")""#);
+    assert_eq!(error("tone and"), r#"column where: "tone and": expected a test at the end"#);
+    assert_eq!(error("tone tone"), r#"column where: "tone tone": unexpected "tone""#);
+    assert_eq!(error("callsign ="), r#"column where: "callsign =": expected a value after "callsign =""#);
+    assert!(error("within 10 of FN42").contains("not a distance"));
+    assert!(error("county = \"New").contains("unterminated quote"));
+}
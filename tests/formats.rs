@@ -0,0 +1,130 @@
+use freqm::formats::*;
+use freqm::import::OnError;
+use freqm::{Code, Mode};
+
+const CHIRP: &str = "\
+Location,Name,Frequency,Duplex,Offset,Tone,rToneFreq,cToneFreq,DtcsCode,DtcsPolarity,RxDtcsCode,CrossMode,Mode,TStep,Skip,Power,Comment,URCALL,RPT1CALL,RPT2CALL,DVCODE
+0,W1ABC,146.610000,-,0.600000,TSQL,88.5,100.0,023,NN,023,Tone->Tone,FM,5.00,,50W,,,,,
+1,W1XYZ,442.500000,+,5.000000,Cross,88.5,88.5,023,NN,754,Tone->DTCS,NFM,5.00,,50W,,,,,
+";
+
+const ICS: &str = "\
+\"Ch #\",Function,\"Channel Name/Trunked Radio System Talkgroup\",Assignment,RX Freq      N or W,RX Tone/NAC,TX Freq      N or W,\"TX Tone/NAC\",\"Mode A or D\",Remarks
+1,Start Area Logistics,S1,Hopkinton Wide Area,447.775 W,88.5,442.775 W,88.5,A,Hopkinton (2025 Freq Change)
+2,Medical,M2,RESTRICTED - medical only,146.520 W,CSQ,146.520 W,CSQ,A,
+,,,,,,,,,
+3,Command,CMD,Command net,453.100 N,293,458.100 N,293,D,P25
+";
+
+#[test]
+fn sniff_formats() {
+    let best = |bytes: &[u8]| sniff(bytes).map(|s| (s.format.id, (s.confidence * 100.0).round()));
+    assert_eq!(best(CHIRP.as_bytes()), Some(("chirp", 100.0)));
+    assert_eq!(best(ICS.as_bytes()), Some(("ics", 100.0)));
+    assert_eq!(best(include_bytes!("../data/at878uvii_3.06_export_channels.csv")), Some(("at878", 100.0)));
+    let (id, confidence) = best(include_bytes!("../data/NERepeaters.csv")).unwrap();
+    assert!(id == "ne" && confidence > 90.0, "{} {}", id, confidence);
+
+    // an older Kenwood list, without the model and aux columns, and a bad row
+    let kenwood = "Wn\tWorld Region\tCn\tCountry\tGn\tGroup\tCallsign\tGateway\tLockout\tName\tSub Name\tFrequency\tShift\tOffset\tMode\tUplink Tone\tDownlink Tone\tPosition\tLat DD\tLat MM.mm\tN/S\tLon DDD\tLon MM.mm\tE/W\tTime Zone
+1\tNA\t1\tUSA\t1\tMA\tW1XYZ\t\tOff\tCambridge\tMA\t146.61\t-\t0.6\tFM\tOn\t88.5\tNone\t\t\t\t\t\t\t-5
+1\tNA\t1\tUSA\t1\tMA\tW1ABC\t\tOff\tBoston\tMA\t146.82\t-\t0.6\tAM\tOff\t\tNone\t\t\t\t\t\t\t-5
+";
+    let found = sniff_all(kenwood.as_bytes());
+    assert_eq!(found.len(), 1, "only Kenwood lists are tab separated");
+    assert_eq!(found[0].format.id, "kenwood");
+    assert!((found[0].confidence - (0.6 * 25.0 / 31.0 + 0.4 * 0.5)).abs() < 1e-9, "{:?}", found[0]);
+
+    // a couple of matching column names isn't convincing
+    let (_, confidence) = best(b"name,frequency\nW1ABC,146.61\n").unwrap();
+    assert!(confidence < 10.0, "{}", confidence);
+    assert!(sniff(b"").is_none());
+
+    // everything we write is recognized
+    for format in FORMATS {
+        let mut out = Vec::new();
+        let (repeaters, _) = find("chirp").unwrap().read(CHIRP.as_bytes(), OnError::Abort).unwrap();
+        format.write(&mut out, &repeaters).unwrap();
+        assert_eq!(best(&out), Some((format.id, 100.0)));
+    }
+    assert!(find("IcOm").is_ok());
+    assert!(find("csv").is_err());
+}
+
+#[test]
+fn ics_to_chirp() {
+    let (repeaters, report) = find("ics").unwrap().read(ICS.as_bytes(), OnError::Abort).unwrap();
+    // the restricted channel and the blank line aren't channels
+    assert_eq!(report.imported, 2);
+    assert_eq!(repeaters[0].callsign(), "S1");
+    assert_eq!(repeaters[0].input_freq().unwrap().to_string(), "442.775");
+    assert_eq!(repeaters[1].modes(), &[Mode::P25]);
+    assert_eq!(repeaters[1].code_in(), Some(Code::Nac(0x293)));
+
+    let mut out = Vec::new();
+    let report = find("chirp").unwrap().write(&mut out, &repeaters).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert_eq!(
+        text.lines().nth(1).unwrap(),
+        "1,S1,447.775000,-,5.000000,TSQL,88.5,88.5,023,NN,023,Tone->Tone,FM,5.00,,,\
+         Start Area Logistics | Hopkinton Wide Area | Hopkinton (2025 Freq Change),,,,"
+    );
+    assert!(report.unrepresented.is_empty(), "{:?}", report);
+
+    // and back, with the comment split into its columns again
+    let mut out = Vec::new();
+    find("ics").unwrap().write(&mut out, &repeaters).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains(",Start Area Logistics,S1,Hopkinton Wide Area,"), "{}", text);
+    assert!(text.contains(",A,Hopkinton (2025 Freq Change)"), "{}", text);
+    assert_eq!(report.written, 1);
+    assert_eq!(report.left_out[0].to_string(), "CMD 453.1: no P25 channels");
+}
+
+#[test]
+fn reports_unrepresented() {
+    let (repeaters, _) = find("chirp").unwrap().read(CHIRP.as_bytes(), OnError::Abort).unwrap();
+    assert_eq!(repeaters[0].code_in(), Some(Code::Ctcss(1000)));
+    assert_eq!(repeaters[1].code_in(), Some(Code::Ctcss(885)));
+    assert_eq!(repeaters[1].code_out(), Some(Code::Dcs(0o754)));
+
+    // chirp itself holds everything
+    let report = find("chirp").unwrap().write(Vec::new(), &repeaters).unwrap();
+    assert!(report.unrepresented.is_empty(), "{:?}", report);
+
+    // Kenwood lists only hold the tone sent to the repeater
+    let report = find("kenwood").unwrap().write(Vec::new(), &repeaters).unwrap();
+    let lost: Vec<String> = report.unrepresented.iter().map(|u| u.to_string()).collect();
+    assert_eq!(lost, ["W1ABC 146.61: code_out 100.0", "W1XYZ 442.5: code_out D754"]);
+
+    // analog channels in an AT-878 list keep their codes, DMR channels can't go to chirp
+    let (repeaters, _) = find("at878")
+        .unwrap()
+        .read(include_bytes!("../data/at878uvii_3.06_export_channels.csv"), OnError::Abort)
+        .unwrap();
+    assert_eq!(repeaters.len(), 11);
+    assert_eq!(repeaters[3].modes(), &[Mode::Dmr, Mode::Fm]);
+    let report = find("chirp").unwrap().write(Vec::new(), &repeaters).unwrap();
+    assert_eq!(report.written, 7);
+    assert_eq!(report.left_out.len(), 4);
+    assert_eq!(report.unrepresented[0].to_string(), "Channel 3 446.575: modes Dmr");
+}
+
+#[test]
+fn kenwood_export_is_stable() {
+    let kenwood = "Wn\tWorld Region\tCn\tCountry\tGn\tGroup\tCallsign\tGateway\tLockout\tName\tSub Name\tFrequency\tShift\tOffset\tMode\tUplink Tone\tDownlink Tone\tPosition\tLat DD\tLat MM.mm\tN/S\tLon DDD\tLon MM.mm\tE/W\tTime Zone
+1\tNA\t1\tUSA\t1\tMA\tW1XYZ\t\tOff\tCambridge\tMA\t146.61\t-\t0.6\tFM\tOn\t88.5\tExact\t42\t21.61\tN\t071\t03.53\tW\t-5
+";
+    let format = find("kenwood").unwrap();
+    let round_trip = |bytes: &[u8]| {
+        let (repeaters, _) = format.read(bytes, OnError::Abort).unwrap();
+        let mut out = Vec::new();
+        format.write(&mut out, &repeaters).unwrap();
+        out
+    };
+
+    let first = round_trip(kenwood.as_bytes());
+    let second = round_trip(&first);
+    assert!(String::from_utf8_lossy(&first).lines().nth(1).unwrap().contains("\tCambridge\tMA\t"));
+    assert_eq!(String::from_utf8(second).unwrap(), String::from_utf8(first).unwrap());
+}
@@ -0,0 +1,68 @@
+use std::convert::TryInto;
+
+use freqm::gazetteer::Gazetteer;
+use freqm::geo::{LatLon, Located};
+use freqm::ne_repeater::NeRepeaterRecord;
+use freqm::Repeater;
+
+// trimmed from the census county subdivision gazetteer, including its padded last header
+const COUSUBS: &str = "USPS\tGEOID\tANSICODE\tNAME\tFUNCSTAT\tALAND\tAWATER\tALAND_SQMI\tAWATER_SQMI\tINTPTLAT\tINTPTLONG       
+MA\t2501774175\t00618254\tMarlborough city\tA\t54463466\t2425542\t21.028\t0.937\t42.350909\t-71.547479
+MA\t2501778865\t00619445\tWeston town\tA\t43924034\t1248916\t16.959\t0.482\t42.358880\t-71.301530
+CT\t0900308980\t00213394\tBristol city\tA\t68455013\t611023\t26.431\t0.236\t41.681578\t-72.940749
+NH\t3300342180\t00873499\tMount Washington town\tA\t1\t0\t0.000\t0.000\t44.270000\t-71.300000
+";
+
+fn gazetteer() -> Gazetteer {
+    let mut g = Gazetteer::new();
+    g.read(COUSUBS.as_bytes()).unwrap();
+    g.read(b"state,town,lat,lon\nCT,Terryville,41.6787,-73.0090\nMA,Weston,0,0\n").unwrap();
+    g
+}
+
+#[test]
+fn lookup() {
+    let g = gazetteer();
+    assert_eq!(g.len(), 5);
+    assert_eq!(g.lookup("MA", "Weston"), Some(LatLon { lat: 42.358880, lon: -71.301530 }));
+    assert_eq!(g.lookup("ma", "  MARLBOROUGH "), Some(LatLon { lat: 42.350909, lon: -71.547479 }));
+    assert_eq!(g.lookup("NH", "Mt. Washington"), Some(LatLon { lat: 44.27, lon: -71.3 }));
+    assert_eq!(g.lookup_place("Terryville, CT"), Some(LatLon { lat: 41.6787, lon: -73.0090 }));
+    assert_eq!(g.lookup("CT", "Weston"), None);
+
+    let mut g = Gazetteer::new();
+    let e = g.read(b"state,town,latitude,lon\n").unwrap_err();
+    assert!(e.to_string().contains("\"lat\""), "{}", e);
+    let e = g.read(b"state,town,lat,lon\nMA,Boston,north,-71\n").unwrap_err();
+    assert_eq!(e.location().unwrap().line, Some(2));
+}
+
+#[test]
+fn ne_sites() {
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(
+            r#""29.640","-","CT","Bristol","","KB1CDI","","88.5",,"Hartford","","13782","RX in Terryville CT, Donkey Dusters Net","2012/06/09",
+"146.955","-","CT","Morris","","KB1CDI","100.0","",,"Litchfield","","13782","Donkey Dusters Net","2014/02/23",
+"#
+            .as_bytes(),
+        );
+    let records: Vec<NeRepeaterRecord> = csv.records().map(|r| r.unwrap().try_into().unwrap()).collect();
+    let g = gazetteer();
+
+    let site = records[0].site(&g).unwrap();
+    assert!(site.is_approximate());
+    assert_eq!(site.location(), "Bristol, CT");
+    assert_eq!(site.grid().as_str(), "FN31mq");
+    assert!(records[1].site(&g).is_none());
+
+    let mut repeater: Repeater = records[0].clone().try_into().unwrap();
+    assert_eq!(repeater.position(), None);
+    repeater.set_site(Some(site));
+    assert_eq!(repeater.position(), Some(LatLon { lat: 41.681578, lon: -72.940749 }));
+
+    // approximate positions are exported as such
+    let line: freqm::icom_id51a::ChannelLine = (&repeater).into();
+    assert_eq!(line.position, "Approximate");
+}
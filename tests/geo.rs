@@ -0,0 +1,160 @@
+use std::convert::TryFrom;
+
+use freqm::geo::*;
+
+const BOSTON: LatLon = LatLon { lat: 42.3601, lon: -71.0589 };
+const NEW_YORK: LatLon = LatLon { lat: 40.7128, lon: -74.0060 };
+
+#[test]
+fn distance_and_bearing() {
+    let d = BOSTON.distance(&NEW_YORK);
+    assert!((d.as_km() - 306.1).abs() < 1.0, "{}", d);
+    assert!((d.as_miles() - 190.2).abs() < 1.0, "{}", d);
+    assert_eq!(BOSTON.distance(&BOSTON).as_km(), 0.0);
+
+    let b = BOSTON.bearing(&NEW_YORK);
+    assert!((b - 235.0).abs() < 1.0, "{}", b);
+    assert_eq!(compass(b), "SW");
+    assert_eq!(compass(0.0), "N");
+    assert_eq!(compass(355.0), "N");
+    assert_eq!(compass(100.0), "E");
+}
+
+#[test]
+fn parse() {
+    assert_eq!("42.5, -71.25".parse::<LatLon>().unwrap(), LatLon { lat: 42.5, lon: -71.25 });
+    assert!("91,0".parse::<LatLon>().is_err());
+    assert!("42.5".parse::<LatLon>().is_err());
+
+    let d: Distance = "30mi".parse().unwrap();
+    assert_eq!(d.unit(), DistanceUnit::Mi);
+    assert!((d.as_km() - 48.28).abs() < 0.01);
+    assert_eq!(d.to_string(), "30.0mi");
+    assert_eq!("2.5 km".parse::<Distance>().unwrap(), Distance::km(2.5));
+    // the unit is only how it's shown
+    assert_eq!(d.in_unit(DistanceUnit::Km), d);
+    assert_eq!(d.partial_cmp(&d.in_unit(DistanceUnit::Km)), Some(std::cmp::Ordering::Equal));
+    assert!("30".parse::<Distance>().is_err());
+    assert!("-1km".parse::<Distance>().is_err());
+    assert!("3 furlongs".parse::<Distance>().is_err());
+}
+
+#[test]
+fn near_sorts_and_limits() {
+    let worcester = LatLon { lat: 42.2626, lon: -71.8023 };
+    let providence = LatLon { lat: 41.8240, lon: -71.4128 };
+    let items = [NEW_YORK, providence, BOSTON, worcester];
+
+    let found = near(&items, BOSTON, "50mi".parse().unwrap());
+    let positions: Vec<LatLon> = found.iter().map(|n| *n.item).collect();
+    assert_eq!(positions, vec![BOSTON, worcester, providence]);
+
+    // distances are in the radius' unit
+    assert_eq!(found[1].distance.unit(), DistanceUnit::Mi);
+    assert!((found[1].distance.value() - 38.4).abs() < 0.5, "{}", found[1].distance);
+    assert_eq!(compass(found[1].bearing), "W");
+}
+
+#[test]
+fn listing_positions() {
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Boston", "MA", "W1BOS  B", "W1BOS  G", "145.230000", "DUP-", "0.600000", "DV", "OFF", "88.5Hz",
+        "Yes", "Approximate", "42.360100", "-71.058900", "-05:00",
+    ]);
+    let line = freqm::icom_id51a::ChannelLine::try_from(record).unwrap();
+    assert_eq!(line.position(), Some(BOSTON));
+
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Boston", "MA", "W1BOS  B", "W1BOS  G", "145.230000", "DUP-", "0.600000", "DV", "OFF", "88.5Hz",
+        "Yes", "None", "0", "0", "-05:00",
+    ]);
+    let line = freqm::icom_id51a::ChannelLine::try_from(record).unwrap();
+    assert_eq!(line.position(), None);
+
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Boston", "MA", "W1BOS  B", "W1BOS  G", "fast", "DUP-", "0.600000", "DV", "OFF", "88.5Hz",
+        "Yes", "None", "0", "0", "-05:00",
+    ]);
+    let e = freqm::icom_id51a::ChannelLine::try_from(record).unwrap_err();
+    assert_eq!(e.location().unwrap().column, Some("Frequency"));
+}
+
+#[test]
+fn grid_locators() {
+    assert_eq!(Grid::of(BOSTON, 4).as_str(), "FN42");
+    assert_eq!(Grid::of(BOSTON, 6).as_str(), "FN42li");
+    assert_eq!(Grid::of(BOSTON, 8).as_str(), "FN42li26");
+    assert_eq!(Grid::of(LatLon { lat: 90.0, lon: 180.0 }, 6).as_str(), "RR99xx");
+    assert_eq!(Grid::of(LatLon { lat: -90.0, lon: -180.0 }, 4).as_str(), "AA00");
+
+    let g: Grid = "fn42LI".parse().unwrap();
+    assert_eq!(g.to_string(), "FN42li");
+    let (sw, ne) = g.bounds();
+    assert!((sw.lat - 42.333).abs() < 0.001 && (sw.lon + 71.083).abs() < 0.001, "{}", sw);
+    assert!((ne.lat - 42.375).abs() < 0.001 && (ne.lon + 71.0).abs() < 0.001, "{}", ne);
+    assert!(g.contains(&BOSTON));
+    assert!(!g.contains(&NEW_YORK));
+    assert!("FN42".parse::<Grid>().unwrap().contains(&g.center()));
+
+    for bad in ["FN4", "SN42", "FN4A", "FN42yy", "FN42li8x", ""] {
+        assert!(bad.parse::<Grid>().is_err(), "{}", bad);
+    }
+
+    // positions may be given as grid squares
+    let center: LatLon = "FN42".parse().unwrap();
+    assert_eq!(center, LatLon { lat: 42.5, lon: -71.0 });
+}
+
+#[test]
+fn exports_include_grid() {
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Boston", "MA", "W1BOS  B", "W1BOS  G", "145.23", "DUP-", "0.6", "DV", "OFF", "88.5Hz", "Yes",
+        "None", "0", "0", "-05:00",
+    ]);
+    let mut line = freqm::icom_id51a::ChannelLine::try_from(record).unwrap();
+    line.set_position(Some(BOSTON), true);
+
+    let mut out = Vec::new();
+    freqm::icom_id51a::write_csv(&mut out, &[line]).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("Group No,Group Name,"));
+    assert!(text.lines().nth(1).unwrap().ends_with(",Exact,42.3601,-71.0589,-05:00,FN42li"), "{}", text);
+
+    let mut tsv = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(false)
+        .from_reader("1\tNA\t1\tUSA\t1\tMA\tW1XYZ\t\tOff\tCambridge\tMA\t146.61\t-\t0.6\tFM\tOn\t88.5\tNone\t\t\t\t\t\t\t-5\n".as_bytes());
+    let mut row: freqm::csv::KenwoodTh74aRow = tsv.deserialize().next().unwrap().unwrap();
+    assert_eq!(row.position(), None);
+    row.set_position(Some(BOSTON), false);
+    assert_eq!((row.lat_dd.as_str(), row.lat_mm_mm.as_str(), row.n_s.as_str()), ("42", "21.61", "N"));
+    assert_eq!((row.lon_ddd.as_str(), row.lon_mm_mm.as_str(), row.e_w.as_str()), ("071", "03.53", "W"));
+    assert!(Grid::of(BOSTON, 8).contains(&row.position().unwrap()));
+
+    let mut out = Vec::new();
+    freqm::csv::write_kenwood_tsv(&mut out, &[row]).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("Wn\tWorld Region\t"));
+    assert!(text.lines().nth(1).unwrap().contains("\tApproximate\t42\t21.61\tN\t071\t03.53\tW\t-5\t\t\t\tFN42li\t\t"), "{}", text);
+}
+
+#[test]
+fn icom_export_is_stable() {
+    use freqm::icom_id51a::{write_csv, ChannelLine};
+    use freqm::Repeater;
+
+    let record = csv::StringRecord::from(vec![
+        "1", "Canada", "Saint John", "New Brunswick", "VE9SJ", "", "147.03", "DUP+", "0.6", "FM", "TONE", "88.5Hz",
+        "Yes", "Exact", "45.2733", "-66.0633", "-04:00",
+    ]);
+    let write = |line: &ChannelLine| {
+        let mut out = Vec::new();
+        write_csv(&mut out, std::slice::from_ref(line)).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let first = ChannelLine::from(&Repeater::try_from(&ChannelLine::try_from(record).unwrap()).unwrap());
+    let second = ChannelLine::from(&Repeater::try_from(&first).unwrap());
+    assert_eq!(first.sub_name, "New Brunswick");
+    assert_eq!(write(&second), write(&first));
+}
@@ -0,0 +1,84 @@
+use freqm::hexdump::{self, Annotations, DumpOptions};
+use freqm::sparse_mem::SparseMem;
+
+fn text(f: impl FnOnce(&mut Vec<u8>)) -> String {
+    let mut out = Vec::new();
+    f(&mut out);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn dump_gaps() {
+    let mut s = SparseMem::default();
+    s.insert(0x0e, b"freqm!").unwrap();
+    s.insert(0x100, &[0, 1, 0xff]).unwrap();
+
+    let out = text(|w| hexdump::dump(w, &s, &DumpOptions::default()).unwrap());
+    assert_eq!(
+        out,
+        "00000000                                             66 72 |              fr|\n\
+         00000010  65 71 6d 21                                      |eqm!            |\n\
+         -- gap 0x20..0x100 (224 bytes) --\n\
+         00000100  00 01 ff                                         |...             |\n"
+    );
+}
+
+#[test]
+fn dump_range_ends_in_gap() {
+    let mut s = SparseMem::default();
+    s.insert(0, &[1]).unwrap();
+    s.insert(0x200, &[2]).unwrap();
+
+    let opts = DumpOptions { range: Some(0x100..0x108), ..Default::default() };
+    let out = text(|w| hexdump::dump(w, &s, &opts).unwrap());
+    assert_eq!(out, "-- gap 0x100..0x108 (8 bytes) --\n");
+}
+
+#[test]
+fn dump_annotated() {
+    let mut s = SparseMem::default();
+    s.insert(0, &[0; 32]).unwrap();
+    let ann: Annotations = "# test\n0x0+2 flags\n0x12..0x20 name\n".parse().unwrap();
+
+    let out = text(|w| {
+        let opts = DumpOptions { range: Some(0x10..0x100), annotations: Some(&ann), color: false };
+        hexdump::dump(w, &s, &opts).unwrap()
+    });
+    assert_eq!(out.lines().count(), 1);
+    assert!(out.ends_with("|  name\n"), "{}", out);
+}
+
+#[test]
+fn bad_annotation() {
+    let e = "0x0+2 flags\n\n0x12-0x20 name\n".parse::<Annotations>().unwrap_err();
+    let location = e.location().unwrap();
+    assert_eq!((location.line, location.record), (Some(3), Some(2)));
+}
+
+#[test]
+fn diff_marks_changes() {
+    let mut a = SparseMem::default();
+    a.insert(0, &[0; 64]).unwrap();
+    let mut b = SparseMem::default();
+    b.insert(0, &[0; 64]).unwrap();
+    b.write(0x21, &[1]).unwrap();
+    b.insert(0x40, &[2]).unwrap();
+    let ann: Annotations = "0x20+4 squelch\n".parse().unwrap();
+
+    let mut changed = 0;
+    let out = text(|w| {
+        let opts = DumpOptions { annotations: Some(&ann), ..Default::default() };
+        changed = hexdump::diff(w, &a, &b, &opts).unwrap();
+    });
+    assert_eq!(changed, 2);
+    assert_eq!(
+        out,
+        "-00000020  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00 |................|\n\
+         +00000020  00 01 00 00 00 00 00 00  00 00 00 00 00 00 00 00 |................|  squelch\n\
+         \x20             ^^\n\
+         ...\n\
+         -00000040                                                   |                |\n\
+         +00000040  02                                               |.               |\n\
+         \x20          ^^\n"
+    );
+}
@@ -0,0 +1,107 @@
+use freqm::error::FormatError;
+use freqm::image::{self, ImageFormat};
+use freqm::sparse_mem::SparseMem;
+use freqm::FreqmError;
+
+fn sample() -> SparseMem {
+    let mut s = SparseMem::default();
+    s.insert(0x10, &(0..40).collect::<Vec<u8>>()).unwrap();
+    s.insert(0x1_fff8, &[0xaa; 16]).unwrap();
+    s
+}
+
+fn roundtrip(mem: &SparseMem, format: ImageFormat) -> SparseMem {
+    let mut out = Vec::new();
+    image::write(mem, format, &mut out).unwrap();
+    image::read(&out, format).unwrap()
+}
+
+#[test]
+fn record_formats_keep_gaps() {
+    let s = sample();
+
+    for format in [ImageFormat::IntelHex, ImageFormat::SRecord] {
+        let r = roundtrip(&s, format);
+        assert_eq!(r.ranges().collect::<Vec<_>>(), s.ranges().collect::<Vec<_>>(), "{:?}", format);
+    }
+}
+
+#[test]
+fn raw_fills_gaps() {
+    let mut s = SparseMem::default();
+    s.insert(0x102, &[1, 2]).unwrap();
+    s.insert(0x105, &[5]).unwrap();
+
+    let mut out = Vec::new();
+    image::write(&s, ImageFormat::Raw { base: 0x100 }, &mut out).unwrap();
+    assert_eq!(out, [0xff, 0xff, 1, 2, 0xff, 5]);
+
+    let r = image::read(&out, ImageFormat::Raw { base: 0x100 }).unwrap();
+    assert_eq!(r.ranges().collect::<Vec<_>>(), vec![(0x100, &out[..])]);
+
+    let e = image::write(&s, ImageFormat::Raw { base: 0x103 }, &mut Vec::new()).unwrap_err();
+    assert!(matches!(e, FreqmError::Format { source: FormatError::BelowBase { addr: 0x102, base: 0x103 }, .. }));
+}
+
+#[test]
+fn ihex_known() {
+    let text = b":0400100001020304E2\n:020000040001F9\n:02000000AABB99\n:00000001FF\n";
+    let s = image::read(text, ImageFormat::IntelHex).unwrap();
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0x10, &[1, 2, 3, 4][..]), (0x1_0000, &[0xaa, 0xbb][..])]);
+
+    let mut out = Vec::new();
+    image::write(&s, ImageFormat::IntelHex, &mut out).unwrap();
+    assert_eq!(out, text);
+}
+
+#[test]
+fn ihex_bad_checksum() {
+    let text = b":0400100001020304E2\n:0400100001020304E3\n";
+    let e = image::read(text, ImageFormat::IntelHex).unwrap_err();
+    assert_eq!(e.location().unwrap().line, Some(2));
+    assert!(matches!(e, FreqmError::Format { source: FormatError::ImageChecksum { found: 0xe3, expected: 0xe2 }, .. }));
+}
+
+#[test]
+fn srec_known() {
+    let text = b"S0080000667265716DDC\nS107001001020304DE\nS5030001FB\nS9030000FC\n";
+    let s = image::read(text, ImageFormat::SRecord).unwrap();
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0x10, &[1, 2, 3, 4][..])]);
+
+    let mut out = Vec::new();
+    image::write(&s, ImageFormat::SRecord, &mut out).unwrap();
+    assert_eq!(out, text);
+}
+
+#[test]
+fn chirp_img_metadata() {
+    let mut s = SparseMem::default();
+    s.insert(0, &[1, 2, 3]).unwrap();
+    let md = image::ChirpMetadata {
+        rclass: "AnyToneTERMN8RRadio".to_owned(),
+        vendor: "AnyTone".to_owned(),
+        model: "TERMN-8R".to_owned(),
+        ..Default::default()
+    };
+
+    let mut out = Vec::new();
+    image::write_chirp_img(&s, Some(&md), &mut out).unwrap();
+    assert!(out.starts_with(&[1, 2, 3, 0x00, 0xff, b'c']));
+
+    let (r, r_md) = image::read_chirp_img(&out).unwrap();
+    assert_eq!(r.ranges().collect::<Vec<_>>(), s.ranges().collect::<Vec<_>>());
+    assert_eq!(r_md, Some(md.clone()));
+
+    // plain dumps without metadata are accepted too
+    let (r, r_md) = image::read_chirp_img(&[1, 2, 3]).unwrap();
+    assert_eq!(r.ranges().collect::<Vec<_>>(), s.ranges().collect::<Vec<_>>());
+    assert_eq!(r_md, None);
+
+    // saving a loaded .img keeps its metadata
+    let path = std::env::temp_dir().join(format!("freqm-chirp-{}.img", std::process::id()));
+    image::save_with_metadata(&s, Some(&md), &path, ImageFormat::ChirpImg).unwrap();
+    let (r, r_md) = image::load_with_metadata(&path, ImageFormat::ChirpImg).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(r.ranges().collect::<Vec<_>>(), s.ranges().collect::<Vec<_>>());
+    assert_eq!(r_md, Some(md));
+}
@@ -0,0 +1,129 @@
+use freqm::formats::find;
+use freqm::layout::*;
+use freqm::Repeater;
+
+mod common;
+
+const NE: &str = r#""447.775","-","MA","Waltham","","K1ABC","88.5","","","Middlesex","","","",
+"146.610","-","MA","Boston","","W1BOS","","","","Suffolk","","","",
+"145.230","-","MA","Cambridge","","W1XYZ","","","","Middlesex","","","",
+"146.790","-","MA","Weston","","W1DEF","","","","Middlesex","","","",
+"29.640","-","RI","Providence","","N1BS","67.0","","","","","","",
+"#;
+
+/// "<number> <group> <callsign>" for each channel
+fn show(plan: &Plan<'_>) -> Vec<String> {
+    plan.channels
+        .iter()
+        .map(|c| {
+            let group = plan.group_of(c).map(|g| g.name.as_str()).unwrap_or("-");
+            format!("{} {} {}", c.number, group, c.repeater.callsign())
+        })
+        .collect()
+}
+
+#[test]
+fn sort_and_number() {
+    let repeaters = common::ne(NE);
+    let all: Vec<&Repeater> = repeaters.iter().collect();
+
+    let plan = Layout::default().plan(&all);
+    assert_eq!(show(&plan), ["1 - K1ABC", "2 - W1BOS", "3 - W1XYZ", "4 - W1DEF", "5 - N1BS"]);
+    assert!(plan.groups.is_empty());
+
+    let layout = Layout {
+        sort: vec!["freq".parse().unwrap()],
+        first: 3,
+        reserved: vec![parse_range("4-5").unwrap(), parse_range("8").unwrap()],
+        ..Default::default()
+    };
+    assert_eq!(show(&layout.plan(&all)), ["3 - N1BS", "6 - W1XYZ", "7 - W1BOS", "9 - W1DEF", "10 - K1ABC"]);
+
+    let layout = Layout { sort: vec![SortKey::Name], ..Default::default() };
+    assert_eq!(show(&layout.plan(&all))[..2], ["1 - W1BOS", "2 - W1XYZ"]);
+
+    assert!(parse_range("5-1").is_err());
+    assert!("size".parse::<SortKey>().is_err());
+}
+
+#[test]
+fn groups() {
+    let repeaters = common::ne(NE);
+    let all: Vec<&Repeater> = repeaters.iter().collect();
+
+    // groups are in the order they first appear, and big ones are split
+    let layout = Layout {
+        sort: vec![SortKey::Frequency],
+        group_by: Some(GroupBy::County),
+        group_size: Some(2),
+        ..Default::default()
+    };
+    let plan = layout.plan(&all);
+    assert_eq!(
+        show(&plan),
+        ["1 Other N1BS", "2 Middlesex 1 W1XYZ", "3 Middlesex 1 W1DEF", "4 Middlesex 2 K1ABC", "5 Suffolk W1BOS"]
+    );
+    assert_eq!(plan.groups[1], Group { name: "Middlesex 1".to_owned(), channels: vec![1, 2] });
+
+    let layout = Layout { group_by: Some("band".parse().unwrap()), ..Default::default() };
+    let names: Vec<String> = layout.plan(&all).groups.into_iter().map(|g| g.name).collect();
+    assert_eq!(names, ["70cm", "2m", "10m"]);
+
+    // the limit keeps the first channels in sort order, not input order
+    let layout = Layout { sort: vec![SortKey::Frequency], limit: Some(3), ..layout };
+    assert_eq!(show(&layout.plan(&all)), ["1 10m N1BS", "2 2m W1XYZ", "3 2m W1BOS"]);
+}
+
+#[test]
+fn groups_in_formats() {
+    let repeaters = common::ne(NE);
+    let layout = Layout { group_by: Some(GroupBy::Band), ..Default::default() };
+
+    // the ID-51A's group columns
+    let (written, _) = find("icom").unwrap().check(&repeaters);
+    let mut out = Vec::new();
+    find("icom").unwrap().write_plan(&mut out, &layout.plan(&written)).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.lines().nth(1).unwrap().starts_with("1,70cm,Waltham,MA,K1ABC,"), "{}", text);
+    assert!(text.lines().nth(2).unwrap().starts_with("2,2m,Boston,MA,W1BOS,"), "{}", text);
+
+    // the AT-878's zone list
+    let at878 = find("at878").unwrap();
+    let (written, _) = at878.check(&repeaters);
+    let plan = layout.plan(&written);
+    let mut out = Vec::new();
+    at878.write_groups(&mut out, &plan).unwrap();
+    let zones = String::from_utf8(out).unwrap();
+    assert_eq!(
+        zones.lines().nth(2).unwrap(),
+        "2,2m,W1BOS|W1XYZ|W1DEF,146.61000|145.23000|146.79000,146.01000|144.63000|146.19000,W1BOS,146.61000,146.01000,W1BOS,146.61000,146.01000"
+    );
+
+    // and a scan list for each zone, which its channels name
+    let mut out = Vec::new();
+    at878.write_scan_lists(&mut out, &plan).unwrap();
+    let scan_lists = String::from_utf8(out).unwrap();
+    assert_eq!(
+        scan_lists.lines().nth(2).unwrap(),
+        "2,2m,W1BOS|W1XYZ|W1DEF,146.61000|145.23000|146.79000,146.01000|144.63000|146.19000,Off,Off,Off,,,Off,,,Selected,2.0,3.0,3.1,3.1"
+    );
+    let mut out = Vec::new();
+    at878.write_plan(&mut out, &plan).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let scan_list = text.lines().next().unwrap().split(',').position(|c| c == "Scan List").unwrap();
+    let names: Vec<&str> = text.lines().skip(1).map(|l| l.split(',').nth(scan_list).unwrap()).collect();
+    assert_eq!(names, ["70cm", "2m", "2m", "2m", "10m"]);
+
+    // the radio's scan lists are smaller than its zones
+    let big = Layout { group_by: Some(GroupBy::State), ..Default::default() };
+    let many: Vec<&Repeater> = std::iter::repeat_n(written[1], 51).collect();
+    let e = at878.write_scan_lists(&mut Vec::new(), &big.plan(&many)).unwrap_err();
+    assert!(e.to_string().contains("51 channels, more than the 50"), "{}", e);
+
+    // chirp has no groups, but is numbered by the plan
+    let mut out = Vec::new();
+    let chirp = find("chirp").unwrap();
+    let layout = Layout { first: 10, ..layout };
+    chirp.write_plan(&mut out, &layout.plan(&chirp.check(&repeaters).0)).unwrap();
+    assert!(String::from_utf8(out).unwrap().lines().nth(2).unwrap().starts_with("11,W1BOS,"));
+}
@@ -0,0 +1,81 @@
+use freqm::formats::find;
+use freqm::import::OnError;
+use freqm::lint::*;
+use freqm::Location;
+
+mod common;
+
+const NE: &str = r#""146.610","-","MA","Boston","","W1BOS","88.5","","","Suffolk","","","",
+"146.610","-","MA","Boston","","W1BOS","88.5","","","Suffolk","","","",
+"147.000","*","MA","Acton","","K1ABC","100.0","","","Middlesex","","","*Input: 146.100",
+"449.000","-","MA","Acton","","K1XYZ","150.0","","","Middlesex","","","",
+"147.300","*","MA","Acton","","N1DEF","","","","Middlesex","","","*Input: 442.300",
+"#;
+
+fn show(findings: &[Finding]) -> Vec<String> {
+    findings.iter().map(|f| f.to_string()).collect()
+}
+
+#[test]
+fn checks() {
+    let repeaters = common::ne(NE);
+    let findings = lint(&Check::ALL, &repeaters);
+    assert_eq!(
+        show(&findings),
+        [
+            "record 2: warning: W1BOS 146.61: same channel as record 1 [duplicate]",
+            "record 3: warning: K1ABC 147: offset -0.9 MHz is not the band's standard 0.6 MHz [offset]",
+            "record 4: error: K1XYZ 449: input tone 150.0 is not a standard CTCSS tone [tone]",
+            "record 5: error: N1DEF 147.3: input 442.3 is outside the 2m band [band]",
+            "record 5: warning: N1DEF 147.3: offset 295 MHz is not the band's standard 0.6 MHz [offset]",
+        ]
+    );
+
+    // only the checks asked for
+    let findings = lint(&[Check::Tone], &repeaters);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].severity(), Severity::Error);
+}
+
+#[test]
+fn color_codes() {
+    let (repeaters, _) = find("at878").unwrap().read(include_bytes!("../data/at878uvii_3.06_export_channels.csv"), OnError::Abort).unwrap();
+    assert!(lint(&[Check::ColorCode], &repeaters).is_empty());
+
+    let ne = concat!(
+        r#""442.500","+","MA","Acton","DMR","W1DMR","","","","Middlesex","","","","#,
+        "\n",
+        r#""443.500","+","MA","Acton","DMR","W1CC","CC3","","","Middlesex","","","","#,
+    );
+    let repeaters = common::ne(ne);
+    assert_eq!(show(&lint(&[Check::ColorCode], &repeaters)), ["record 1: error: W1DMR 442.5: DMR without a color code [color-code]"]);
+}
+
+#[test]
+fn check_names() {
+    assert_eq!("color-code".parse::<Check>().unwrap(), Check::ColorCode);
+    assert_eq!("Offset".parse::<Check>().unwrap(), Check::Offset);
+    let e = "spelling".parse::<Check>().unwrap_err();
+    assert_eq!(e.to_string(), "column check: lint check \"spelling\" unknown");
+}
+
+#[test]
+fn records_skip_rows_that_arent_channels() {
+    // a heading and a restricted channel between the channels
+    let ics = "\
+\"Ch #\",Function,\"Channel Name/Trunked Radio System Talkgroup\",Assignment,RX Freq      N or W,RX Tone/NAC,TX Freq      N or W,\"TX Tone/NAC\",\"Mode A or D\",Remarks
+1,Logistics,S1,Hopkinton,447.775 W,88.5,442.775 W,88.5,A,
+,Medical,,,,,,,,
+2,Medical,M2,RESTRICTED - medical only,146.520 W,CSQ,146.520 W,CSQ,A,
+3,Command,CMD,Command net,146.550 W,CSQ,146.550 W,CSQ,A,
+4,Command,CMD2,Backup net,146.550 W,CSQ,146.550 W,CSQ,A,
+";
+    let (repeaters, report) = find("ics").unwrap().read(ics.as_bytes(), OnError::Abort).unwrap();
+    assert_eq!(report.records, [1, 4, 5]);
+
+    let located = report.records.iter().zip(&repeaters).map(|(&n, r)| (Location { record: Some(n), ..Default::default() }, r));
+    assert_eq!(
+        show(&lint_records(&[Check::Duplicate], located)),
+        ["record 5: warning: CMD2 146.55: same channel as record 4 [duplicate]"]
+    );
+}
@@ -0,0 +1,175 @@
+use freqm::error::MapError;
+use freqm::memory_map::{MemoryMap, Value};
+use freqm::sparse_mem::SparseMem;
+
+const MAP: &str = r#"
+name = "test"
+
+[enums.power]
+low = 0
+mid = 1
+high = 2
+
+[structs.channel]
+size = 16
+field = [
+    { name = "rx_freq", at = 0, type = "bcd", bytes = 4, decimals = 5, blank = 0xff },
+    { name = "offset", at = 4, type = "bcd", bytes = 2, endian = "big", decimals = 2 },
+    { name = "power", at = 6, type = "uint", bit = 0, width = 2, enum = "power" },
+    { name = "scan", at = 6, type = "uint", bit = 7, width = 1 },
+    { name = "tone", at = 7, type = "int", bytes = 1 },
+    { name = "name", at = 8, type = "str", len = 6, pad = 0xff },
+]
+
+[[field]]
+name = "model"
+at = 0
+type = "str"
+len = 4
+encoding = "charset"
+charset = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+
+[[field]]
+name = "count"
+at = 4
+type = "uint"
+bytes = 2
+endian = "big"
+
+[[field]]
+name = "channels"
+at = 0x10
+type = "struct"
+struct = "channel"
+count = 2
+"#;
+
+fn image() -> SparseMem {
+    let mut s = SparseMem::default();
+    s.insert(0, &[0x11, 0x0e, 0x1b, 0x16, 0x01, 0x02]).unwrap();
+    s.insert(
+        0x10,
+        &[
+            0x00, 0x20, 0x65, 0x14, 0x00, 0x60, 0x81, 0xfe, b'M', b'U', b'R', b'S', 0xff, 0xff,
+            0x00, 0x00, //
+            0xff, 0xff, 0xff, 0xff, 0x1a, 0x00, 0x03, 0x00,
+        ],
+    )
+    .unwrap();
+    s
+}
+
+#[test]
+fn decode() {
+    let map: MemoryMap = MAP.parse().unwrap();
+    let values = map.decode(&image());
+
+    let get = |p: &str| values.iter().find(|(path, _)| path == p).unwrap().1.to_string();
+    assert_eq!(get("model"), "\"HERM\"");
+    assert_eq!(get("count"), "258");
+    assert_eq!(get("channels[0].rx_freq"), "146.52000");
+    assert_eq!(get("channels[0].offset"), "0.60");
+    assert_eq!(get("channels[0].power"), "mid");
+    assert_eq!(get("channels[0].scan"), "1");
+    assert_eq!(get("channels[0].tone"), "-2");
+    assert_eq!(get("channels[0].name"), "\"MURS\"");
+    assert_eq!(get("channels[1].rx_freq"), "-");
+    assert_eq!(get("channels[1].offset"), "invalid [1a, 00]");
+    assert_eq!(get("channels[1].power"), "3");
+    assert_eq!(get("channels[1].name"), "?");
+    assert_eq!(values.len(), 2 + 2 * 6);
+}
+
+#[test]
+fn set() {
+    let map: MemoryMap = MAP.parse().unwrap();
+    let mut s = image();
+
+    map.set(&mut s, "channels[0].rx_freq", "446.1").unwrap();
+    map.set(&mut s, "channels[0].power", "high").unwrap();
+    map.set(&mut s, "channels[0].tone", "5").unwrap();
+    map.set(&mut s, "channels[0].name", "GMRS").unwrap();
+    map.set(&mut s, "channels[1].rx_freq", "-").unwrap();
+    map.set(&mut s, "model", "AB12").unwrap();
+    map.set(&mut s, "channels[1].name", "x").unwrap();
+
+    assert_eq!(map.get(&s, "channels[0].rx_freq").unwrap(), Value::Decimal("446.10000".parse().unwrap()));
+    assert_eq!(&s[0x10..0x14], &[0x00, 0x00, 0x61, 0x44]);
+    // the scan bit is kept
+    assert_eq!(s[0x16..0x17], [0x82]);
+    assert_eq!(map.get(&s, "channels[0].tone").unwrap(), Value::Int(5));
+    assert_eq!(&s[0x18..0x1e], b"GMRS\xff\xff");
+    assert_eq!(map.get(&s, "model").unwrap(), Value::Str("AB12".to_owned()));
+    // writing into a gap populates it
+    assert_eq!(&s[0x28..0x2e], b"x\xff\xff\xff\xff\xff");
+}
+
+#[test]
+fn set_errors() {
+    let map: MemoryMap = MAP.parse().unwrap();
+    let mut s = image();
+
+    let value_error = |path: &str, value: &str| match map.set(&mut s.clone(), path, value) {
+        Err(freqm::FreqmError::Map { source: MapError::Value { reason, .. } }) => reason,
+        other => panic!("{:?}", other),
+    };
+
+    assert_eq!(value_error("channels[0].rx_freq", "146.520001"), "is not a decimal with that precision");
+    assert_eq!(value_error("channels[0].rx_freq", "1000"), "is too large");
+    assert_eq!(value_error("channels[0].offset", "-"), "can't be blank");
+    assert_eq!(value_error("channels[0].power", "max"), "is not a number or enum variant");
+    assert_eq!(value_error("channels[0].power", "4"), "is too large");
+    assert_eq!(value_error("channels[0].tone", "128"), "is out of range");
+    assert_eq!(value_error("channels[0].name", "TOOLONG"), "is too long");
+    assert_eq!(value_error("model", "ab"), "has characters not in the charset");
+
+    assert!(matches!(
+        map.set(&mut s, "channels[2].name", "x"),
+        Err(freqm::FreqmError::Map { source: MapError::UnknownField { .. } })
+    ));
+}
+
+#[test]
+fn bad_definitions() {
+    let reason = |text: &str| match text.parse::<MemoryMap>() {
+        Err(MapError::Definition { name, reason }) => format!("{}: {}", name, reason),
+        other => panic!("{:?}", other),
+    };
+
+    assert_eq!(
+        reason("[[field]]\nname = \"a\"\nat = 0\ntype = \"uint\"\nbit = 6\nwidth = 3\n"),
+        "a: bitfield doesn't fit in its bytes"
+    );
+    assert_eq!(
+        reason("[[field]]\nname = \"a\"\nat = 0\ntype = \"struct\"\nstruct = \"b\"\n"),
+        "a: no struct \"b\""
+    );
+    assert_eq!(
+        reason("[structs.s]\nfield = [{ name = \"s\", at = 0, type = \"struct\", struct = \"s\" }]\n[[field]]\nname = \"a\"\nat = 0\ntype = \"struct\"\nstruct = \"s\"\n"),
+        "s.s: struct \"s\" contains itself"
+    );
+    assert!(matches!("[[field]]\nname = 1\n".parse::<MemoryMap>(), Err(MapError::Toml { .. })));
+}
+
+#[test]
+fn annotations() {
+    let map: MemoryMap = MAP.parse().unwrap();
+    let ann = map.annotations();
+    let names: Vec<&str> = ann.containing(0x16).map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["channels[0].power", "channels[0].scan"]);
+    assert_eq!(ann.starting_in(0x20..0x30).count(), 6);
+}
+
+#[test]
+fn anytone_termn8r() {
+    let map: MemoryMap = include_str!("../maps/anytone_termn8r.toml").parse().unwrap();
+
+    // from a download, see the capture in anytone_ht.rs
+    let mut s = SparseMem::default();
+    s.insert(0x10, b"\x11\x04TERMN8R\x04\x00\x00\x00\xf4\x00\x00").unwrap();
+    s.insert(0x20, &[0xff; 16]).unwrap();
+    s.insert(0x30, b"2015-2-11\x00\x00\x00\x00\x00\x00\x00").unwrap();
+
+    assert_eq!(map.get(&s, "model").unwrap(), Value::Str("TERMN8R".to_owned()));
+    assert_eq!(map.get(&s, "firmware_date").unwrap(), Value::Str("2015-2-11".to_owned()));
+}
@@ -0,0 +1,94 @@
+use freqm::formats::find;
+use freqm::layout::Layout;
+use freqm::names::*;
+use freqm::Repeater;
+
+mod common;
+
+const NE: &str = r#""447.775","-","MA","North Andover","","K1ABC","88.5","","","Essex","","","",
+"146.610","-","MA","Boston","","W1BOS","","","","Suffolk","","","",
+"145.230","-","MA","Boston","","W1BOS","","","","Suffolk","","","",
+"#;
+
+#[test]
+fn templates() {
+    let repeaters = common::ne(NE);
+    let t: Template = "{callsign} {town}, {state} {tone}".parse().unwrap();
+    assert_eq!(t.name(&repeaters[0]), "K1ABC North Andover, MA 88.5");
+    // missing fields don't leave double spaces
+    assert_eq!(t.name(&repeaters[1]), "W1BOS Boston, MA");
+
+    let t: Template = "{{{band}}} {callsign:3} {town:4}".parse().unwrap();
+    assert_eq!(t.name(&repeaters[1]), "{2m} W1B Bstn");
+
+    assert!("{callsign".parse::<Template>().is_err());
+    assert!("callsign}".parse::<Template>().is_err());
+    assert!("{call}".parse::<Template>().is_err());
+    let e = "{town:x}".parse::<Template>().unwrap_err();
+    assert!(e.to_string().contains("width"), "{}", e);
+}
+
+#[test]
+fn towns() {
+    assert_eq!(abbreviate("North Andover", 16), "North Andover");
+    assert_eq!(abbreviate("North Andover", 10), "N Andover");
+    assert_eq!(abbreviate("North Andover", 8), "NAndover");
+    assert_eq!(abbreviate("North Andover", 6), "NAndvr");
+    assert_eq!(abbreviate("Boston", 4), "Bstn");
+    assert_eq!(abbreviate("Mount Washington", 3), "MtW");
+}
+
+#[test]
+fn rules() {
+    let upper = NameRules { len: 7, chars: Charset::Upper };
+    assert_eq!(upper.fit("w1bos Boston"), "W1BOS B");
+    assert_eq!(upper.fit("K1ABC (N)"), "K1ABC N");
+    let ascii = NameRules { len: 16, chars: Charset::Ascii };
+    assert_eq!(ascii.fit("Zürich  rptr"), "Zrich rptr");
+    assert_eq!(NameRules::UNLIMITED.fit("a long name with spaces"), "a long name with spaces");
+}
+
+#[test]
+fn unique_names() {
+    let repeaters = common::ne(NE);
+    let all: Vec<&Repeater> = repeaters.iter().collect();
+    let t: Template = "{callsign}".parse().unwrap();
+
+    let mut plan = Layout::default().plan(&all);
+    name_channels(&mut plan, &t, &NameRules { len: 16, chars: Charset::Ascii });
+    let names: Vec<_> = plan.channels.iter().map(|c| c.name.clone().unwrap()).collect();
+    assert_eq!(names, ["K1ABC", "W1BOS", "W1BOS 2"]);
+
+    // no room for a space, or for the whole name
+    let mut plan = Layout::default().plan(&all);
+    name_channels(&mut plan, &t, &NameRules { len: 5, chars: Charset::Ascii });
+    let names: Vec<_> = plan.channels.iter().map(|c| c.name.clone().unwrap()).collect();
+    assert_eq!(names, ["K1ABC", "W1BOS", "W1BO2"]);
+}
+
+#[test]
+fn named_lists() {
+    let repeaters = common::ne(NE);
+    let t: Template = "{callsign} {town:6}".parse().unwrap();
+    let uv5r = freqm::models::find("uv5r").unwrap();
+    let chirp = uv5r.format.list_format();
+    assert_eq!(chirp.id, "chirp");
+
+    let (written, _) = chirp.check(&repeaters);
+    let mut plan = Layout::default().plan(&written);
+    name_channels(&mut plan, &t, &uv5r.names);
+    let mut out = Vec::new();
+    chirp.write_plan(&mut out, &plan).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.lines().nth(2).unwrap().starts_with("2,W1BOS B,"), "{}", text);
+    assert!(text.lines().nth(3).unwrap().starts_with("3,W1BOS2,"), "{}", text);
+
+    let icom = find("icom").unwrap();
+    let (written, _) = icom.check(&repeaters);
+    let mut plan = Layout::default().plan(&written);
+    name_channels(&mut plan, &t, &freqm::models::find("id51a").unwrap().names);
+    let mut out = Vec::new();
+    icom.write_plan(&mut out, &plan).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.lines().nth(1).unwrap().contains(",K1ABC NAndvr,"), "{}", text);
+}
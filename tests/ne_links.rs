@@ -0,0 +1,60 @@
+use std::convert::TryInto;
+
+use freqm::ne_links::*;
+use freqm::ne_repeater::NeRepeaterRecord;
+
+const LISTING: &str = r#""29.640","-","CT","Bristol","","KB1CDI","","88.5",,"Hartford","","13782","RX in Terryville CT, Donkey Dusters Net","2012/06/09",
+"29.640","-","CT","Terryville",,"KB1CDI","88.5",,,,,,"Remote RX for KB1CDI Bristol, CT"
+"29.640","-","RI","Providence","","N1BS","67.0","","OFF","Providence","","","","2019/06/10",
+"29.680","-","MA","Marlborough","","W1MRA","131.8","","OFF","Middlesex","","","[MMRA Net],[MMRA2 Net],[146.790 Weston]","2019/11/26",
+"146.790","-","MA","Weston","","N1BE","146.2","",,"Middlesex","4136","","[MMRA Net],[MMRA2 Net],[29.680 Marlborough]","2016/02/15",
+"146.955","-","CT","Morris","","KB1CDI","100.0","",,"Litchfield","","13782","Donkey Dusters Net","2014/02/23",
+"#;
+
+fn graph() -> LinkGraph {
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(LISTING.as_bytes());
+
+    let records: Vec<NeRepeaterRecord> = csv
+        .records()
+        .map(|r| r.unwrap().try_into().unwrap())
+        .collect();
+
+    LinkGraph::new(&records)
+}
+
+#[test]
+fn edges() {
+    let g = graph();
+
+    assert_eq!(g.edges(), &[
+        LinkEdge { from: 1, to: 0, kind: EdgeKind::RemoteRx },
+        LinkEdge { from: 3, to: 4, kind: EdgeKind::Link { bracketed: true } },
+        LinkEdge { from: 4, to: 3, kind: EdgeKind::Link { bracketed: true } },
+    ]);
+    assert!(g.unresolved().is_empty());
+    assert_eq!(g.nets()["MMRA Net"], vec![3, 4]);
+}
+
+#[test]
+fn networks() {
+    let g = graph();
+
+    assert_eq!(g.networks(), vec![vec![0, 1, 5], vec![3, 4]]);
+    assert_eq!(g.network_of(2), vec![2]);
+    assert_eq!(g.find_callsign("kb1cdi"), vec![0, 1, 5]);
+}
+
+#[test]
+fn dot() {
+    let g = graph();
+    let mut out = Vec::new();
+    g.write_dot(&mut out, Some(&[3, 4])).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("digraph links {\n"));
+    assert!(out.contains("n3 -> n4 [dir=none, style=dashed];"));
+    assert!(!out.contains("n0 "));
+}
@@ -0,0 +1,224 @@
+use std::convert::TryInto;
+
+use freqm::ne_repeater::*;
+
+fn record(line: &str) -> NeRepeaterRecord {
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+
+    csv.records().next().unwrap().unwrap().try_into().unwrap()
+}
+
+fn town(town: &str, state: Option<&str>) -> NeTown {
+    NeTown {
+        town: town.to_owned(),
+        state: state.map(|s| s.to_owned()),
+    }
+}
+
+#[test]
+fn comments_custom_input() {
+    let r = record(r#""147.280","*","RI","Cranston","","N1NTP","67.0","",,"Providence","","","*Input: 147.885 (+605 kHz), Closed","2013/06/10","#);
+    let c = r.comments().unwrap();
+
+    assert_eq!(c.input_freq, Some(decimal::d128!(147.885)));
+    assert_eq!(c.notes, vec!["Closed".to_owned()]);
+    assert!(c.links.is_empty());
+}
+
+#[test]
+fn comments_remote_rx() {
+    let r = record(r#""29.640","-","CT","Terryville",,"KB1CDI","88.5",,,,,,"Remote RX for KB1CDI Bristol, CT""#);
+    let c = r.comments().unwrap();
+
+    assert_eq!(c.remote_rx, vec![NeRemoteRx {
+        callsign: "KB1CDI".to_owned(),
+        location: town("Bristol", Some("CT")),
+    }]);
+    assert!(c.notes.is_empty());
+}
+
+#[test]
+fn comments_nets_and_links() {
+    let c: LinksAndComments = "[MMRA Net],[MMRA2 Net],[146.790 Weston]".parse().unwrap();
+
+    assert_eq!(c.nets, vec![
+        NeNet { name: "MMRA Net".to_owned(), bracketed: true },
+        NeNet { name: "MMRA2 Net".to_owned(), bracketed: true },
+    ]);
+    assert_eq!(c.links, vec![NeLink {
+        freq: decimal::d128!(146.790),
+        locations: vec![town("Weston", None)],
+        bracketed: true,
+    }]);
+
+    let c: LinksAndComments = "53.450 (Vernon, CT),[W1HDN West Net]".parse().unwrap();
+    assert_eq!(c.links, vec![NeLink {
+        freq: decimal::d128!(53.450),
+        locations: vec![town("Vernon", Some("CT"))],
+        bracketed: false,
+    }]);
+    assert_eq!(c.nets, vec![NeNet { name: "W1HDN West Net".to_owned(), bracketed: true }]);
+
+    let c: LinksAndComments = "224.060 Claremont, NH".parse().unwrap();
+    assert_eq!(c.links[0].locations, vec![town("Claremont", Some("NH"))]);
+    assert!(c.notes.is_empty());
+
+    let c: LinksAndComments = "442.400 (Farmington, ME)  (Livermore Falls, ME)".parse().unwrap();
+    assert_eq!(c.links[0].locations, vec![
+        town("Farmington", Some("ME")),
+        town("Livermore Falls", Some("ME")),
+    ]);
+}
+
+#[test]
+fn comments_notes() {
+    let c: LinksAndComments = "Quahog Net,RB:2m/440, AllStar Node 50532".parse().unwrap();
+
+    assert_eq!(c.nets, vec![NeNet { name: "Quahog Net".to_owned(), bracketed: false }]);
+    assert_eq!(c.notes, vec!["RB:2m/440".to_owned(), "AllStar Node 50532".to_owned()]);
+
+    let c: LinksAndComments = "MMRA Net,[MMRA2 Net],88.5 linked/71.9 local".parse().unwrap();
+    assert!(c.links.is_empty());
+    assert_eq!(c.notes, vec!["88.5 linked/71.9 local".to_owned()]);
+}
+
+#[test]
+fn comments_bad_input() {
+    assert!("*Input: TBD".parse::<LinksAndComments>().is_err());
+}
+
+#[test]
+fn metadata() {
+    use freqm::{Mode, Status};
+
+    let r = record(r#""29.640","-","CT","Bristol","","KB1CDI","","88.5",,"Hartford","","13782","RX in Terryville CT, Donkey Dusters Net","2012/06/09","#);
+    assert_eq!(r.modes().unwrap(), vec![Mode::Fm]);
+    assert_eq!(r.status().unwrap(), Status::On);
+    assert_eq!(r.irlp_node().unwrap(), None);
+    assert_eq!(r.echolink_node().unwrap(), Some(13782));
+    assert_eq!(r.updated().unwrap(), chrono::NaiveDate::from_ymd_opt(2012, 6, 9));
+
+    let r = record(r#""29.640","-","RI","Providence","","N1BS","67.0","","OFF","Providence","","","","2019/06/10","#);
+    assert_eq!(r.status().unwrap(), Status::Off);
+
+    let r = record(r#""29.640","-","CT","Terryville",,"KB1CDI","88.5",,,,,,"Remote RX for KB1CDI Bristol, CT""#);
+    assert_eq!(r.updated().unwrap(), None);
+}
+
+#[test]
+fn modes() {
+    use freqm::Mode;
+
+    let modes = |mode: &str| {
+        let r = record(&format!(r#""146.610","-","MA","Waltham","{}","W1XYZ","","",,"Middlesex","","","","2020/01/01","#, mode));
+        r.modes()
+    };
+
+    assert_eq!(modes("NFM").unwrap(), vec![Mode::Nfm]);
+    assert_eq!(modes("DMR    ").unwrap(), vec![Mode::Dmr]);
+    assert_eq!(modes("YSF/FM ").unwrap(), vec![Mode::Ysf, Mode::Fm]);
+    assert_eq!(modes("P25/NFM").unwrap(), vec![Mode::P25, Mode::Nfm]);
+    assert_eq!(
+        modes("P25YSFD-STARNXDNDMR/FM ").unwrap(),
+        vec![Mode::P25, Mode::Ysf, Mode::DStar, Mode::Nxdn, Mode::Dmr, Mode::Fm]
+    );
+    assert!(modes("AM").is_err());
+}
+
+#[test]
+fn echolink_with_callsign() {
+    let r = record(r#""146.610","-","MA","Mendon","","K1KWP","146.2","",,"Worcester","4133","4133/W1MRA-R","MMRA Net,[MMRA2 Net]","2016/01/12","#);
+    assert_eq!(r.irlp_node().unwrap(), Some(4133));
+    assert_eq!(r.echolink_node().unwrap(), Some(4133));
+}
+
+#[test]
+fn import_report() {
+    use freqm::error::{FormatError, ValidationError};
+    use freqm::import::OnError;
+
+    let listing = r#""29.640","-","RI","Providence","","N1BS","67.0","","OFF","Providence","","","","2019/06/10",
+"29.640","-"
+"146.490","*","MA","Newton","DMR    ","N1PA","CC1","","OFF","Middlesex","","","Brandmeister DMR Net","2020/04/19",
+"999.000","+","MA","Newton","","N1PA","","",,"Middlesex","","","","2020/04/19",
+"#;
+
+    let err = import(listing.as_bytes(), OnError::Abort).unwrap_err();
+    let location = err.location().unwrap();
+    assert_eq!((location.line, location.record), (Some(2), Some(2)));
+    assert_eq!(err.to_string(), "line 2: record 2: wrong number of fields: have 2, expected 13 to 15");
+
+    let (records, report) = import(listing.as_bytes(), OnError::Continue).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(report.imported, 2);
+
+    let skipped: Vec<_> = report.skipped.iter().map(|s| s.line).collect();
+    assert_eq!(skipped, vec![2, 4]);
+    assert!(matches!(
+        report.skipped[0].error,
+        freqm::FreqmError::Format { source: FormatError::FieldCount { found: 2, .. }, .. }
+    ));
+    assert!(matches!(
+        report.skipped[1].error,
+        freqm::FreqmError::Validation { source: ValidationError::FreqNotInAnyBand { .. }, .. }
+    ));
+    assert_eq!(report.skipped[1].error.location().unwrap().column, Some("output_freq"));
+    assert_eq!(report.skipped[0].raw, "29.640,-");
+
+    assert_eq!(report.warnings.len(), 1);
+    assert_eq!(report.warnings[0].line, 3);
+    assert_eq!(report.to_string(), "imported 2, skipped 2, warned 1");
+}
+
+#[test]
+fn codes() {
+    use freqm::Code;
+
+    let code_in = |mode: &str, code: &str| {
+        let r = record(&format!(r#""146.610","-","MA","Waltham","{}","W1XYZ","{}","",,"Middlesex","","","","2020/01/01","#, mode, code));
+        r.code_in()
+    };
+
+    assert_eq!(code_in("", "67.0*").unwrap(), Some(Code::Ctcss(670)));
+    assert_eq!(code_in("", "D031*").unwrap(), Some(Code::Dcs(0o31)));
+    assert_eq!(code_in("", "71.9/127.3").unwrap(), Some(Code::Ctcss(719)));
+    assert_eq!(code_in("D-STAR/FM", "C/77.0").unwrap(), Some(Code::Ctcss(770)));
+    assert_eq!(code_in("P25NXDNDMR/FM", "NAC293/RAN1/CC9/67.0").unwrap(), Some(Code::Nac(0x293)));
+    assert_eq!(code_in("NXDN/FM", "RAN1/ 67.0").unwrap(), Some(Code::Ctcss(670)));
+    assert_eq!(code_in("D-STAR", "B").unwrap(), None);
+    assert_eq!(code_in("NXDN", "RAN1").unwrap(), None);
+    assert!(code_in("", "tone").is_err());
+}
+
+#[test]
+fn import_listing() {
+    use freqm::import::OnError;
+
+    let file = std::fs::File::open("data/NERepeaters.csv").unwrap();
+    let (records, report) = import(file, OnError::Abort).unwrap();
+    assert_eq!(records.len(), 1002);
+    assert!(report.skipped.is_empty());
+}
+
+#[test]
+fn import_report_with_header() {
+    use freqm::import::{import, OnError};
+
+    let text = "callsign,node\nW1ABC,4133\nW1XYZ,none\nW1BAD\nW1DEF,13782\n";
+    let mut csv = csv::ReaderBuilder::new().from_reader(text.as_bytes());
+    let (nodes, report) = import(&mut csv, OnError::Continue, |record, _| {
+        record[1].parse::<u32>().map_err(|_| freqm::FreqmError::Parse {
+            location: freqm::Location::column("node"),
+            source: freqm::error::ParseError::Node { value: record[1].to_owned() },
+        })
+    })
+    .unwrap();
+    assert_eq!(nodes, vec![4133, 13782]);
+
+    // records are numbered from the first after the header, lines from the top of the file
+    let rows: Vec<_> = report.skipped.iter().map(|s| s.error.location().map(|l| (l.line, l.record)).unwrap()).collect();
+    assert_eq!(rows, vec![(Some(3), Some(2)), (Some(4), Some(3))]);
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+use freqm::layout::{GroupBy, SortKey};
+use freqm::project::Project;
+
+const PROJECT: &str = r#"
+where = "state = MA"
+
+[inputs]
+ne_csv = ["NERepeaters.csv"]
+prefer = ["radioid", "ne"]
+lists = [{ path = "/plans/marathon.csv", format = "ics" }]
+
+[layout]
+sort = ["freq"]
+group_by = "band"
+reserve = ["1-10"]
+
+[[radio]]
+model = "uv5r"
+output = "out/uv5r.csv"
+name = "{callsign} {town:3}"
+
+[[radio]]
+format = "at878"
+output = "out/at878.csv"
+channels = 500
+layout = { sort = ["name"], group_by = "county", group_size = 64 }
+"#;
+
+#[test]
+fn parse() {
+    let mut project: Project = PROJECT.parse().unwrap();
+    assert_eq!(project.filter.as_ref().unwrap().to_string(), "state = MA");
+    assert_eq!(project.layout.layout().sort, [SortKey::Frequency]);
+    assert_eq!(project.layout.layout().first, 1);
+    assert_eq!(project.layout.reserve, [1..=10]);
+    assert_eq!(project.inputs.prefer[0].to_string(), "radioid");
+
+    let [uv5r, at878] = &project.radios[..] else { panic!("{:?}", project.radios) };
+    assert_eq!(uv5r.format().id, "chirp");
+    assert_eq!(uv5r.channels(), 128);
+    assert_eq!(at878.format().id, "at878");
+    assert_eq!(at878.channels(), 500);
+    assert_eq!(at878.layout.as_ref().unwrap().layout().group_by, Some(GroupBy::County));
+
+    project.resolve(Path::new("/projects/ma"));
+    assert_eq!(project.inputs.ne_csv, [PathBuf::from("/projects/ma/NERepeaters.csv")]);
+    assert_eq!(project.inputs.lists[0].path, Path::new("/plans/marathon.csv"));
+    assert_eq!(project.radios[0].output, Path::new("/projects/ma/out/uv5r.csv"));
+}
+
+#[test]
+fn errors() {
+    let bad = |text: &str| text.parse::<Project>().unwrap_err().to_string();
+
+    assert!(bad("[[radio]]\noutput = \"x.csv\"").contains("neither a model nor a format"));
+    assert!(bad("[[radio]]\nmodel = \"ft-8900\"\noutput = \"x.csv\"").contains("radio model \"ft-8900\" unknown"));
+    assert!(bad("where = \"band = 3m\"").contains("band \"3m\" unknown"));
+    assert!(bad("[layout]\nsort = [\"size\"]").contains("sort key"));
+    assert!(bad("[inputs]\nne = [\"x.csv\"]").contains("unknown field"));
+}
@@ -0,0 +1,111 @@
+use std::convert::TryFrom;
+
+use freqm::geo::Located;
+use freqm::icom_id51a::ChannelLine;
+use freqm::repeater_db::*;
+use freqm::{Code, Repeater, Site};
+
+fn icom(callsign: &str, freq: &str, tone: &str, lat: &str, lon: &str) -> Repeater {
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Weston", "MA", callsign, "", freq, "DUP-", "0.6", "FM", "TONE", tone, "Yes", "Exact", lat, lon,
+        "-05:00",
+    ]);
+    Repeater::try_from(&ChannelLine::try_from(record).unwrap()).unwrap()
+}
+
+fn ne(line: &str) -> Repeater {
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(line.as_bytes());
+    let record: freqm::ne_repeater::NeRepeaterRecord = csv.records().next().unwrap().unwrap().try_into().unwrap();
+    record.try_into().unwrap()
+}
+
+const WESTON: &str = r#""146.790","-","MA","Weston","","N1BE","146.2","",,"Middlesex","4136","","","2016/02/15","#;
+
+#[test]
+fn codes() {
+    for text in ["88.5", "D023", "CC1", "NAC293"] {
+        assert_eq!(text.parse::<Code>().unwrap().to_string(), text);
+    }
+    assert_eq!("100.0Hz".parse::<Code>().unwrap(), Code::Ctcss(1000));
+    assert_eq!("d754".parse::<Code>().unwrap(), Code::Dcs(0o754));
+    for bad in ["", "CC16", "D089", "12", "NACxyz"] {
+        assert!(bad.parse::<Code>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn merge_with_precedence() {
+    let mut db = RepeaterDb::new(vec![]);
+    let a = db.add(Source::Ne, ne(WESTON));
+    let b = db.add(Source::Icom, icom("N1BE", "146.79", "100.0Hz", "42.365", "-71.295"));
+    let c = db.add(Source::Icom, icom("N1BE", "146.79", "100.0Hz", "44.0", "-71.0"));
+    assert_eq!(a, b);
+    assert_ne!(a, c, "same callsign and frequency, but far apart");
+    assert_eq!(db.entries().len(), 2);
+
+    let e = &db.entries()[a];
+    assert_eq!(e.sources, vec![Source::Ne, Source::Icom]);
+    let code_in = e.code_in.as_ref().unwrap();
+    assert_eq!((code_in.value, code_in.source), (Code::Ctcss(1462), Source::Ne));
+    assert_eq!(code_in.conflicts, vec![(Source::Icom, Code::Ctcss(1000))]);
+    assert_eq!(e.input_freq.as_ref().unwrap().conflicts, vec![]);
+    assert_eq!(e.irlp_node.as_ref().unwrap().value, 4136);
+    assert_eq!(e.site.as_ref().unwrap().source, Source::Icom);
+    assert_eq!(e.conflicts().iter().map(|c| c.to_string()).collect::<Vec<_>>(), vec!["code_in: 146.2 (ne), 100.0 (icom)"]);
+
+    let r = e.repeater();
+    assert_eq!(r.code_in(), Some(Code::Ctcss(1462)));
+    assert_eq!(r.irlp_node(), Some(4136));
+    assert!(r.position().is_some());
+
+    // preferring icom flips which value is used
+    let mut db = RepeaterDb::new(vec![Source::Icom]);
+    db.add(Source::Ne, ne(WESTON));
+    db.add(Source::Icom, icom("n1be", "146.790", "100.0Hz", "42.365", "-71.295"));
+    let code_in = db.entries()[0].code_in.as_ref().unwrap();
+    assert_eq!((code_in.value, code_in.source), (Code::Ctcss(1000), Source::Icom));
+    assert_eq!(code_in.conflicts, vec![(Source::Ne, Code::Ctcss(1462))]);
+}
+
+#[test]
+fn exact_positions_win() {
+    let mut db = RepeaterDb::new(vec![Source::Ne]);
+    let mut approximate = ne(WESTON);
+    approximate.set_site(Some(Site::new("Weston", "Weston, MA", "42.3589,-71.3015".parse().unwrap()).approximate()));
+    db.add(Source::Icom, icom("N1BE", "146.79", "146.2Hz", "42.365", "-71.295"));
+    db.add(Source::Ne, approximate);
+
+    let site = db.entries()[0].site.as_ref().unwrap();
+    assert_eq!(site.source, Source::Icom);
+    assert!(!site.value.is_approximate());
+    assert!(db.entries()[0].conflicts().is_empty());
+}
+
+#[test]
+fn save_and_load() {
+    let mut db = RepeaterDb::new(vec![]);
+    db.add(Source::Ne, ne(WESTON));
+    db.add(Source::Icom, icom("N1BE", "146.79", "100.0Hz", "42.365", "-71.295"));
+
+    let path = std::env::temp_dir().join(format!("freqm-repeater-db-{}.json", std::process::id()));
+    db.save(&path).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.contains(r#""value": "146.2""#), "{}", text);
+
+    let mut loaded = RepeaterDb::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.precedence(), db.precedence());
+    assert_eq!(loaded.entries()[0].conflicts(), db.entries()[0].conflicts());
+
+    // the index is rebuilt, so new records still merge
+    assert_eq!(loaded.add(Source::Kenwood, icom("N1BE", "146.79", "146.2Hz", "42.365", "-71.295")), 0);
+
+    // databases from a newer freqm aren't read
+    std::fs::write(&path, text.replacen(r#""version": 1"#, r#""version": 2"#, 1)).unwrap();
+    let e = RepeaterDb::load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(e.to_string().contains("version 2 is newer"), "{}", e);
+}
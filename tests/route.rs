@@ -0,0 +1,102 @@
+use std::convert::TryFrom;
+
+use freqm::geo::LatLon;
+use freqm::route::*;
+
+const BOSTON: LatLon = LatLon { lat: 42.3601, lon: -71.0589 };
+const WORCESTER: LatLon = LatLon { lat: 42.2626, lon: -71.8023 };
+const SPRINGFIELD: LatLon = LatLon { lat: 42.1015, lon: -72.5898 };
+
+#[test]
+fn locate() {
+    let route = Route::new(vec![BOSTON, WORCESTER, SPRINGFIELD]).unwrap();
+    assert!((route.length().as_km() - 129.4).abs() < 1.0, "{}", route.length());
+
+    let at = route.locate(&WORCESTER);
+    assert!((at.along.as_km() - BOSTON.distance(&WORCESTER).as_km()).abs() < 0.01);
+    assert!(at.off.as_km() < 0.01);
+
+    // north of the middle of the first leg
+    let at = route.locate(&LatLon { lat: 42.40, lon: -71.43 });
+    assert!((at.along.as_km() - 31.0).abs() < 2.0, "{}", at.along);
+    assert!((at.off.as_km() - 9.7).abs() < 0.5, "{}", at.off);
+
+    // past the end
+    let at = route.locate(&LatLon { lat: 42.1015, lon: -73.0 });
+    assert!((at.along.as_km() - route.length().as_km()).abs() < 0.01);
+    let past = SPRINGFIELD.distance(&LatLon { lat: 42.1015, lon: -73.0 });
+    assert!((at.off.as_km() - past.as_km()).abs() < 0.01);
+}
+
+#[test]
+fn corridor_and_fit() {
+    let route = Route::new(vec![BOSTON, SPRINGFIELD]).unwrap();
+    let items = [
+        SPRINGFIELD,
+        LatLon { lat: 42.30, lon: -71.80 },
+        BOSTON,
+        LatLon { lat: 43.20, lon: -71.50 },
+        LatLon { lat: 42.33, lon: -71.40 },
+    ];
+
+    let found = corridor(&items, &route, "10mi".parse().unwrap());
+    let order: Vec<LatLon> = found.iter().map(|f| *f.item).collect();
+    assert_eq!(order, vec![BOSTON, items[4], items[1], SPRINGFIELD]);
+    assert_eq!(found[1].along.unit(), freqm::geo::DistanceUnit::Mi);
+
+    let kept: Vec<LatLon> = fit(found, 3).iter().map(|f| *f.item).collect();
+    assert_eq!(kept, vec![BOSTON, items[4], SPRINGFIELD]);
+}
+
+#[test]
+fn gpx() {
+    let route = Route::parse_gpx(
+        r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test">
+  <wpt lat="0" lon="0"><name>ignored, there is a track</name></wpt>
+  <trk><trkseg>
+    <trkpt lat="42.3601" lon="-71.0589"><ele>12</ele></trkpt>
+    <trkpt lon='-71.8023' lat='42.2626'/>
+  </trkseg></trk>
+</gpx>"#,
+    )
+    .unwrap();
+    assert_eq!(route.points(), &[BOSTON, WORCESTER]);
+
+    let route = Route::parse_gpx(r#"<gpx><wpt lat="42.3601" lon="-71.0589"/></gpx>"#).unwrap();
+    assert_eq!(route.points(), &[BOSTON]);
+
+    let e = Route::parse_gpx("<gpx>\n<rte>\n<rtept lat=\"42.1\"/></rte></gpx>").unwrap_err();
+    let location = e.location().unwrap();
+    assert_eq!((location.line, location.column), (Some(3), Some("rtept")));
+    assert!(Route::parse_gpx("<gpx></gpx>").is_err());
+}
+
+#[test]
+fn channel_list_for_radio() {
+    let record = csv::StringRecord::from(vec![
+        "1", "USA", "Boston", "MA", "W1BOS  B", "W1BOS  G", "442.5", "DUP+", "5", "DV", "OFF", "88.5Hz", "Yes",
+        "Exact", "42.3601", "-71.0589", "-05:00",
+    ]);
+    let line = freqm::icom_id51a::ChannelLine::try_from(record).unwrap();
+    let repeater = freqm::Repeater::try_from(&line).unwrap();
+    assert_eq!(repeater.callsign(), "W1BOS");
+    assert_eq!(repeater.input_freq().unwrap().to_string(), "447.5");
+
+    let model = freqm::models::find("TH-D74").unwrap();
+    assert!(model.supports(&repeater));
+    assert!(freqm::models::find("ft-991").is_err());
+
+    let mut out = Vec::new();
+    model.format.write(&mut out, &[&repeater]).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let row = text.lines().nth(1).unwrap();
+    assert!(row.contains("\tW1BOS\t"), "{}", row);
+    assert!(row.contains("\t442.5\t+\t5.0\tDV\t"), "{}", row);
+    assert!(row.contains("\tFN42li\t"), "{}", row);
+
+    let mut out = Vec::new();
+    freqm::models::find("id51a").unwrap().format.write(&mut out, &[&repeater]).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.lines().nth(1).unwrap().starts_with("1,Repeaters,Boston,MA,W1BOS  B,W1BOS  G,442.5000,DUP+,5,DV,"), "{}", text);
+}
@@ -1,3 +1,4 @@
+use freqm::error::MemoryError;
 use freqm::sparse_mem::SparseMem;
 
 #[test]
@@ -6,10 +7,10 @@ fn insert_extend() {
 
     assert_eq!(s.insert(0, &[1, 2]), Ok(()));
     assert_eq!(s.insert(10, &[50, 51]), Ok(()));
-    assert_eq!(s.insert(1, &[8, 9]), Err(()));
+    assert_eq!(s.insert(1, &[8, 9]), Err(MemoryError::Overlap { range: 1..3, existing: 0..2 }));
     assert_eq!(s.insert(2, &[3, 4]), Ok(()));
     
-    assert_eq!(s.ranges(), &[(0, [1, 2, 3, 4].to_vec()), (10, [50, 51].to_vec())]);
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[1, 2, 3, 4][..]), (10, &[50, 51][..])]);
 
     assert_eq!(s.get(2..3), Some(&[3][..]));
     assert_eq!(s.get(1..3), Some(&[2, 3][..]));
@@ -22,8 +23,98 @@ fn insert_prefix() {
     let mut s = SparseMem::default();
 
     assert_eq!(s.insert(2, &[3, 4]), Ok(()));
-    assert_eq!(s.insert(1, &[9, 10]), Err(()));
+    assert_eq!(s.insert(1, &[9, 10]), Err(MemoryError::Overlap { range: 1..3, existing: 2..4 }));
     assert_eq!(s.insert(0, &[1, 2]), Ok(()));
     
-    assert_eq!(s.ranges(), &[(0, [1, 2, 3, 4].to_vec())]);
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[1, 2, 3, 4][..])]);
+}
+
+#[test]
+fn insert_bridge() {
+    let mut s = SparseMem::default();
+
+    assert_eq!(s.insert(0, &[1, 2]), Ok(()));
+    assert_eq!(s.insert(4, &[5, 6]), Ok(()));
+    assert_eq!(s.insert(8, &[9]), Ok(()));
+    assert_eq!(s.insert(3, &[4, 5]), Err(MemoryError::Overlap { range: 3..5, existing: 4..6 }));
+    assert_eq!(s.insert(2, &[3, 4]), Ok(()));
+
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[1, 2, 3, 4, 5, 6][..]), (8, &[9][..])]);
+    assert_eq!(s.get(5..6), Some(&[6][..]));
+    assert_eq!(s.get(6..8), None);
+    assert_eq!(&s[8..9], &[9]);
+}
+
+#[test]
+fn insert_overflow() {
+    let mut s = SparseMem::default();
+
+    assert_eq!(s.insert(u64::MAX, &[1, 2]), Err(MemoryError::AddressOverflow { addr: u64::MAX, len: 2 }));
+    assert_eq!(s.insert(u64::MAX - 1, &[1]), Ok(()));
+}
+
+#[test]
+fn write_overwrites() {
+    let mut s = SparseMem::default();
+
+    s.insert(0, &[1, 2, 3, 4]).unwrap();
+    s.insert(8, &[9, 10]).unwrap();
+
+    // patch inside a range
+    assert_eq!(s.write(1, &[20, 30]), Ok(()));
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[1, 20, 30, 4][..]), (8, &[9, 10][..])]);
+
+    // spanning a gap merges everything
+    assert_eq!(s.write(3, &[40, 5, 6, 7, 8, 90]), Ok(()));
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[1, 20, 30, 40, 5, 6, 7, 8, 90, 10][..])]);
+}
+
+#[test]
+fn remove_splits() {
+    let mut s = SparseMem::default();
+
+    s.insert(0, &[0, 1, 2, 3, 4, 5]).unwrap();
+    s.insert(10, &[10, 11]).unwrap();
+
+    s.remove(2..4);
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[0, 1][..]), (4, &[4, 5][..]), (10, &[10, 11][..])]);
+
+    s.remove(5..11);
+    assert_eq!(s.ranges().collect::<Vec<_>>(), vec![(0, &[0, 1][..]), (4, &[4][..]), (11, &[11][..])]);
+
+    // nothing there, and empty
+    s.remove(6..8);
+    s.remove(1..1);
+    assert_eq!(s.ranges().count(), 3);
+
+    s.remove(0..100);
+    assert_eq!(s.ranges().count(), 0);
+}
+
+#[test]
+fn gaps() {
+    let mut s = SparseMem::default();
+
+    s.insert(2, &[2, 3]).unwrap();
+    s.insert(6, &[6]).unwrap();
+
+    assert_eq!(s.gaps(0..10).collect::<Vec<_>>(), vec![0..2, 4..6, 7..10]);
+    assert_eq!(s.gaps(3..6).collect::<Vec<_>>(), vec![4..6]);
+    assert_eq!(s.gaps(2..4).count(), 0);
+    assert_eq!(s.gaps(5..5).count(), 0);
+    assert_eq!(SparseMem::default().gaps(1..3).collect::<Vec<_>>(), vec![1..3]);
+}
+
+#[test]
+fn read_across_gaps() {
+    let mut s = SparseMem::default();
+
+    s.insert(2, &[2, 3]).unwrap();
+    s.insert(6, &[6]).unwrap();
+
+    assert_eq!(s.read(2..4), Ok(&[2, 3][..]));
+    assert_eq!(s.read(3..7), Err(MemoryError::Unpopulated { gap: 4..6 }));
+    assert_eq!(s.read(0..1), Err(MemoryError::Unpopulated { gap: 0..1 }));
+    assert_eq!(s.read_filled(0..8, 0xff), vec![0xff, 0xff, 2, 3, 0xff, 0xff, 6, 0xff]);
+    assert_eq!(s.read_filled(3..4, 0xff), vec![3]);
 }
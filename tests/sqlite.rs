@@ -0,0 +1,94 @@
+use freqm::channel_file::ChannelRecord;
+use freqm::formats::find;
+use freqm::import::OnError;
+use freqm::repeater_db::{RepeaterDb, Source};
+use freqm::{Band, Repeater};
+
+mod common;
+
+fn records(repeaters: &[Repeater]) -> Vec<ChannelRecord> {
+    repeaters.iter().map(ChannelRecord::from).collect()
+}
+
+fn temp(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("freqm-{}-{}.sqlite", name, std::process::id()))
+}
+
+#[test]
+fn save_and_load() {
+    let (ne, report) = find("ne").unwrap().load("data/NERepeaters.csv", OnError::Continue).unwrap();
+    assert!(report.skipped.is_empty(), "{}", report.skipped[0].error);
+    assert_eq!(ne.len(), 1002);
+    let (at878, report) = find("at878").unwrap().load("data/at878uvii_3.06_export_channels.csv", OnError::Continue).unwrap();
+    assert!(report.skipped.is_empty(), "{}", report.skipped[0].error);
+    let all: Vec<&Repeater> = ne.iter().chain(&at878).collect();
+
+    let path = temp("save");
+    freqm::sqlite::save(&path, all.iter().map(|&r| (r, &[][..]))).unwrap();
+    let loaded = freqm::sqlite::load(&path).unwrap();
+    assert_eq!(records(&loaded), all.iter().map(|&r| ChannelRecord::from(r)).collect::<Vec<_>>());
+
+    // towns are stored once, and the derived columns can be queried
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap();
+    assert_eq!(count("SELECT count(*) FROM channels"), all.len() as i64);
+    assert!(count("SELECT count(*) FROM places") < count("SELECT count(*) FROM channels WHERE place_id IS NOT NULL"));
+    let two_meter = all.iter().filter(|r| Band::of(r.output_freq()) == Some(Band::M2)).count();
+    assert_eq!(count("SELECT count(*) FROM channels WHERE band = '2m'"), two_meter as i64);
+
+    // saving again replaces the channels
+    freqm::sqlite::save(&path, at878.iter().map(|r| (r, &[][..]))).unwrap();
+    assert_eq!(freqm::sqlite::load(&path).unwrap().len(), at878.len());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn sources() {
+    let lines = r#""146.790","-","MA","Weston","","N1BE","146.2","",,"Middlesex","4136","","","2016/02/15",
+"147.000","+","MA","Acton","","K1ABC","100.0","","","Middlesex","","","",
+"#;
+    let ne = common::ne(lines);
+    // the first repeater again, as if from another listing
+    let icom = common::ne(lines.lines().next().unwrap());
+    let mut db = RepeaterDb::new(vec![]);
+    for r in ne {
+        db.add(Source::Ne, r);
+    }
+    for r in icom {
+        db.add(Source::Icom, r);
+    }
+
+    let path = temp("sources");
+    let merged = db.repeaters();
+    freqm::sqlite::save(&path, merged.iter().zip(db.entries().iter().map(|e| e.sources.as_slice()))).unwrap();
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let mut stmt = conn
+        .prepare("SELECT c.callsign, s.source FROM channels c JOIN channel_sources s ON s.channel_id = c.id ORDER BY c.id, s.source")
+        .unwrap();
+    let rows: Vec<(String, String)> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().map(Result::unwrap).collect();
+    let expected = [("N1BE", "icom"), ("N1BE", "ne"), ("K1ABC", "ne")];
+    assert_eq!(rows, expected.map(|(c, s)| (c.to_owned(), s.to_owned())));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn errors() {
+    let (ne, _) = find("ne").unwrap().load("data/NERepeaters.csv", OnError::Continue).unwrap();
+    let path = temp("errors");
+    freqm::sqlite::save(&path, ne.iter().take(3).map(|r| (r, &[][..]))).unwrap();
+    let conn = rusqlite::Connection::open(&path).unwrap();
+
+    conn.execute("UPDATE channels SET code_in = 'tone' WHERE id = 2", []).unwrap();
+    let e = freqm::sqlite::load(&path).unwrap_err();
+    let location = e.location().unwrap();
+    assert_eq!((location.record, location.column), (Some(2), Some("code_in")), "{}", e);
+    assert_eq!(location.file.as_deref(), Some(path.as_path()));
+
+    conn.pragma_update(None, "user_version", 2).unwrap();
+    let e = freqm::sqlite::load(&path).unwrap_err();
+    assert!(e.to_string().contains("version 2"), "{}", e);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(freqm::sqlite::load(temp("missing")).is_err());
+    assert!(!temp("missing").exists());
+}